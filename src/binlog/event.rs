@@ -0,0 +1,839 @@
+//! Decoding of [binlog events](https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_replication_binlog_event.html)
+
+use std::sync::Arc;
+
+use bytes::{Buf, Bytes};
+
+use super::{
+    gtid::{GtidSet, MariaDbGtid, MariaDbGtidList, ParseGtidError, Uuid},
+    json::JsonError,
+    rows::{RowsEvent, TableMapEvent, TableMaps},
+};
+use crate::{
+    protocol::{ColumnType, ServerVersion},
+    BytesExt,
+};
+
+/// The size of the common header of every event since binlog version 4
+pub const EVENT_HEADER_LEN: usize = 19;
+
+/// The size of the CRC32 checksum trailing events when checksums are enabled
+pub const CHECKSUM_LEN: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EventError {
+    #[error("the {0:?} event is truncated")]
+    Truncated(EventType),
+
+    #[error("the event header is truncated")]
+    TruncatedHeader,
+
+    #[error("the event size {size} does not match the {actual} bytes received")]
+    Size { size: u32, actual: usize },
+
+    #[error("the event size {0} exceeds the largest max_allowed_packet of 1 GiB")]
+    TooLarge(u32),
+
+    #[error(
+        "the checksum of the {event_type:?} event is {actual:#010x}, expected {expected:#010x}"
+    )]
+    Checksum {
+        event_type: EventType,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[error("unsupported binlog checksum algorithm {0}")]
+    ChecksumAlgorithm(u8),
+
+    #[error("the gtid set of the event is invalid")]
+    GtidSet(#[from] ParseGtidError),
+
+    #[error("unknown column type {0} in a table map")]
+    ColumnType(u8),
+
+    #[error("unsupported column type {0:?} in a row image")]
+    UnsupportedColumnType(ColumnType),
+
+    #[error("invalid metadata {metadata} for a {column_type:?} column")]
+    ColumnMetadata {
+        column_type: ColumnType,
+        metadata: u16,
+    },
+
+    #[error("the rows event refers to the unmapped table id {0}")]
+    UnknownTable(u64),
+
+    #[error("the rows event has {actual} columns but table id {table_id} maps {expected}")]
+    ColumnCount {
+        table_id: u64,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error(transparent)]
+    Json(#[from] JsonError),
+}
+
+macro_rules! event_types {
+    ($($name:ident = $value:literal,)*) => {
+        /// The type code of an event
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum EventType {
+            $($name,)*
+            Other(u8),
+        }
+
+        impl From<u8> for EventType {
+            fn from(value: u8) -> Self {
+                match value {
+                    $($value => EventType::$name,)*
+                    other => EventType::Other(other),
+                }
+            }
+        }
+
+        impl From<EventType> for u8 {
+            fn from(value: EventType) -> Self {
+                match value {
+                    $(EventType::$name => $value,)*
+                    EventType::Other(other) => other,
+                }
+            }
+        }
+    };
+}
+
+event_types! {
+    Unknown = 0,
+    StartV3 = 1,
+    Query = 2,
+    Stop = 3,
+    Rotate = 4,
+    Intvar = 5,
+    Slave = 7,
+    AppendBlock = 9,
+    DeleteFile = 11,
+    Rand = 13,
+    UserVar = 14,
+    FormatDescription = 15,
+    Xid = 16,
+    BeginLoadQuery = 17,
+    ExecuteLoadQuery = 18,
+    TableMap = 19,
+    WriteRowsV1 = 23,
+    UpdateRowsV1 = 24,
+    DeleteRowsV1 = 25,
+    Incident = 26,
+    Heartbeat = 27,
+    Ignorable = 28,
+    RowsQuery = 29,
+    WriteRowsV2 = 30,
+    UpdateRowsV2 = 31,
+    DeleteRowsV2 = 32,
+    Gtid = 33,
+    AnonymousGtid = 34,
+    PreviousGtids = 35,
+    TransactionContext = 36,
+    ViewChange = 37,
+    XaPrepare = 38,
+    PartialUpdateRows = 39,
+    TransactionPayload = 40,
+    HeartbeatV2 = 41,
+    MariaDbAnnotateRows = 160,
+    MariaDbBinlogCheckpoint = 161,
+    MariaDbGtid = 162,
+    MariaDbGtidList = 163,
+    MariaDbStartEncryption = 164,
+}
+
+/// The checksum algorithm announced by the format description event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Off,
+    Crc32,
+}
+
+impl TryFrom<u8> for ChecksumAlgorithm {
+    type Error = EventError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ChecksumAlgorithm::Off),
+            1 => Ok(ChecksumAlgorithm::Crc32),
+            other => Err(EventError::ChecksumAlgorithm(other)),
+        }
+    }
+}
+
+/// The common header of every event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventHeader {
+    pub timestamp: u32,
+    pub event_type: EventType,
+    pub server_id: u32,
+    pub event_size: u32,
+    /// The position of the next event in the binlog, 0 for artificial events
+    pub log_pos: u32,
+    pub flags: u16,
+}
+
+impl EventHeader {
+    /// The event was not written to the binlog but generated for the stream, like the rotate
+    /// event starting a dump
+    pub const ARTIFICIAL: u16 = 0x20;
+
+    fn decode(buf: &mut Bytes) -> Result<Self, EventError> {
+        if buf.len() < EVENT_HEADER_LEN {
+            return Err(EventError::TruncatedHeader);
+        }
+        Ok(Self {
+            timestamp: buf.get_u32_le(),
+            event_type: buf.get_u8().into(),
+            server_id: buf.get_u32_le(),
+            event_size: buf.get_u32_le(),
+            log_pos: buf.get_u32_le(),
+            flags: buf.get_u16_le(),
+        })
+    }
+
+    pub fn is_artificial(&self) -> bool {
+        self.flags & Self::ARTIFICIAL != 0
+    }
+}
+
+/// A decoded event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub header: EventHeader,
+    pub data: EventData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventData {
+    Rotate(RotateEvent),
+    FormatDescription(FormatDescriptionEvent),
+    Query(QueryEvent),
+    Xid(XidEvent),
+    Heartbeat(HeartbeatEvent),
+    /// A MySQL GTID or anonymous GTID event, starting a transaction
+    Gtid(GtidEvent),
+    /// The GTIDs executed before the current binlog file
+    PreviousGtids(GtidSet),
+    /// A MariaDB GTID event, starting a transaction
+    MariaDbGtid(MariaDbGtidEvent),
+    /// The MariaDB GTID state at the start of the current binlog file
+    MariaDbGtidList(MariaDbGtidList),
+    /// Maps a table id to the table of the following rows events
+    TableMap(Arc<TableMapEvent>),
+    /// Rows written, updated or deleted in row based replication
+    Rows(RowsEvent),
+    /// An event this crate does not decode, with its body without the checksum
+    Other(Bytes),
+}
+
+/// Switches the stream to another binlog file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotateEvent {
+    pub position: u64,
+    pub next_file: String,
+}
+
+/// Describes the format of the following events, written first in every binlog file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatDescriptionEvent {
+    pub binlog_version: u16,
+    pub server_version: String,
+    pub create_timestamp: u32,
+    pub header_length: u8,
+    /// The post header length of every event type, indexed by type code minus one
+    pub post_header_lengths: Vec<u8>,
+    pub checksum: ChecksumAlgorithm,
+}
+
+impl FormatDescriptionEvent {
+    /// The post header length of the given event type
+    pub fn post_header_length(&self, event_type: EventType) -> Option<u8> {
+        let index = u8::from(event_type).checked_sub(1)?;
+        self.post_header_lengths.get(index as usize).copied()
+    }
+}
+
+/// A statement written in statement based replication, and the `BEGIN` of row based
+/// transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryEvent {
+    pub thread_id: u32,
+    pub execution_time: u32,
+    pub error_code: u16,
+    pub status_vars: Bytes,
+    pub schema: String,
+    pub query: String,
+}
+
+/// The commit of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XidEvent {
+    pub xid: u64,
+}
+
+/// Sent by the server when no event was written for the heartbeat period
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatEvent {
+    pub file: String,
+    /// The position in the file, which MySQL 8 sends in the body of its v2 heartbeats
+    pub position: u64,
+}
+
+/// Starts a MySQL transaction, with its GTID unless it is anonymous
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GtidEvent {
+    pub flags: u8,
+    pub uuid: Uuid,
+    pub gno: u64,
+    /// The logical clock of multi threaded replicas, since MySQL 5.7
+    pub last_committed: Option<i64>,
+    pub sequence_number: Option<i64>,
+}
+
+impl GtidEvent {
+    /// The transaction may have changed non transactional tables
+    pub const MAY_HAVE_SBR: u8 = 0x01;
+
+    /// Anonymous GTID events have no uuid and number
+    pub fn is_anonymous(&self) -> bool {
+        self.gno == 0
+    }
+}
+
+/// Starts a MariaDB transaction or event group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MariaDbGtidEvent {
+    pub gtid: MariaDbGtid,
+    pub flags: u8,
+    /// Shared by transactions committed together, for parallel replication
+    pub commit_id: Option<u64>,
+}
+
+impl MariaDbGtidEvent {
+    /// The group is a single statement without a terminating XID or COMMIT
+    pub const STANDALONE: u8 = 0x01;
+    pub const GROUP_COMMIT_ID: u8 = 0x02;
+    /// The group is a DDL statement, committed by the statement itself
+    pub const DDL: u8 = 0x20;
+
+    pub fn is_standalone(&self) -> bool {
+        self.flags & Self::STANDALONE != 0
+    }
+
+    pub fn is_ddl(&self) -> bool {
+        self.flags & Self::DDL != 0
+    }
+}
+
+/// Decodes events, keeping the format description and checksum algorithm of the binlog
+#[derive(Debug, Clone, Default)]
+pub struct EventDecoder {
+    checksum: ChecksumAlgorithm,
+    verify_checksum: bool,
+    format: Option<FormatDescriptionEvent>,
+    tables: TableMaps,
+}
+
+impl EventDecoder {
+    pub fn new(checksum: ChecksumAlgorithm, verify_checksum: bool) -> Self {
+        Self {
+            checksum,
+            verify_checksum,
+            format: None,
+            tables: TableMaps::default(),
+        }
+    }
+
+    /// The last format description event decoded
+    pub fn format(&self) -> Option<&FormatDescriptionEvent> {
+        self.format.as_ref()
+    }
+
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.checksum
+    }
+
+    /// Decodes a whole event, header included
+    pub fn decode(&mut self, mut bytes: Bytes) -> Result<Event, EventError> {
+        let raw = bytes.clone();
+        let header = EventHeader::decode(&mut bytes)?;
+        if header.event_size as usize != raw.len() {
+            return Err(EventError::Size {
+                size: header.event_size,
+                actual: raw.len(),
+            });
+        }
+
+        // A format description event carries the checksum bytes whenever it has the
+        // algorithm, even when the algorithm is off
+        let (checksum, has_checksum) = if header.event_type == EventType::FormatDescription {
+            match format_description_checksum(&bytes)? {
+                Some(checksum) => (checksum, true),
+                None => (ChecksumAlgorithm::Off, false),
+            }
+        } else {
+            (self.checksum, self.checksum == ChecksumAlgorithm::Crc32)
+        };
+        if has_checksum {
+            if bytes.len() < CHECKSUM_LEN {
+                return Err(EventError::Truncated(header.event_type));
+            }
+            let body_len = bytes.len() - CHECKSUM_LEN;
+            let expected = (&bytes[body_len..]).get_u32_le();
+            if checksum == ChecksumAlgorithm::Crc32 && self.verify_checksum {
+                let actual = crc32fast::hash(&raw[..raw.len() - CHECKSUM_LEN]);
+                if actual != expected {
+                    return Err(EventError::Checksum {
+                        event_type: header.event_type,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+            bytes.truncate(body_len);
+        }
+
+        let data = self.decode_data(&header, bytes)?;
+        if let EventData::FormatDescription(format) = &data {
+            self.checksum = format.checksum;
+            self.format = Some(format.clone());
+        }
+        Ok(Event { header, data })
+    }
+
+    fn decode_data(
+        &mut self,
+        header: &EventHeader,
+        mut buf: Bytes,
+    ) -> Result<EventData, EventError> {
+        let event_type = header.event_type;
+        let truncated = || EventError::Truncated(event_type);
+        let data = match event_type {
+            EventType::Rotate => {
+                ensure(&buf, 8, event_type)?;
+                EventData::Rotate(RotateEvent {
+                    position: buf.get_u64_le(),
+                    next_file: lossy(&buf),
+                })
+            }
+            EventType::FormatDescription => {
+                ensure(&buf, 57, event_type)?;
+                let binlog_version = buf.get_u16_le();
+                let server_version = buf.split_to(50);
+                let server_version = lossy(
+                    &server_version[..server_version
+                        .iter()
+                        .position(|&b| b == 0)
+                        .unwrap_or(server_version.len())],
+                );
+                let create_timestamp = buf.get_u32_le();
+                let header_length = buf.get_u8();
+                let checksum = if has_checksum_algorithm(&server_version) {
+                    let alg = buf.len().checked_sub(1).ok_or_else(truncated)?;
+                    let checksum = ChecksumAlgorithm::try_from(buf[alg])?;
+                    buf.truncate(alg);
+                    checksum
+                } else {
+                    ChecksumAlgorithm::Off
+                };
+                EventData::FormatDescription(FormatDescriptionEvent {
+                    binlog_version,
+                    server_version,
+                    create_timestamp,
+                    header_length,
+                    post_header_lengths: buf.to_vec(),
+                    checksum,
+                })
+            }
+            EventType::Query => {
+                ensure(&buf, 13, event_type)?;
+                let thread_id = buf.get_u32_le();
+                let execution_time = buf.get_u32_le();
+                let schema_len = buf.get_u8() as usize;
+                let error_code = buf.get_u16_le();
+                let status_vars_len = buf.get_u16_le() as usize;
+                ensure(&buf, status_vars_len + schema_len + 1, event_type)?;
+                let status_vars = buf.split_to(status_vars_len);
+                let schema = lossy(&buf.split_to(schema_len));
+                buf.advance(1);
+                EventData::Query(QueryEvent {
+                    thread_id,
+                    execution_time,
+                    error_code,
+                    status_vars,
+                    schema,
+                    query: lossy(&buf),
+                })
+            }
+            EventType::Xid => {
+                ensure(&buf, 8, event_type)?;
+                EventData::Xid(XidEvent {
+                    xid: buf.get_u64_le(),
+                })
+            }
+            EventType::Heartbeat => EventData::Heartbeat(HeartbeatEvent {
+                file: lossy(&buf),
+                position: header.log_pos as u64,
+            }),
+            EventType::HeartbeatV2 => {
+                EventData::Heartbeat(decode_heartbeat_v2(buf).map_err(|_| truncated())?)
+            }
+            EventType::Gtid | EventType::AnonymousGtid => {
+                ensure(&buf, 25, event_type)?;
+                let flags = buf.get_u8();
+                let mut uuid = [0; 16];
+                buf.copy_to_slice(&mut uuid);
+                let gno = buf.get_u64_le();
+                // The logical timestamps follow their type code 2
+                let (last_committed, sequence_number) = if buf.len() >= 17 && buf[0] == 2 {
+                    buf.advance(1);
+                    (Some(buf.get_i64_le()), Some(buf.get_i64_le()))
+                } else {
+                    (None, None)
+                };
+                EventData::Gtid(GtidEvent {
+                    flags,
+                    uuid: Uuid(uuid),
+                    gno,
+                    last_committed,
+                    sequence_number,
+                })
+            }
+            EventType::PreviousGtids => EventData::PreviousGtids(GtidSet::decode(buf)?),
+            EventType::MariaDbGtid => {
+                ensure(&buf, 13, event_type)?;
+                let sequence = buf.get_u64_le();
+                let domain_id = buf.get_u32_le();
+                let flags = buf.get_u8();
+                let commit_id = if flags & MariaDbGtidEvent::GROUP_COMMIT_ID != 0 {
+                    ensure(&buf, 8, event_type)?;
+                    Some(buf.get_u64_le())
+                } else {
+                    None
+                };
+                EventData::MariaDbGtid(MariaDbGtidEvent {
+                    gtid: MariaDbGtid {
+                        domain_id,
+                        server_id: header.server_id,
+                        sequence,
+                    },
+                    flags,
+                    commit_id,
+                })
+            }
+            EventType::MariaDbGtidList => {
+                ensure(&buf, 4, event_type)?;
+                // The upper 4 bits hold flags
+                let count = buf.get_u32_le() & 0x0FFF_FFFF;
+                ensure(&buf, count as usize * 16, event_type)?;
+                let list = (0..count)
+                    .map(|_| MariaDbGtid {
+                        domain_id: buf.get_u32_le(),
+                        server_id: buf.get_u32_le(),
+                        sequence: buf.get_u64_le(),
+                    })
+                    .collect();
+                EventData::MariaDbGtidList(list)
+            }
+            EventType::TableMap => {
+                EventData::TableMap(self.tables.decode_table_map(self.format.as_ref(), buf)?)
+            }
+            EventType::WriteRowsV1
+            | EventType::UpdateRowsV1
+            | EventType::DeleteRowsV1
+            | EventType::WriteRowsV2
+            | EventType::UpdateRowsV2
+            | EventType::DeleteRowsV2
+            | EventType::PartialUpdateRows => EventData::Rows(self.tables.decode_rows(
+                self.format.as_ref(),
+                event_type,
+                buf,
+            )?),
+            _ => EventData::Other(buf),
+        };
+        Ok(data)
+    }
+}
+
+/// Ensures the event body holds at least `len` more bytes
+pub(crate) fn ensure(buf: &[u8], len: usize, event_type: EventType) -> Result<(), EventError> {
+    if buf.len() < len {
+        return Err(EventError::Truncated(event_type));
+    }
+    Ok(())
+}
+
+/// Decodes the fields of a MySQL 8 heartbeat, each a type and a length followed by the value
+fn decode_heartbeat_v2(mut buf: Bytes) -> Result<HeartbeatEvent, std::io::Error> {
+    const END_MARK: u64 = 0;
+    const FILE: u64 = 1;
+    const POSITION: u64 = 2;
+
+    let mut heartbeat = HeartbeatEvent {
+        file: String::new(),
+        position: 0,
+    };
+    while !buf.is_empty() {
+        let field = buf.get_len_encoded_int()?;
+        if field == END_MARK {
+            break;
+        }
+        let mut value = buf.get_len_encoded_bytes()?;
+        match field {
+            FILE => heartbeat.file = lossy(&value),
+            POSITION => heartbeat.position = value.get_len_encoded_int()?,
+            _ => {}
+        }
+    }
+    Ok(heartbeat)
+}
+
+pub(crate) fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Servers since 5.6.1 end the format description event with the checksum algorithm, followed
+/// by a checksum if it is enabled
+fn has_checksum_algorithm(server_version: &str) -> bool {
+    server_version
+        .parse::<ServerVersion>()
+        .map(|v| (v.major(), v.minor(), v.patch()) >= (5, 6, 1))
+        .unwrap_or(false)
+}
+
+/// The checksum algorithm of a format description event, `None` for servers that predate it
+fn format_description_checksum(body: &[u8]) -> Result<Option<ChecksumAlgorithm>, EventError> {
+    let server_version = body
+        .get(2..52)
+        .ok_or(EventError::Truncated(EventType::FormatDescription))?;
+    let end = server_version.iter().position(|&b| b == 0).unwrap_or(50);
+    if !has_checksum_algorithm(&lossy(&server_version[..end])) {
+        return Ok(None);
+    }
+    let alg = body
+        .len()
+        .checked_sub(CHECKSUM_LEN + 1)
+        .ok_or(EventError::Truncated(EventType::FormatDescription))?;
+    ChecksumAlgorithm::try_from(body[alg]).map(Some)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bytes::{BufMut, BytesMut};
+
+    use super::*;
+    use crate::BufMutExt;
+
+    /// Frames a body into an event, appending a CRC32 checksum when asked to
+    pub(crate) fn frame(event_type: EventType, log_pos: u32, body: &[u8], crc: bool) -> Bytes {
+        let size = EVENT_HEADER_LEN + body.len() + if crc { CHECKSUM_LEN } else { 0 };
+        let mut bytes = BytesMut::new();
+        bytes.put_u32_le(1_700_000_000);
+        bytes.put_u8(event_type.into());
+        bytes.put_u32_le(1);
+        bytes.put_u32_le(size as u32);
+        bytes.put_u32_le(log_pos);
+        bytes.put_u16_le(0);
+        bytes.put_slice(body);
+        if crc {
+            let crc = crc32fast::hash(&bytes);
+            bytes.put_u32_le(crc);
+        }
+        bytes.freeze()
+    }
+
+    pub(crate) fn format_description(checksum: ChecksumAlgorithm) -> Vec<u8> {
+        let mut body = BytesMut::new();
+        body.put_u16_le(4);
+        let mut version = [0; 50];
+        version[..6].copy_from_slice(b"8.0.36");
+        body.put_slice(&version);
+        body.put_u32_le(0);
+        body.put_u8(EVENT_HEADER_LEN as u8);
+        body.put_slice(&[
+            56, 13, 0, 8, 0, 18, 0, 4, 4, 4, 4, 18, 0, 0, 95, 0, 4, 26, 8,
+        ]);
+        body.put_u8(checksum as u8);
+        // The checksum bytes are written even when the algorithm is off
+        if checksum == ChecksumAlgorithm::Off {
+            body.put_u32_le(0);
+        }
+        body.to_vec()
+    }
+
+    #[test]
+    fn events_should_be_decoded_with_checksums() {
+        let mut decoder = EventDecoder::new(ChecksumAlgorithm::Off, true);
+        let event = decoder
+            .decode(frame(
+                EventType::FormatDescription,
+                120,
+                &format_description(ChecksumAlgorithm::Crc32),
+                true,
+            ))
+            .unwrap();
+        let EventData::FormatDescription(format) = event.data else {
+            panic!("expected a format description event");
+        };
+        assert_eq!(format.server_version, "8.0.36");
+        assert_eq!(format.checksum, ChecksumAlgorithm::Crc32);
+        assert_eq!(format.post_header_length(EventType::Query), Some(13));
+        assert_eq!(decoder.checksum(), ChecksumAlgorithm::Crc32);
+
+        let mut query = BytesMut::new();
+        query.put_u32_le(7);
+        query.put_u32_le(0);
+        query.put_u8(4);
+        query.put_u16_le(0);
+        query.put_u16_le(0);
+        query.put_slice(b"shop\0BEGIN");
+        let event = decoder
+            .decode(frame(EventType::Query, 200, &query, true))
+            .unwrap();
+        assert_eq!(event.header.log_pos, 200);
+        let EventData::Query(query) = event.data else {
+            panic!("expected a query event");
+        };
+        assert_eq!(query.schema, "shop");
+        assert_eq!(query.query, "BEGIN");
+
+        let event = decoder
+            .decode(frame(EventType::Xid, 231, &42u64.to_le_bytes(), true))
+            .unwrap();
+        assert_eq!(event.data, EventData::Xid(XidEvent { xid: 42 }));
+    }
+
+    #[test]
+    fn format_descriptions_should_skip_the_checksum_bytes_when_off() {
+        let mut decoder = EventDecoder::new(ChecksumAlgorithm::Crc32, true);
+        let event = decoder
+            .decode(frame(
+                EventType::FormatDescription,
+                120,
+                &format_description(ChecksumAlgorithm::Off),
+                false,
+            ))
+            .unwrap();
+        let EventData::FormatDescription(format) = event.data else {
+            panic!("expected a format description event");
+        };
+        assert_eq!(format.checksum, ChecksumAlgorithm::Off);
+        assert_eq!(format.post_header_lengths.len(), 19);
+        assert_eq!(decoder.checksum(), ChecksumAlgorithm::Off);
+
+        let event = decoder
+            .decode(frame(EventType::Xid, 151, &42u64.to_le_bytes(), false))
+            .unwrap();
+        assert_eq!(event.data, EventData::Xid(XidEvent { xid: 42 }));
+    }
+
+    #[test]
+    fn gtid_events_should_be_decoded() {
+        let mut decoder = EventDecoder::new(ChecksumAlgorithm::Off, true);
+        let uuid: Uuid = "3e11fa47-71ca-11e1-9e33-c80aa9429562".parse().unwrap();
+
+        let mut body = BytesMut::new();
+        body.put_u8(1);
+        body.put_slice(&uuid.0);
+        body.put_u64_le(23);
+        body.put_u8(2);
+        body.put_i64_le(10);
+        body.put_i64_le(11);
+        let event = decoder
+            .decode(frame(EventType::Gtid, 300, &body, false))
+            .unwrap();
+        assert_eq!(
+            event.data,
+            EventData::Gtid(GtidEvent {
+                flags: 1,
+                uuid,
+                gno: 23,
+                last_committed: Some(10),
+                sequence_number: Some(11),
+            })
+        );
+
+        let mut body = BytesMut::new();
+        body.put_u64_le(100);
+        body.put_u32_le(0);
+        body.put_u8(MariaDbGtidEvent::GROUP_COMMIT_ID);
+        body.put_u64_le(7);
+        let event = decoder
+            .decode(frame(EventType::MariaDbGtid, 400, &body, false))
+            .unwrap();
+        let EventData::MariaDbGtid(gtid) = event.data else {
+            panic!("expected a mariadb gtid event");
+        };
+        assert_eq!(gtid.gtid.to_string(), "0-1-100");
+        assert_eq!(gtid.commit_id, Some(7));
+    }
+
+    #[test]
+    fn heartbeats_should_carry_the_position() {
+        let mut decoder = EventDecoder::new(ChecksumAlgorithm::Off, true);
+        let event = decoder
+            .decode(frame(EventType::Heartbeat, 1234, b"binlog.000002", false))
+            .unwrap();
+        let heartbeat = HeartbeatEvent {
+            file: "binlog.000002".into(),
+            position: 1234,
+        };
+        assert_eq!(event.data, EventData::Heartbeat(heartbeat));
+
+        // The position does not fit the header of large files
+        let mut body = BytesMut::new();
+        body.put_len_encoded_int(1);
+        body.put_len_encoded_str("binlog.000002");
+        body.put_len_encoded_int(2);
+        body.put_len_encoded_int(9);
+        body.put_len_encoded_int(5_000_000_000);
+        body.put_len_encoded_int(0);
+        let event = decoder
+            .decode(frame(EventType::HeartbeatV2, 0, &body, false))
+            .unwrap();
+        let heartbeat = HeartbeatEvent {
+            file: "binlog.000002".into(),
+            position: 5_000_000_000,
+        };
+        assert_eq!(event.data, EventData::Heartbeat(heartbeat));
+
+        let event = decoder.decode(frame(EventType::HeartbeatV2, 0, &[2, 9], false));
+        assert!(matches!(
+            event,
+            Err(EventError::Truncated(EventType::HeartbeatV2))
+        ));
+    }
+
+    #[test]
+    fn corrupted_events_should_fail_checksum() {
+        let mut decoder = EventDecoder::new(ChecksumAlgorithm::Crc32, true);
+        let mut body = 4u64.to_le_bytes().to_vec();
+        body.extend_from_slice(b"binlog.000002");
+        let mut event = frame(EventType::Rotate, 0, &body, true).to_vec();
+        let event_len = event.len();
+        event[event_len - CHECKSUM_LEN - 1] ^= 1;
+        assert!(matches!(
+            decoder.decode(event.into()),
+            Err(EventError::Checksum { .. })
+        ));
+
+        let mut decoder = EventDecoder::new(ChecksumAlgorithm::Crc32, false);
+        let event = decoder
+            .decode(frame(EventType::Rotate, 0, &body, true))
+            .unwrap();
+        assert_eq!(
+            event.data,
+            EventData::Rotate(RotateEvent {
+                position: 4,
+                next_file: "binlog.000002".into(),
+            })
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Reads binlog and relay log files from disk, without a server
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::event::{ChecksumAlgorithm, Event, EventDecoder, EventError, EVENT_HEADER_LEN};
+
+/// The magic number starting every binlog file
+pub const BINLOG_MAGIC: &[u8; 4] = b"\xfebin";
+
+/// Events are bounded by the largest `max_allowed_packet`
+const MAX_EVENT_SIZE: usize = 1 << 30;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BinlogFileError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("the file does not start with the binlog magic number")]
+    Magic,
+
+    #[error("failed to decode the event at position {position}")]
+    Event {
+        position: u64,
+        #[source]
+        source: EventError,
+    },
+}
+
+/// Reads the events of a binlog file in order.
+///
+/// Events are decoded from the format description event at the start of the file, which is
+/// read before seeking. Row events need the table map events before them, so seek to the start
+/// of a transaction.
+#[derive(Debug)]
+pub struct BinlogReader<R> {
+    reader: R,
+    decoder: EventDecoder,
+    position: u64,
+    start_time: Option<u32>,
+    stop_time: Option<u32>,
+}
+
+impl BinlogReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BinlogFileError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> BinlogReader<R> {
+    /// Reads the magic number at the start of the file
+    pub fn new(mut reader: R) -> Result<Self, BinlogFileError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != BINLOG_MAGIC {
+            return Err(BinlogFileError::Magic);
+        }
+        Ok(Self {
+            reader,
+            decoder: EventDecoder::new(ChecksumAlgorithm::Off, true),
+            position: BINLOG_MAGIC.len() as u64,
+            start_time: None,
+            stop_time: None,
+        })
+    }
+
+    /// Whether to verify the CRC32 checksum of events, on by default
+    pub fn verify_checksum(mut self, verify: bool) -> Self {
+        self.decoder = EventDecoder::new(self.decoder.checksum(), verify);
+        self
+    }
+
+    /// Skips the events written before the given unix timestamp
+    pub fn start_time(mut self, timestamp: u32) -> Self {
+        self.start_time = Some(timestamp);
+        self
+    }
+
+    /// Ends at the first event written at or after the given unix timestamp
+    pub fn stop_time(mut self, timestamp: u32) -> Self {
+        self.stop_time = Some(timestamp);
+        self
+    }
+
+    /// The position of the next event in the file
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Reads the next event in the time range, or `None` at the end of the file
+    pub fn next_event(&mut self) -> Result<Option<Event>, BinlogFileError> {
+        loop {
+            let Some(event) = self.read_event()? else {
+                return Ok(None);
+            };
+            let timestamp = event.header.timestamp;
+            if self.stop_time.is_some_and(|stop| timestamp >= stop) {
+                return Ok(None);
+            }
+            // The format description is kept since it describes the events that follow
+            if self.start_time.is_some_and(|start| timestamp < start)
+                && event.header.event_type != super::EventType::FormatDescription
+            {
+                continue;
+            }
+            return Ok(Some(event));
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event>, BinlogFileError> {
+        let mut header = [0; EVENT_HEADER_LEN];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let position = self.position;
+        let event_error = |source| BinlogFileError::Event { position, source };
+        let size = u32::from_le_bytes([header[9], header[10], header[11], header[12]]);
+        if (size as usize) < EVENT_HEADER_LEN {
+            return Err(event_error(EventError::TruncatedHeader));
+        }
+        if size as usize > MAX_EVENT_SIZE {
+            return Err(event_error(EventError::TooLarge(size)));
+        }
+        // Read through `take` so a corrupted size cannot allocate more than the file holds
+        let mut bytes = header.to_vec();
+        (&mut self.reader)
+            .take((size as usize - EVENT_HEADER_LEN) as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != size as usize {
+            let actual = bytes.len();
+            return Err(event_error(EventError::Size { size, actual }));
+        }
+        self.position += size as u64;
+
+        let event = self.decoder.decode(bytes.into()).map_err(event_error)?;
+        Ok(Some(event))
+    }
+}
+
+impl<R: Read + Seek> BinlogReader<R> {
+    /// Moves to the event at the given position of the file
+    pub fn seek(&mut self, position: u64) -> Result<(), BinlogFileError> {
+        if self.decoder.format().is_none() && self.position == BINLOG_MAGIC.len() as u64 {
+            self.read_event()?;
+        }
+        self.reader.seek(SeekFrom::Start(position))?;
+        self.position = position;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for BinlogReader<R> {
+    type Item = Result<Event, BinlogFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::binlog::{
+        event::tests::{format_description, frame},
+        EventData, EventType,
+    };
+
+    fn binlog() -> Vec<u8> {
+        let mut file = BINLOG_MAGIC.to_vec();
+        let format = frame(
+            EventType::FormatDescription,
+            0,
+            &format_description(ChecksumAlgorithm::Crc32),
+            true,
+        );
+        file.extend_from_slice(&format);
+        for xid in 1..=3u64 {
+            let position = (file.len() + EVENT_HEADER_LEN + 12) as u32;
+            let mut event = frame(EventType::Xid, position, &xid.to_le_bytes(), true).to_vec();
+            // One event per second
+            event[..4].copy_from_slice(&(1_700_000_000 + xid as u32).to_le_bytes());
+            let crc = crc32fast::hash(&event[..event.len() - 4]);
+            let len = event.len();
+            event[len - 4..].copy_from_slice(&crc.to_le_bytes());
+            file.extend_from_slice(&event);
+        }
+        file
+    }
+
+    fn xids(reader: BinlogReader<Cursor<Vec<u8>>>) -> Vec<u64> {
+        reader
+            .filter_map(|event| match event.unwrap().data {
+                EventData::Xid(xid) => Some(xid.xid),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn binlog_file_should_be_read_in_order() {
+        let reader = BinlogReader::new(Cursor::new(binlog())).unwrap();
+        assert_eq!(xids(reader), [1, 2, 3]);
+
+        let reader = BinlogReader::new(Cursor::new(binlog()))
+            .unwrap()
+            .start_time(1_700_000_002)
+            .stop_time(1_700_000_003);
+        assert_eq!(xids(reader), [2]);
+
+        let mut reader = BinlogReader::new(Cursor::new(binlog())).unwrap();
+        let third = binlog().len() as u64 - (EVENT_HEADER_LEN + 12) as u64;
+        reader.seek(third).unwrap();
+        assert_eq!(xids(reader), [3]);
+
+        let err = BinlogReader::new(Cursor::new(b"\xfebim".to_vec())).unwrap_err();
+        assert!(matches!(err, BinlogFileError::Magic));
+    }
+
+    #[test]
+    fn corrupted_sizes_should_fail() {
+        let mut file = binlog();
+        let len = file.len();
+        // The size of the last event, larger than the rest of the file
+        let last = len - (EVENT_HEADER_LEN + 12);
+        file[last + 9..last + 13].copy_from_slice(&1000u32.to_le_bytes());
+        let reader = BinlogReader::new(Cursor::new(file.clone())).unwrap();
+        let err = reader.last().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            BinlogFileError::Event {
+                source: EventError::Size { size: 1000, actual },
+                ..
+            } if actual == EVENT_HEADER_LEN + 12
+        ));
+
+        file[last + 9..last + 13].copy_from_slice(&u32::MAX.to_le_bytes());
+        let reader = BinlogReader::new(Cursor::new(file)).unwrap();
+        let err = reader.last().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            BinlogFileError::Event {
+                source: EventError::TooLarge(u32::MAX),
+                position,
+            } if position == last as u64
+        ));
+    }
+}
@@ -0,0 +1,317 @@
+//! GTIDs, to resume replication from the executed transactions instead of a file and position.
+//!
+//! MySQL identifies transactions by `uuid:number` and tracks them as [`GtidSet`]s of intervals,
+//! MariaDB by `domain-server-sequence` and tracks the last [`MariaDbGtid`] of every domain.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseGtidError {
+    #[error("invalid uuid {0}")]
+    Uuid(String),
+
+    #[error("invalid gtid interval {0}")]
+    Interval(String),
+
+    #[error("invalid mariadb gtid {0}")]
+    MariaDb(String),
+
+    #[error("the encoded gtid set is truncated")]
+    Truncated,
+}
+
+/// The uuid of the server that originated a MySQL transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid(pub [u8; 16]);
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = ParseGtidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseGtidError::Uuid(s.to_owned());
+        let hex: Vec<u8> = s.bytes().filter(|&b| b != b'-').collect();
+        if hex.len() != 32 {
+            return Err(invalid());
+        }
+        let mut uuid = [0; 16];
+        for (i, pair) in hex.chunks_exact(2).enumerate() {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            uuid[i] = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        }
+        Ok(Uuid(uuid))
+    }
+}
+
+/// A set of MySQL GTIDs, e.g. `3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GtidSet {
+    /// Sorted, disjoint and non adjacent inclusive intervals of every server
+    intervals: BTreeMap<Uuid, Vec<(u64, u64)>>,
+}
+
+impl GtidSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn contains(&self, uuid: &Uuid, gno: u64) -> bool {
+        self.intervals
+            .get(uuid)
+            .is_some_and(|intervals| intervals.iter().any(|&(s, e)| s <= gno && gno <= e))
+    }
+
+    /// The inclusive intervals of every server
+    pub fn iter(&self) -> impl Iterator<Item = (&Uuid, &[(u64, u64)])> {
+        self.intervals.iter().map(|(uuid, i)| (uuid, i.as_slice()))
+    }
+
+    /// Adds a single transaction
+    pub fn add(&mut self, uuid: Uuid, gno: u64) {
+        self.add_interval(uuid, gno, gno);
+    }
+
+    /// Adds the transactions `start..=end`
+    pub fn add_interval(&mut self, uuid: Uuid, start: u64, end: u64) {
+        let intervals = self.intervals.entry(uuid).or_default();
+        intervals.push((start.min(end), start.max(end)));
+        intervals.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+        for &(start, end) in intervals.iter() {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        *intervals = merged;
+    }
+
+    /// Encodes the set as sent in `COM_BINLOG_DUMP_GTID` and written in `PREVIOUS_GTIDS`
+    /// events, with exclusive interval ends
+    pub fn encode(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+        bytes.put_u64_le(self.intervals.len() as u64);
+        for (uuid, intervals) in &self.intervals {
+            bytes.put_slice(&uuid.0);
+            bytes.put_u64_le(intervals.len() as u64);
+            for &(start, end) in intervals {
+                bytes.put_u64_le(start);
+                bytes.put_u64_le(end + 1);
+            }
+        }
+        bytes.freeze()
+    }
+
+    pub fn decode(mut buf: Bytes) -> Result<Self, ParseGtidError> {
+        let mut set = GtidSet::new();
+        if buf.len() < 8 {
+            return Err(ParseGtidError::Truncated);
+        }
+        let sids = buf.get_u64_le();
+        for _ in 0..sids {
+            if buf.len() < 24 {
+                return Err(ParseGtidError::Truncated);
+            }
+            let mut uuid = [0; 16];
+            buf.copy_to_slice(&mut uuid);
+            let count = buf.get_u64_le();
+            for _ in 0..count {
+                if buf.len() < 16 {
+                    return Err(ParseGtidError::Truncated);
+                }
+                let start = buf.get_u64_le();
+                let end = buf.get_u64_le();
+                if end > start {
+                    set.add_interval(Uuid(uuid), start, end - 1);
+                }
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (uuid, intervals)) in self.intervals.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", uuid)?;
+            for &(start, end) in intervals {
+                if start == end {
+                    write!(f, ":{}", start)?;
+                } else {
+                    write!(f, ":{}-{}", start, end)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GtidSet {
+    type Err = ParseGtidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = GtidSet::new();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let mut fields = part.split(':');
+            let uuid = fields.next().unwrap_or_default().trim().parse()?;
+            for interval in fields {
+                let invalid = || ParseGtidError::Interval(interval.to_owned());
+                let (start, end) = interval.split_once('-').unwrap_or((interval, interval));
+                let start = start.trim().parse().map_err(|_| invalid())?;
+                let end = end.trim().parse().map_err(|_| invalid())?;
+                if start == 0 || end < start {
+                    return Err(invalid());
+                }
+                set.add_interval(uuid, start, end);
+            }
+        }
+        Ok(set)
+    }
+}
+
+/// A MariaDB GTID, `domain-server-sequence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MariaDbGtid {
+    pub domain_id: u32,
+    pub server_id: u32,
+    pub sequence: u64,
+}
+
+impl fmt::Display for MariaDbGtid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.domain_id, self.server_id, self.sequence)
+    }
+}
+
+impl FromStr for MariaDbGtid {
+    type Err = ParseGtidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseGtidError::MariaDb(s.to_owned());
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().ok_or_else(invalid);
+        Ok(Self {
+            domain_id: next()?.parse().map_err(|_| invalid())?,
+            server_id: next()?.parse().map_err(|_| invalid())?,
+            sequence: next()?.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// The last MariaDB GTID of every replication domain, as in `@@gtid_slave_pos` and
+/// `@slave_connect_state`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MariaDbGtidList {
+    gtids: Vec<MariaDbGtid>,
+}
+
+impl MariaDbGtidList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gtids.is_empty()
+    }
+
+    pub fn gtids(&self) -> &[MariaDbGtid] {
+        &self.gtids
+    }
+
+    /// Records the GTID as the last one of its domain
+    pub fn set(&mut self, gtid: MariaDbGtid) {
+        match self
+            .gtids
+            .iter_mut()
+            .find(|g| g.domain_id == gtid.domain_id)
+        {
+            Some(existing) => *existing = gtid,
+            None => self.gtids.push(gtid),
+        }
+    }
+}
+
+impl FromIterator<MariaDbGtid> for MariaDbGtidList {
+    fn from_iter<T: IntoIterator<Item = MariaDbGtid>>(iter: T) -> Self {
+        let mut list = Self::new();
+        for gtid in iter {
+            list.set(gtid);
+        }
+        list
+    }
+}
+
+impl fmt::Display for MariaDbGtidList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, gtid) in self.gtids.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", gtid)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MariaDbGtidList {
+    type Err = ParseGtidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+
+    #[test]
+    fn gtid_set_should_roundtrip() {
+        let set: GtidSet = format!("{UUID}:1-5:7:6, 00000000-0000-0000-0000-000000000001:3")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            set.to_string(),
+            format!("00000000-0000-0000-0000-000000000001:3,{UUID}:1-7")
+        );
+        assert!(set.contains(&UUID.parse().unwrap(), 6));
+        assert_eq!(GtidSet::decode(set.encode()).unwrap(), set);
+
+        assert!("nope:1-2".parse::<GtidSet>().is_err());
+        assert!(format!("{UUID}:5-1").parse::<GtidSet>().is_err());
+    }
+
+    #[test]
+    fn mariadb_gtid_list_should_keep_the_last_gtid_per_domain() {
+        let mut list: MariaDbGtidList = "0-1-100,1-2-5".parse().unwrap();
+        list.set("0-3-101".parse().unwrap());
+        assert_eq!(list.to_string(), "0-3-101,1-2-5");
+        assert!("0-1".parse::<MariaDbGtidList>().is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Converts the [binary JSON](https://dev.mysql.com/doc/dev/mysql-server/latest/json__binary_8h.html)
+//! of row events to JSON text
+
+use std::fmt::Write;
+
+use bytes::Buf;
+
+use super::rows::decode_decimal;
+use crate::value::{format_date_time, format_time};
+
+const SMALL_OBJECT: u8 = 0x00;
+const LARGE_OBJECT: u8 = 0x01;
+const SMALL_ARRAY: u8 = 0x02;
+const LARGE_ARRAY: u8 = 0x03;
+const LITERAL: u8 = 0x04;
+const INT16: u8 = 0x05;
+const UINT16: u8 = 0x06;
+const INT32: u8 = 0x07;
+const UINT32: u8 = 0x08;
+const INT64: u8 = 0x09;
+const UINT64: u8 = 0x0A;
+const DOUBLE: u8 = 0x0B;
+const STRING: u8 = 0x0C;
+const OPAQUE: u8 = 0x0F;
+
+/// The nesting limit of MySQL, which also bounds the recursion on malformed documents
+const MAX_DEPTH: usize = 100;
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid binary json")]
+pub struct JsonError;
+
+/// Converts a binary JSON document to JSON text, `null` for an empty document
+pub fn to_text(bytes: &[u8]) -> Result<String, JsonError> {
+    let mut out = String::new();
+    match bytes.split_first() {
+        Some((&value_type, data)) => write_value(&mut out, value_type, data, 0)?,
+        None => out.push_str("null"),
+    }
+    Ok(out)
+}
+
+fn write_value(
+    out: &mut String,
+    value_type: u8,
+    data: &[u8],
+    depth: usize,
+) -> Result<(), JsonError> {
+    match value_type {
+        SMALL_OBJECT => write_container(out, data, false, true, depth),
+        LARGE_OBJECT => write_container(out, data, true, true, depth),
+        SMALL_ARRAY => write_container(out, data, false, false, depth),
+        LARGE_ARRAY => write_container(out, data, true, false, depth),
+        LITERAL => write_literal(out, *data.first().ok_or(JsonError)?),
+        INT16 => write_number(out, read(data, 2)?.get_i16_le()),
+        UINT16 => write_number(out, read(data, 2)?.get_u16_le()),
+        INT32 => write_number(out, read(data, 4)?.get_i32_le()),
+        UINT32 => write_number(out, read(data, 4)?.get_u32_le()),
+        INT64 => write_number(out, read(data, 8)?.get_i64_le()),
+        UINT64 => write_number(out, read(data, 8)?.get_u64_le()),
+        DOUBLE => write_number(out, read(data, 8)?.get_f64_le()),
+        STRING => {
+            let (len, data) = read_variable_length(data)?;
+            let string = data.get(..len).ok_or(JsonError)?;
+            write_string(out, &String::from_utf8_lossy(string));
+            Ok(())
+        }
+        OPAQUE => {
+            let (&field_type, data) = data.split_first().ok_or(JsonError)?;
+            let (len, data) = read_variable_length(data)?;
+            write_opaque(out, field_type, data.get(..len).ok_or(JsonError)?)
+        }
+        _ => Err(JsonError),
+    }
+}
+
+fn write_container(
+    out: &mut String,
+    data: &[u8],
+    large: bool,
+    object: bool,
+    depth: usize,
+) -> Result<(), JsonError> {
+    if depth >= MAX_DEPTH {
+        return Err(JsonError);
+    }
+    let offset_size = if large { 4 } else { 2 };
+    let read_offset = |at: usize| -> Result<usize, JsonError> {
+        let mut bytes = data.get(at..at + offset_size).ok_or(JsonError)?;
+        Ok(if large {
+            bytes.get_u32_le() as usize
+        } else {
+            bytes.get_u16_le() as usize
+        })
+    };
+
+    let count = read_offset(0)?;
+    let key_entries = 2 * offset_size;
+    let value_entries = if object {
+        key_entries + count * (offset_size + 2)
+    } else {
+        key_entries
+    };
+
+    out.push(if object { '{' } else { '[' });
+    for i in 0..count {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        if object {
+            let entry = key_entries + i * (offset_size + 2);
+            let key_offset = read_offset(entry)?;
+            let key_len = read(data.get(entry + offset_size..).ok_or(JsonError)?, 2)?.get_u16_le();
+            let key = data
+                .get(key_offset..key_offset + key_len as usize)
+                .ok_or(JsonError)?;
+            write_string(out, &String::from_utf8_lossy(key));
+            out.push_str(": ");
+        }
+
+        let entry = value_entries + i * (1 + offset_size);
+        let value_type = *data.get(entry).ok_or(JsonError)?;
+        let inlined = matches!(value_type, LITERAL | INT16 | UINT16)
+            || (large && matches!(value_type, INT32 | UINT32));
+        if inlined {
+            let inline = data
+                .get(entry + 1..entry + 1 + offset_size)
+                .ok_or(JsonError)?;
+            write_value(out, value_type, inline, depth + 1)?;
+        } else {
+            let offset = read_offset(entry + 1)?;
+            let value = data.get(offset..).ok_or(JsonError)?;
+            write_value(out, value_type, value, depth + 1)?;
+        }
+    }
+    out.push(if object { '}' } else { ']' });
+    Ok(())
+}
+
+fn write_literal(out: &mut String, literal: u8) -> Result<(), JsonError> {
+    out.push_str(match literal {
+        0x00 => "null",
+        0x01 => "true",
+        0x02 => "false",
+        _ => return Err(JsonError),
+    });
+    Ok(())
+}
+
+fn write_number(out: &mut String, number: impl std::fmt::Display) -> Result<(), JsonError> {
+    write!(out, "{}", number).map_err(|_| JsonError)
+}
+
+/// Opaque values keep the MySQL type of temporal and decimal values
+fn write_opaque(out: &mut String, field_type: u8, data: &[u8]) -> Result<(), JsonError> {
+    const NEWDECIMAL: u8 = 246;
+    const DATE: u8 = 10;
+    const TIME: u8 = 11;
+    const DATETIME: u8 = 12;
+    const TIMESTAMP: u8 = 7;
+
+    match field_type {
+        NEWDECIMAL => {
+            let (&precision, data) = data.split_first().ok_or(JsonError)?;
+            let (&scale, data) = data.split_first().ok_or(JsonError)?;
+            let decimal = decode_decimal(precision, scale, data).ok_or(JsonError)?;
+            out.push_str(&decimal);
+        }
+        DATE | DATETIME | TIMESTAMP | TIME => {
+            let packed = read(data, 8)?.get_i64_le();
+            let (negative, packed) = (packed < 0, packed.unsigned_abs());
+            let micros = (packed % (1 << 24)) as u32;
+            let fsp = if micros == 0 { 0 } else { 6 };
+            let text = if field_type == TIME {
+                let hms = packed >> 24;
+                let time = ((hms >> 12) % (1 << 10), (hms >> 6) % 64, hms % 64);
+                format_time(negative, time, micros, fsp)
+            } else {
+                let date_time = packed >> 24;
+                let ymd = date_time >> 17;
+                let hms = date_time % (1 << 17);
+                let date = ((ymd >> 5) / 13, (ymd >> 5) % 13, ymd % 32);
+                let time = (hms >> 12, (hms >> 6) % 64, hms % 64);
+                let text = format_date_time(date, time, micros, fsp);
+                if field_type == DATE {
+                    text[..10].to_owned()
+                } else {
+                    text
+                }
+            };
+            write_string(out, &text);
+        }
+        _ => write_string(out, &format!("base64:type{}:{}", field_type, base64(data))),
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn read(data: &[u8], len: usize) -> Result<&[u8], JsonError> {
+    data.get(..len).ok_or(JsonError)
+}
+
+/// Reads a length stored in 7 bits per byte, the high bit marking that more bytes follow
+fn read_variable_length(data: &[u8]) -> Result<(usize, &[u8]), JsonError> {
+    let mut len = 0usize;
+    for (i, &b) in data.iter().take(5).enumerate() {
+        len |= ((b & 0x7F) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok((len, &data[i + 1..]));
+        }
+    }
+    Err(JsonError)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_json_should_convert_to_text() {
+        // {"a": [1, true, "x"]} as written by the server
+        let array: &[u8] = &[
+            0x03, 0x00, 0x0F, 0x00, // count and size
+            0x05, 0x01, 0x00, // int16 1 inlined
+            0x04, 0x01, 0x00, // literal true inlined
+            0x0C, 0x0D, 0x00, // string at offset 13
+            0x01, b'x',
+        ];
+        let mut object = vec![
+            0x00, 0x01, 0x00, // type and count
+            0x00, 0x00, // size, filled in below
+            0x0B, 0x00, 0x01, 0x00, // key at offset 11, 1 byte
+            0x02, 0x0C, 0x00, // small array at offset 12
+            b'a',
+        ];
+        object.extend_from_slice(array);
+        let size = object.len() as u16 - 1;
+        object[3..5].copy_from_slice(&size.to_le_bytes());
+
+        assert_eq!(to_text(&object).unwrap(), r#"{"a": [1, true, "x"]}"#);
+        assert_eq!(to_text(&[0x0C, 0x02, b'"', b'\n']).unwrap(), r#""\"\n""#);
+        assert!(to_text(&[0x0B, 0x00]).is_err());
+    }
+
+    #[test]
+    fn nesting_should_be_bounded() {
+        // An array whose only element is itself, at offset 0
+        let cyclic = [0x02, 0x01, 0x00, 0x07, 0x00, 0x02, 0x00, 0x00];
+        assert!(to_text(&cyclic).is_err());
+    }
+}
@@ -0,0 +1,598 @@
+//! A replication client streaming binlog events, e.g. for change data capture.
+//!
+//! The connection registers as a replica with `COM_REGISTER_SLAVE` and requests the binlog
+//! from a file and position with `COM_BINLOG_DUMP`, or from the executed GTIDs with
+//! `COM_BINLOG_DUMP_GTID` on MySQL and `@slave_connect_state` on MariaDB. It is dedicated to
+//! the stream afterwards. Archived binlog files are read with [`BinlogReader`].
+//!
+//! ```no_run
+//! # async fn run(connection: dibi::connection::Connection) -> Result<(), dibi::binlog::BinlogError> {
+//! use dibi::binlog::BinlogOptions;
+//! use futures::StreamExt;
+//!
+//! let options = BinlogOptions::new(1001).file("binlog.000001", 4);
+//! let mut events = Box::pin(connection.binlog(options).await?.into_stream());
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod event;
+pub mod file;
+pub mod gtid;
+mod json;
+pub mod rows;
+
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
+
+use crate::{
+    codec::MAX_BUFFER_SIZE,
+    connection::{CommandError, Connection},
+    my::MyStream,
+    protocol::{
+        client::com::{ComBinlogDump, ComBinlogDumpGtid, ComRegisterSlave, BINLOG_DUMP_NON_BLOCK},
+        server::{ErrPacket, OkPacket},
+    },
+    DecodePacket,
+};
+
+pub use event::{
+    ChecksumAlgorithm, Event, EventData, EventDecoder, EventError, EventHeader, EventType,
+};
+pub use file::{BinlogFileError, BinlogReader};
+pub use gtid::{GtidSet, MariaDbGtid, MariaDbGtidList, Uuid};
+pub use json::JsonError;
+pub use rows::{RowChange, RowImage, RowValue, RowsEvent, RowsEventKind, TableMapEvent};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BinlogError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Server(#[from] ErrPacket),
+
+    #[error("failed to decode a binlog event")]
+    Event(#[from] EventError),
+
+    #[error("unexpected packet with header {0:#04x} in the binlog stream")]
+    UnexpectedPacket(u8),
+}
+
+/// Where and how to start streaming the binlog
+#[derive(Debug, Clone)]
+pub struct BinlogOptions {
+    server_id: u32,
+    file: String,
+    position: u32,
+    heartbeat: Option<Duration>,
+    verify_checksum: bool,
+    non_blocking: bool,
+    report_host: String,
+    gtid: Option<GtidStart>,
+}
+
+/// The executed GTIDs to resume the stream after
+#[derive(Debug, Clone)]
+enum GtidStart {
+    MySql(GtidSet),
+    MariaDb(MariaDbGtidList),
+}
+
+impl BinlogOptions {
+    /// Streams from the start of the first binlog as the replica with the given server id,
+    /// which must be unique among the replicas of the server
+    pub fn new(server_id: u32) -> Self {
+        Self {
+            server_id,
+            file: String::new(),
+            position: 4,
+            heartbeat: None,
+            verify_checksum: true,
+            non_blocking: false,
+            report_host: String::new(),
+            gtid: None,
+        }
+    }
+
+    /// Starts at the given position of a binlog file
+    pub fn file(mut self, file: impl Into<String>, position: u32) -> Self {
+        self.file = file.into();
+        self.position = position;
+        self
+    }
+
+    /// Streams every transaction not in the executed MySQL GTID set, with
+    /// `COM_BINLOG_DUMP_GTID`
+    pub fn gtid_set(mut self, executed: GtidSet) -> Self {
+        self.gtid = Some(GtidStart::MySql(executed));
+        self
+    }
+
+    /// Streams every transaction after the MariaDB GTID of every domain, with
+    /// `@slave_connect_state`
+    pub fn mariadb_gtids(mut self, executed: MariaDbGtidList) -> Self {
+        self.gtid = Some(GtidStart::MariaDb(executed));
+        self
+    }
+
+    /// Asks the server to send a heartbeat event when no event was written for the period.
+    /// The read timeout of the connection only applies to the stream when it is longer than
+    /// the period, as an idle server sends nothing else.
+    pub fn heartbeat(mut self, period: Duration) -> Self {
+        self.heartbeat = Some(period);
+        self
+    }
+
+    /// Whether to verify the CRC32 checksum of events, on by default
+    pub fn verify_checksum(mut self, verify: bool) -> Self {
+        self.verify_checksum = verify;
+        self
+    }
+
+    /// Ends the stream at the end of the binlog instead of waiting for new events
+    pub fn non_blocking(mut self, non_blocking: bool) -> Self {
+        self.non_blocking = non_blocking;
+        self
+    }
+
+    /// The host reported in `SHOW REPLICAS`
+    pub fn report_host(mut self, host: impl Into<String>) -> Self {
+        self.report_host = host.into();
+        self
+    }
+}
+
+impl Connection {
+    /// Turns the connection into a replica streaming the binlog
+    pub async fn binlog(mut self, options: BinlogOptions) -> Result<BinlogStream, BinlogError> {
+        let checksum = self.negotiate_checksum().await?;
+        self.prepare_replication(&options).await?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending register slave packet");
+        self.send(ComRegisterSlave::new(
+            options.server_id,
+            &options.report_host,
+            0,
+        ))
+        .await?;
+        self.recv_ok().await?;
+
+        let flags = if options.non_blocking {
+            BINLOG_DUMP_NON_BLOCK
+        } else {
+            0
+        };
+        if let Some(GtidStart::MySql(executed)) = &options.gtid {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("Sending binlog dump gtid packet after {}", executed);
+            let encoded = executed.encode();
+            self.send(ComBinlogDumpGtid::new(
+                options.server_id,
+                &options.file,
+                options.position as u64,
+                flags,
+                &encoded,
+            ))
+            .await?;
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "Sending binlog dump packet from {}:{}",
+                options.file,
+                options.position
+            );
+            self.send(ComBinlogDump::new(
+                options.server_id,
+                &options.file,
+                options.position,
+                flags,
+            ))
+            .await?;
+        }
+
+        let mut stream = self.into_stream();
+        let mut timeouts = stream.timeouts();
+        let heartbeat_in_time = options
+            .heartbeat
+            .zip(timeouts.read)
+            .is_some_and(|(period, read)| period < read);
+        if !heartbeat_in_time {
+            timeouts.read = None;
+        }
+        stream.set_timeouts(timeouts);
+
+        let mut binlog = BinlogStream::new(
+            stream,
+            EventDecoder::new(checksum, options.verify_checksum),
+            options.file,
+            options.position,
+        );
+        match options.gtid {
+            Some(GtidStart::MySql(executed)) => binlog.gtid_set = executed,
+            Some(GtidStart::MariaDb(executed)) => binlog.mariadb_gtids = executed,
+            None => {}
+        }
+        Ok(binlog)
+    }
+
+    /// Tells the server the stream understands checksums, so it keeps them as configured
+    /// instead of stripping them
+    async fn negotiate_checksum(&mut self) -> Result<ChecksumAlgorithm, BinlogError> {
+        // Servers before checksums do not know the variable
+        let result = match self.query("SELECT @@global.binlog_checksum").await {
+            Ok(result) => result,
+            Err(CommandError::Server(_)) => return Ok(ChecksumAlgorithm::Off),
+            Err(err) => return Err(err.into()),
+        };
+        let algorithm = result
+            .rows
+            .first()
+            .and_then(|row| row.get(0))
+            .and_then(|value| value.as_str())
+            .unwrap_or("NONE");
+        if !algorithm.eq_ignore_ascii_case("CRC32") {
+            return Ok(ChecksumAlgorithm::Off);
+        }
+
+        self.query("SET @master_binlog_checksum = @@global.binlog_checksum")
+            .await?;
+        Ok(ChecksumAlgorithm::Crc32)
+    }
+
+    async fn prepare_replication(&mut self, options: &BinlogOptions) -> Result<(), BinlogError> {
+        if let Some(period) = options.heartbeat {
+            self.query(&format!(
+                "SET @master_heartbeat_period = {}",
+                period.as_nanos()
+            ))
+            .await?;
+        }
+        if self.stream().context().is_maria_db() {
+            // Announce GTID support so MariaDB sends its own events instead of dummies
+            self.query("SET @mariadb_slave_capability = 4").await?;
+        }
+        if let Some(GtidStart::MariaDb(executed)) = &options.gtid {
+            self.query(&format!("SET @slave_connect_state = '{}'", executed))
+                .await?;
+            self.query("SET @slave_gtid_strict_mode = 0").await?;
+            self.query("SET @slave_gtid_ignore_duplicates = 0").await?;
+        }
+        Ok(())
+    }
+}
+
+/// A stream of binlog events from a connection turned replica
+#[derive(Debug)]
+pub struct BinlogStream {
+    stream: MyStream,
+    decoder: EventDecoder,
+    file: String,
+    position: u64,
+    gtid_set: GtidSet,
+    mariadb_gtids: MariaDbGtidList,
+    pending_gtid: Option<PendingGtid>,
+    /// Whether the pending GTID belongs to a transaction, committed by an XID or a `COMMIT`
+    /// rather than by its first statement
+    in_transaction: bool,
+}
+
+/// The GTID of the transaction being streamed, executed once it commits
+#[derive(Debug, Clone, Copy)]
+enum PendingGtid {
+    MySql(Uuid, u64),
+    MariaDb(MariaDbGtid),
+}
+
+impl BinlogStream {
+    fn new(mut stream: MyStream, decoder: EventDecoder, file: String, position: u32) -> Self {
+        stream.clear_deadline();
+        Self {
+            stream,
+            decoder,
+            file,
+            position: position as u64,
+            gtid_set: GtidSet::new(),
+            mariadb_gtids: MariaDbGtidList::new(),
+            pending_gtid: None,
+            in_transaction: false,
+        }
+    }
+
+    /// The binlog file and position after the last event, to resume the stream from
+    pub fn position(&self) -> (&str, u64) {
+        (&self.file, self.position)
+    }
+
+    /// The MySQL GTIDs executed so far, to resume the stream from after a failover
+    pub fn gtid_set(&self) -> &GtidSet {
+        &self.gtid_set
+    }
+
+    /// The last MariaDB GTID of every domain executed so far
+    pub fn mariadb_gtids(&self) -> &MariaDbGtidList {
+        &self.mariadb_gtids
+    }
+
+    /// Receives the next event, or `None` at the end of a non blocking stream
+    pub async fn next_event(&mut self) -> Result<Option<Event>, BinlogError> {
+        let payload = self.recv_payload().await?;
+        match payload.first() {
+            Some(0x00) => {}
+            Some(&OkPacket::EOF_HEADER) if payload.len() < 9 => return Ok(None),
+            Some(&ErrPacket::HEADER) => {
+                let packet = crate::codec::PacketFrame::new(payload);
+                return Err(ErrPacket::decode_packet(packet, self.stream.context())?.into());
+            }
+            Some(&header) => return Err(BinlogError::UnexpectedPacket(header)),
+            None => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        }
+
+        let event = self.decoder.decode(payload.slice(1..))?;
+        match &event.data {
+            EventData::Rotate(rotate) => {
+                self.file.clone_from(&rotate.next_file);
+                self.position = rotate.position;
+            }
+            EventData::Heartbeat(heartbeat) if heartbeat.position != 0 => {
+                self.position = heartbeat.position;
+            }
+            _ if event.header.log_pos != 0 => self.position = event.header.log_pos as u64,
+            _ => {}
+        }
+        self.track_gtid(&event.data);
+        Ok(Some(event))
+    }
+
+    /// Marks the GTID of a transaction executed once its commit is streamed
+    fn track_gtid(&mut self, data: &EventData) {
+        let commits = match data {
+            EventData::Gtid(gtid) if !gtid.is_anonymous() => {
+                // MySQL opens transactions with a BEGIN query, DDL comes without
+                self.pending_gtid = Some(PendingGtid::MySql(gtid.uuid, gtid.gno));
+                self.in_transaction = false;
+                false
+            }
+            EventData::MariaDbGtid(gtid) => {
+                // MariaDB writes no BEGIN, the GTID event flags the groups of one statement
+                self.pending_gtid = Some(PendingGtid::MariaDb(gtid.gtid));
+                self.in_transaction = !gtid.is_standalone() && !gtid.is_ddl();
+                false
+            }
+            EventData::Xid(_) => true,
+            EventData::Query(query) => {
+                let statement = query.query.trim();
+                if statement.eq_ignore_ascii_case("BEGIN") {
+                    self.in_transaction = true;
+                    false
+                } else {
+                    statement.eq_ignore_ascii_case("COMMIT") || !self.in_transaction
+                }
+            }
+            _ => false,
+        };
+        if !commits {
+            return;
+        }
+        self.in_transaction = false;
+        match self.pending_gtid.take() {
+            Some(PendingGtid::MySql(uuid, gno)) => self.gtid_set.add(uuid, gno),
+            Some(PendingGtid::MariaDb(gtid)) => self.mariadb_gtids.set(gtid),
+            None => {}
+        }
+    }
+
+    /// Converts into an async [`Stream`] of events, ending after the first error
+    pub fn into_stream(self) -> impl Stream<Item = Result<Event, BinlogError>> + Send {
+        futures::stream::unfold(Some(self), |state| async move {
+            let mut binlog = state?;
+            match binlog.next_event().await {
+                Ok(Some(event)) => Some((Ok(event), Some(binlog))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+
+    /// Receives a packet, joining the chunks of events larger than a single packet
+    async fn recv_payload(&mut self) -> Result<Bytes, BinlogError> {
+        let packet = self.stream.recv_packet().await?.take_buffer();
+        if packet.len() < MAX_BUFFER_SIZE {
+            return Ok(packet);
+        }
+
+        let mut payload = BytesMut::from(&packet[..]);
+        loop {
+            let chunk = self.stream.recv_packet().await?.take_buffer();
+            payload.extend_from_slice(&chunk);
+            if chunk.len() < MAX_BUFFER_SIZE {
+                return Ok(payload.freeze());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BufMut;
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::Framed;
+
+    use super::*;
+    use crate::{
+        codec::PacketCodec,
+        connection::tests::{connect_in_memory, err, ok, read_packet, respond, write_packet},
+        my::stream::StreamTransporter,
+        protocol::ServerStatus,
+        stream::Stream,
+        timeout::Timeouts,
+        BufMutExt,
+    };
+    use event::{tests::frame, MariaDbGtidEvent};
+
+    /// Starts streaming from an in-memory server over a connection with a read timeout of a
+    /// second, returning the server end once the dump was requested
+    async fn start_binlog(heartbeat: Option<Duration>) -> (BinlogStream, tokio::io::DuplexStream) {
+        let (mut connection, mut server) = connect_in_memory().await;
+        connection.stream_mut().set_timeouts(Timeouts {
+            read: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        let server = tokio::spawn(async move {
+            // Without binlog_checksum the server predates checksums
+            read_packet(&mut server).await;
+            respond(&mut server, &[err(1193, "Unknown system variable")]).await;
+            if heartbeat.is_some() {
+                read_packet(&mut server).await;
+                respond(&mut server, &[ok(ServerStatus::AUTOCOMMIT)]).await;
+            }
+            read_packet(&mut server).await;
+            respond(&mut server, &[ok(ServerStatus::AUTOCOMMIT)]).await;
+            let (_, dump) = read_packet(&mut server).await;
+            assert_eq!(dump[0], 0x12);
+            server
+        });
+
+        let mut options = BinlogOptions::new(1001).file("binlog.000001", 4);
+        if let Some(period) = heartbeat {
+            options = options.heartbeat(period);
+        }
+        let binlog = connection.binlog(options).await.unwrap();
+        (binlog, server.await.unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_streams_without_heartbeats_should_not_time_out() {
+        let (mut binlog, mut server) = start_binlog(None).await;
+        let idle = tokio::time::timeout(Duration::from_secs(60), binlog.next_event()).await;
+        assert!(idle.is_err());
+
+        let mut body = BytesMut::new();
+        body.put_len_encoded_int(1);
+        body.put_len_encoded_str("binlog.000001");
+        body.put_len_encoded_int(2);
+        body.put_len_encoded_int(3);
+        body.put_len_encoded_int(5000);
+        let mut packet = vec![0x00];
+        packet.extend_from_slice(&frame(EventType::HeartbeatV2, 0, &body, false));
+        write_packet(&mut server, 1, &packet).await;
+        let event = binlog.next_event().await.unwrap().unwrap();
+        assert!(matches!(event.data, EventData::Heartbeat(_)));
+        assert_eq!(binlog.position(), ("binlog.000001", 5000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missed_heartbeats_should_time_out() {
+        let (mut binlog, _server) = start_binlog(Some(Duration::from_millis(500))).await;
+        let missed = tokio::time::timeout(Duration::from_secs(60), binlog.next_event()).await;
+        assert!(missed.unwrap().is_err());
+    }
+
+    fn query(statement: &str) -> Vec<u8> {
+        let mut body = BytesMut::new();
+        body.put_u32_le(7);
+        body.put_u32_le(0);
+        body.put_u8(4);
+        body.put_u16_le(0);
+        body.put_u16_le(0);
+        body.put_slice(b"shop\0");
+        body.put_slice(statement.as_bytes());
+        body.to_vec()
+    }
+
+    /// Streams the events one packet at a time, returning the GTID state after each of them
+    async fn stream_gtids<T>(events: &[Bytes], state: impl Fn(&BinlogStream) -> T) -> Vec<T> {
+        let (client, mut server) = tokio::io::duplex(4096);
+        let transporter = StreamTransporter::Left(Stream::custom(client));
+        let stream = MyStream::new(Framed::new(transporter, PacketCodec::new()));
+        let mut binlog = BinlogStream::new(stream, EventDecoder::default(), String::new(), 4);
+
+        let mut states = Vec::new();
+        for (sequence, event) in events.iter().enumerate() {
+            let mut packet = ((event.len() + 1) as u32).to_le_bytes()[..3].to_vec();
+            packet.push(sequence as u8);
+            packet.push(0x00);
+            packet.extend_from_slice(event);
+            server.write_all(&packet).await.unwrap();
+            binlog.next_event().await.unwrap().unwrap();
+            states.push(state(&binlog));
+        }
+        states
+    }
+
+    #[tokio::test]
+    async fn gtids_should_be_executed_at_the_commit() {
+        let uuid: Uuid = "3e11fa47-71ca-11e1-9e33-c80aa9429562".parse().unwrap();
+        let mut gtid = BytesMut::new();
+        gtid.put_u8(1);
+        gtid.put_slice(&uuid.0);
+        gtid.put_u64_le(23);
+        let events = [
+            frame(EventType::Gtid, 100, &gtid, false),
+            frame(EventType::Query, 200, &query("BEGIN"), false),
+            frame(
+                EventType::Query,
+                300,
+                &query("INSERT INTO t VALUES (1)"),
+                false,
+            ),
+            frame(EventType::Xid, 400, &42u64.to_le_bytes(), false),
+        ];
+        let states = stream_gtids(&events, |binlog| binlog.gtid_set().to_string()).await;
+        let executed = format!("{}:23", uuid);
+        assert_eq!(states, ["", "", "", executed.as_str()]);
+
+        // A DDL statement is its own commit
+        let events = [
+            frame(EventType::Gtid, 100, &gtid, false),
+            frame(
+                EventType::Query,
+                200,
+                &query("CREATE TABLE t (id INT)"),
+                false,
+            ),
+        ];
+        let states = stream_gtids(&events, |binlog| binlog.gtid_set().to_string()).await;
+        assert_eq!(states, ["", executed.as_str()]);
+
+        // MariaDB transactions have no BEGIN, standalone and DDL groups end with their statement
+        let mariadb_gtid = |sequence: u64, flags| {
+            let mut body = BytesMut::new();
+            body.put_u64_le(sequence);
+            body.put_u32_le(0);
+            body.put_u8(flags);
+            frame(EventType::MariaDbGtid, 100, &body, false)
+        };
+        let statement = |text| frame(EventType::Query, 200, &query(text), false);
+        let events = [
+            mariadb_gtid(100, 0),
+            statement("INSERT INTO t VALUES (1)"),
+            frame(EventType::Xid, 300, &42u64.to_le_bytes(), false),
+            mariadb_gtid(101, MariaDbGtidEvent::STANDALONE),
+            statement("INSERT INTO t VALUES (2)"),
+            mariadb_gtid(102, MariaDbGtidEvent::STANDALONE | MariaDbGtidEvent::DDL),
+            statement("CREATE TABLE u (id INT)"),
+            mariadb_gtid(103, 0),
+            statement("INSERT INTO t VALUES (3)"),
+            statement("INSERT INTO t VALUES (4)"),
+            frame(EventType::Xid, 400, &43u64.to_le_bytes(), false),
+        ];
+        let states = stream_gtids(&events, |binlog| binlog.mariadb_gtids().to_string()).await;
+        assert_eq!(
+            states,
+            [
+                "", "", "0-1-100", "0-1-100", "0-1-101", "0-1-101", "0-1-102", "0-1-102",
+                "0-1-102", "0-1-102", "0-1-103",
+            ]
+        );
+    }
+}
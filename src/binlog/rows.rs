@@ -0,0 +1,1026 @@
+//! Decoding of `TABLE_MAP` and row events into typed row images.
+//!
+//! Column names, signedness, enum and set values and the primary key come from the optional
+//! metadata the server writes with `binlog_row_metadata=FULL`. Without it, columns have no name
+//! and integers are read as signed.
+
+use std::{collections::HashMap, sync::Arc};
+
+use bytes::{Buf, Bytes};
+
+use super::{
+    event::{ensure, lossy, EventError, EventType, FormatDescriptionEvent},
+    json,
+};
+use crate::{
+    protocol::ColumnType,
+    value::{format_date_time, format_time, Value},
+    BytesExt,
+};
+
+/// The rows event ends a statement, table ids are not valid afterwards
+pub const STMT_END_F: u16 = 0x0001;
+
+/// The JSON columns of a partial update after image hold a diff
+const PARTIAL_JSON_UPDATES: u64 = 0x0001;
+
+mod optional_metadata {
+    pub const SIGNEDNESS: u8 = 1;
+    pub const COLUMN_NAME: u8 = 4;
+    pub const SET_STR_VALUE: u8 = 5;
+    pub const ENUM_STR_VALUE: u8 = 6;
+    pub const SIMPLE_PRIMARY_KEY: u8 = 8;
+    pub const PRIMARY_KEY_WITH_PREFIX: u8 = 9;
+}
+
+/// Maps a table id of the following row events to the table and its columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableMapEvent {
+    pub table_id: u64,
+    pub flags: u16,
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<TableColumn>,
+    /// The indexes of the primary key columns, from the optional metadata
+    pub primary_key: Vec<usize>,
+}
+
+impl TableMapEvent {
+    /// The index of a column by name, when the server wrote the names
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.name.as_deref() == Some(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableColumn {
+    /// The type of the column, with `ENUM` and `SET` resolved from `STRING`
+    pub column_type: ColumnType,
+    /// The type specific metadata, e.g. the maximum length or the fractional seconds precision
+    pub metadata: u16,
+    pub nullable: bool,
+    pub unsigned: bool,
+    pub name: Option<String>,
+    /// The values of an `ENUM` or `SET` column, indexed by the values of the row images
+    pub values: Vec<String>,
+}
+
+/// The kind of change of a rows event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowsEventKind {
+    Write,
+    Update,
+    /// An update whose after images may hold [`JsonDiff`]s instead of whole JSON documents
+    PartialUpdate,
+    Delete,
+}
+
+/// The rows inserted, updated or deleted by a statement in a single table
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowsEvent {
+    pub kind: RowsEventKind,
+    pub table_id: u64,
+    pub flags: u16,
+    pub table: Arc<TableMapEvent>,
+    pub rows: Vec<RowChange>,
+}
+
+/// The images of a single row, `before` for updates and deletes, `after` for writes and
+/// updates
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowChange {
+    pub before: Option<RowImage>,
+    pub after: Option<RowImage>,
+}
+
+/// The values of a row, one per column of the table
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowImage {
+    table: Arc<TableMapEvent>,
+    values: Vec<RowValue>,
+}
+
+impl RowImage {
+    pub fn values(&self) -> &[RowValue] {
+        &self.values
+    }
+
+    pub fn get(&self, index: usize) -> Option<&RowValue> {
+        self.values.get(index)
+    }
+
+    /// Gets a value by its column name, when the server wrote the names
+    pub fn get_by_name(&self, name: &str) -> Option<&RowValue> {
+        self.values.get(self.table.column_index(name)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowValue {
+    /// The column is not part of the image, e.g. with `binlog_row_image=MINIMAL`
+    Missing,
+    /// Integers, floats, bytes and strings as is, `ENUM` and `SET` as their index and bits,
+    /// decimals, temporal values and JSON as their text
+    Value(Value),
+    /// The changes of a partially updated JSON document
+    JsonDiff(Vec<JsonDiff>),
+}
+
+impl RowValue {
+    pub fn as_value(&self) -> Option<&Value> {
+        match self {
+            RowValue::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonDiffOperation {
+    Replace,
+    Insert,
+    Remove,
+}
+
+/// A change to a JSON document at a path, with the new value as JSON text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonDiff {
+    pub operation: JsonDiffOperation,
+    pub path: String,
+    pub value: Option<String>,
+}
+
+/// The tables mapped by the current statement
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TableMaps {
+    tables: HashMap<u64, Arc<TableMapEvent>>,
+}
+
+impl TableMaps {
+    pub(crate) fn decode_table_map(
+        &mut self,
+        format: Option<&FormatDescriptionEvent>,
+        mut buf: Bytes,
+    ) -> Result<Arc<TableMapEvent>, EventError> {
+        const EVENT_TYPE: EventType = EventType::TableMap;
+        let io = |_| EventError::Truncated(EVENT_TYPE);
+
+        let table_id_len = table_id_len(format, EVENT_TYPE);
+        ensure(&buf, table_id_len + 2, EVENT_TYPE)?;
+        let table_id = buf.get_uint_le(table_id_len);
+        let flags = buf.get_u16_le();
+
+        let schema = read_name(&mut buf)?;
+        let table = read_name(&mut buf)?;
+        let column_count = buf.get_len_encoded_int().map_err(io)? as usize;
+        ensure(&buf, column_count, EVENT_TYPE)?;
+        let types = buf.split_to(column_count);
+        let mut metadata = buf.get_len_encoded_bytes().map_err(io)?;
+        ensure(&buf, column_count.div_ceil(8), EVENT_TYPE)?;
+        let nullable = buf.split_to(column_count.div_ceil(8));
+
+        let mut columns = Vec::with_capacity(column_count);
+        for (i, &code) in types.iter().enumerate() {
+            let column_type =
+                ColumnType::try_from(code).map_err(|_| EventError::ColumnType(code))?;
+            let (column_type, metadata) = read_column_metadata(column_type, &mut metadata)?;
+            columns.push(TableColumn {
+                column_type,
+                metadata,
+                nullable: bit(&nullable, i),
+                unsigned: false,
+                name: None,
+                values: Vec::new(),
+            });
+        }
+
+        let mut event = TableMapEvent {
+            table_id,
+            flags,
+            schema,
+            table,
+            columns,
+            primary_key: Vec::new(),
+        };
+        read_optional_metadata(&mut event, buf)?;
+
+        let event = Arc::new(event);
+        self.tables.insert(table_id, event.clone());
+        Ok(event)
+    }
+
+    pub(crate) fn decode_rows(
+        &mut self,
+        format: Option<&FormatDescriptionEvent>,
+        event_type: EventType,
+        mut buf: Bytes,
+    ) -> Result<RowsEvent, EventError> {
+        let io = |_| EventError::Truncated(event_type);
+        let (kind, v2) = match event_type {
+            EventType::WriteRowsV1 => (RowsEventKind::Write, false),
+            EventType::UpdateRowsV1 => (RowsEventKind::Update, false),
+            EventType::DeleteRowsV1 => (RowsEventKind::Delete, false),
+            EventType::WriteRowsV2 => (RowsEventKind::Write, true),
+            EventType::UpdateRowsV2 => (RowsEventKind::Update, true),
+            EventType::DeleteRowsV2 => (RowsEventKind::Delete, true),
+            EventType::PartialUpdateRows => (RowsEventKind::PartialUpdate, true),
+            _ => return Err(EventError::Truncated(event_type)),
+        };
+
+        let table_id_len = table_id_len(format, event_type);
+        ensure(&buf, table_id_len + 2, event_type)?;
+        let table_id = buf.get_uint_le(table_id_len);
+        let flags = buf.get_u16_le();
+        if v2 {
+            ensure(&buf, 2, event_type)?;
+            let extra_len = (buf.get_u16_le() as usize).saturating_sub(2);
+            ensure(&buf, extra_len, event_type)?;
+            buf.advance(extra_len);
+        }
+
+        let table = self
+            .tables
+            .get(&table_id)
+            .cloned()
+            .ok_or(EventError::UnknownTable(table_id))?;
+        let column_count = buf.get_len_encoded_int().map_err(io)? as usize;
+        if column_count != table.columns.len() {
+            return Err(EventError::ColumnCount {
+                table_id,
+                expected: table.columns.len(),
+                actual: column_count,
+            });
+        }
+        let bitmap_len = column_count.div_ceil(8);
+        ensure(&buf, bitmap_len, event_type)?;
+        let present_before = buf.split_to(bitmap_len);
+        let present_after = if matches!(kind, RowsEventKind::Update | RowsEventKind::PartialUpdate)
+        {
+            ensure(&buf, bitmap_len, event_type)?;
+            buf.split_to(bitmap_len)
+        } else {
+            present_before.clone()
+        };
+
+        let mut rows = Vec::new();
+        while !buf.is_empty() {
+            let mut reader = ImageReader {
+                table: &table,
+                buf: &mut buf,
+                event_type,
+            };
+            let row = match kind {
+                RowsEventKind::Write => RowChange {
+                    before: None,
+                    after: Some(reader.read(&present_after, false)?),
+                },
+                RowsEventKind::Delete => RowChange {
+                    before: Some(reader.read(&present_before, false)?),
+                    after: None,
+                },
+                RowsEventKind::Update | RowsEventKind::PartialUpdate => RowChange {
+                    before: Some(reader.read(&present_before, false)?),
+                    after: Some(reader.read(&present_after, kind == RowsEventKind::PartialUpdate)?),
+                },
+            };
+            rows.push(row);
+        }
+
+        if flags & STMT_END_F != 0 {
+            self.tables.clear();
+        }
+        Ok(RowsEvent {
+            kind,
+            table_id,
+            flags,
+            table,
+            rows,
+        })
+    }
+}
+
+/// Table ids take 4 bytes in the oldest formats, with a post header of 6 bytes
+fn table_id_len(format: Option<&FormatDescriptionEvent>, event_type: EventType) -> usize {
+    match format.and_then(|f| f.post_header_length(event_type)) {
+        Some(6) => 4,
+        _ => 6,
+    }
+}
+
+fn read_name(buf: &mut Bytes) -> Result<String, EventError> {
+    ensure(buf, 1, EventType::TableMap)?;
+    let len = buf.get_u8() as usize;
+    ensure(buf, len + 1, EventType::TableMap)?;
+    let name = lossy(&buf.split_to(len));
+    buf.advance(1);
+    Ok(name)
+}
+
+/// Reads the metadata of a column, resolving the real type of `STRING` columns
+fn read_column_metadata(
+    column_type: ColumnType,
+    buf: &mut Bytes,
+) -> Result<(ColumnType, u16), EventError> {
+    let len = match column_type {
+        ColumnType::Float
+        | ColumnType::Double
+        | ColumnType::TinyBlob
+        | ColumnType::MediumBlob
+        | ColumnType::LongBlob
+        | ColumnType::Blob
+        | ColumnType::Geometry
+        | ColumnType::Json
+        | ColumnType::Vector
+        | ColumnType::Timestamp2
+        | ColumnType::DateTime2
+        | ColumnType::Time2 => 1,
+        ColumnType::VarChar
+        | ColumnType::VarString
+        | ColumnType::Bit
+        | ColumnType::NewDecimal
+        | ColumnType::String
+        | ColumnType::Enum
+        | ColumnType::Set => 2,
+        _ => 0,
+    };
+    ensure(buf, len, EventType::TableMap)?;
+    let metadata = match (column_type, len) {
+        (_, 0) => 0,
+        (_, 1) => buf.get_u8() as u16,
+        // Written byte by byte, precision and real type first
+        (ColumnType::NewDecimal | ColumnType::String | ColumnType::Enum | ColumnType::Set, _) => {
+            buf.get_u16()
+        }
+        _ => buf.get_u16_le(),
+    };
+
+    match column_type {
+        ColumnType::Enum | ColumnType::Set => return Ok((column_type, metadata & 0xFF)),
+        ColumnType::String => {}
+        _ => return Ok((column_type, metadata)),
+    }
+    let (real_type, max_len) = ((metadata >> 8) as u8, metadata & 0xFF);
+    if real_type & 0x30 != 0x30 {
+        // The length of long CHAR columns borrows two bits of the real type
+        let max_len = max_len | (((real_type as u16 & 0x30) ^ 0x30) << 4);
+        return Ok((ColumnType::String, max_len));
+    }
+    match ColumnType::try_from(real_type) {
+        Ok(real_type @ (ColumnType::Enum | ColumnType::Set)) => Ok((real_type, max_len)),
+        _ => Ok((ColumnType::String, max_len)),
+    }
+}
+
+fn read_optional_metadata(event: &mut TableMapEvent, mut buf: Bytes) -> Result<(), EventError> {
+    let io = |_| EventError::Truncated(EventType::TableMap);
+    while !buf.is_empty() {
+        let field = buf.get_u8();
+        let mut value = buf.get_len_encoded_bytes().map_err(io)?;
+        match field {
+            optional_metadata::SIGNEDNESS => {
+                let numeric = event
+                    .columns
+                    .iter_mut()
+                    .filter(|c| is_numeric(c.column_type));
+                for (i, column) in numeric.enumerate() {
+                    // Unlike the null bitmap, the most significant bit comes first
+                    column.unsigned = value.get(i / 8).is_some_and(|b| b & (0x80 >> (i % 8)) != 0);
+                }
+            }
+            optional_metadata::COLUMN_NAME => {
+                for column in &mut event.columns {
+                    if value.is_empty() {
+                        break;
+                    }
+                    column.name = Some(lossy(&value.get_len_encoded_bytes().map_err(io)?));
+                }
+            }
+            optional_metadata::SET_STR_VALUE | optional_metadata::ENUM_STR_VALUE => {
+                let column_type = if field == optional_metadata::SET_STR_VALUE {
+                    ColumnType::Set
+                } else {
+                    ColumnType::Enum
+                };
+                let columns = event
+                    .columns
+                    .iter_mut()
+                    .filter(|c| c.column_type == column_type);
+                for column in columns {
+                    if value.is_empty() {
+                        break;
+                    }
+                    let count = value.get_len_encoded_int().map_err(io)?;
+                    for _ in 0..count {
+                        let name = value.get_len_encoded_bytes().map_err(io)?;
+                        column.values.push(lossy(&name));
+                    }
+                }
+            }
+            optional_metadata::SIMPLE_PRIMARY_KEY => {
+                while !value.is_empty() {
+                    event
+                        .primary_key
+                        .push(value.get_len_encoded_int().map_err(io)? as usize);
+                }
+            }
+            optional_metadata::PRIMARY_KEY_WITH_PREFIX => {
+                while !value.is_empty() {
+                    event
+                        .primary_key
+                        .push(value.get_len_encoded_int().map_err(io)? as usize);
+                    value.get_len_encoded_int().map_err(io)?;
+                }
+            }
+            // Charsets, geometry types and visibility are not needed to decode rows
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_numeric(column_type: ColumnType) -> bool {
+    matches!(
+        column_type,
+        ColumnType::Tiny
+            | ColumnType::Short
+            | ColumnType::Int24
+            | ColumnType::Long
+            | ColumnType::LongLong
+            | ColumnType::NewDecimal
+            | ColumnType::Float
+            | ColumnType::Double
+    )
+}
+
+fn bit(bitmap: &[u8], index: usize) -> bool {
+    bitmap
+        .get(index / 8)
+        .is_some_and(|b| b & (1 << (index % 8)) != 0)
+}
+
+struct ImageReader<'a> {
+    table: &'a Arc<TableMapEvent>,
+    buf: &'a mut Bytes,
+    event_type: EventType,
+}
+
+impl ImageReader<'_> {
+    fn read(&mut self, present: &[u8], partial: bool) -> Result<RowImage, EventError> {
+        let columns = &self.table.columns;
+        let present_count = (0..columns.len()).filter(|&i| bit(present, i)).count();
+
+        let mut partial_json = Bytes::new();
+        if partial {
+            let options = self
+                .buf
+                .get_len_encoded_int()
+                .map_err(|_| self.truncated())?;
+            if options & PARTIAL_JSON_UPDATES != 0 {
+                let json_columns = (0..columns.len())
+                    .filter(|&i| bit(present, i) && columns[i].column_type == ColumnType::Json)
+                    .count();
+                partial_json = self.take(json_columns.div_ceil(8))?;
+            }
+        }
+        let nulls = self.take(present_count.div_ceil(8))?;
+
+        let mut values = Vec::with_capacity(columns.len());
+        let (mut present_index, mut json_index) = (0, 0);
+        for (i, column) in columns.iter().enumerate() {
+            if !bit(present, i) {
+                values.push(RowValue::Missing);
+                continue;
+            }
+            let is_null = bit(&nulls, present_index);
+            present_index += 1;
+
+            let is_partial = column.column_type == ColumnType::Json && {
+                json_index += 1;
+                bit(&partial_json, json_index - 1)
+            };
+            let value = if is_null {
+                RowValue::Value(Value::Null)
+            } else if is_partial {
+                RowValue::JsonDiff(self.read_json_diff()?)
+            } else {
+                RowValue::Value(self.read_value(column)?)
+            };
+            values.push(value);
+        }
+
+        Ok(RowImage {
+            table: self.table.clone(),
+            values,
+        })
+    }
+
+    fn truncated(&self) -> EventError {
+        EventError::Truncated(self.event_type)
+    }
+
+    fn take(&mut self, len: usize) -> Result<Bytes, EventError> {
+        ensure(self.buf, len, self.event_type)?;
+        Ok(self.buf.split_to(len))
+    }
+
+    fn take_uint_le(&mut self, len: usize) -> Result<u64, EventError> {
+        ensure(self.buf, len, self.event_type)?;
+        Ok(self.buf.get_uint_le(len))
+    }
+
+    fn take_uint_be(&mut self, len: usize) -> Result<u64, EventError> {
+        ensure(self.buf, len, self.event_type)?;
+        Ok(self.buf.get_uint(len))
+    }
+
+    /// Reads bytes prefixed by their length in `prefix` bytes
+    fn take_prefixed(&mut self, prefix: usize) -> Result<Bytes, EventError> {
+        let len = self.take_uint_le(prefix)? as usize;
+        self.take(len)
+    }
+
+    /// Reads the fractional seconds stored in `(fsp + 1) / 2` bytes, as microseconds
+    fn take_micros(&mut self, fsp: u8) -> Result<u32, EventError> {
+        Ok(match fsp {
+            1 | 2 => self.take_uint_be(1)? as u32 * 10000,
+            3 | 4 => self.take_uint_be(2)? as u32 * 100,
+            5 | 6 => self.take_uint_be(3)? as u32,
+            _ => 0,
+        })
+    }
+
+    fn read_value(&mut self, column: &TableColumn) -> Result<Value, EventError> {
+        let metadata = column.metadata;
+        let int = |value: u64, bits: u32, unsigned: bool| {
+            if unsigned {
+                Value::UInt(value)
+            } else {
+                let shift = 64 - bits;
+                Value::Int(((value << shift) as i64) >> shift)
+            }
+        };
+        let text = |text: String| Value::Bytes(Bytes::from(text));
+        // The byte width of a length or value, which the metadata of the column gives
+        let width = |widths: std::ops::RangeInclusive<usize>| {
+            if widths.contains(&(metadata as usize)) {
+                Ok(metadata as usize)
+            } else {
+                Err(EventError::ColumnMetadata {
+                    column_type: column.column_type,
+                    metadata,
+                })
+            }
+        };
+
+        let value = match column.column_type {
+            ColumnType::Null => Value::Null,
+            ColumnType::Tiny => int(self.take_uint_le(1)?, 8, column.unsigned),
+            ColumnType::Short => int(self.take_uint_le(2)?, 16, column.unsigned),
+            ColumnType::Int24 => int(self.take_uint_le(3)?, 24, column.unsigned),
+            ColumnType::Long => int(self.take_uint_le(4)?, 32, column.unsigned),
+            ColumnType::LongLong => int(self.take_uint_le(8)?, 64, column.unsigned),
+            ColumnType::Year => match self.take_uint_le(1)? {
+                0 => Value::UInt(0),
+                year => Value::UInt(1900 + year),
+            },
+            ColumnType::Float => Value::Float(f32::from_bits(self.take_uint_le(4)? as u32)),
+            ColumnType::Double => Value::Double(f64::from_bits(self.take_uint_le(8)?)),
+            ColumnType::NewDecimal => {
+                let (precision, scale) = ((metadata >> 8) as u8, metadata as u8);
+                let bytes = self.take(decimal_size(precision, scale))?;
+                text(decode_decimal(precision, scale, &bytes).ok_or_else(|| self.truncated())?)
+            }
+            ColumnType::Date | ColumnType::NewDate => {
+                let date = self.take_uint_le(3)?;
+                text(format!(
+                    "{:04}-{:02}-{:02}",
+                    date >> 9,
+                    (date >> 5) & 15,
+                    date & 31
+                ))
+            }
+            ColumnType::Time => {
+                let time = self.take_uint_le(3)?;
+                let time = ((time << 40) as i64) >> 40;
+                let (negative, time) = (time < 0, time.unsigned_abs());
+                text(format_time(
+                    negative,
+                    (time / 10000, time / 100 % 100, time % 100),
+                    0,
+                    0,
+                ))
+            }
+            ColumnType::Time2 => {
+                let fsp = metadata as u8;
+                // As `my_time_packed_from_binary`, into the integer part shifted by 24 bits
+                // plus the microseconds
+                let packed = match fsp {
+                    1..=4 => {
+                        let (len, unit) = if fsp <= 2 { (1, 10_000) } else { (2, 100) };
+                        let int = self.take_uint_be(3)? as i64 - 0x80_0000;
+                        let frac = self.take_uint_be(len)? as i64;
+                        // Negative times store the fraction as a complement of the next second
+                        if int < 0 && frac != 0 {
+                            ((int + 1) << 24) + (frac - (1 << (8 * len))) * unit
+                        } else {
+                            (int << 24) + frac * unit
+                        }
+                    }
+                    // The integer part and the fraction form a single signed integer
+                    5 | 6 => self.take_uint_be(6)? as i64 - 0x8000_0000_0000,
+                    _ => (self.take_uint_be(3)? as i64 - 0x80_0000) << 24,
+                };
+                let (negative, packed) = (packed < 0, packed.unsigned_abs());
+                let hms = packed >> 24;
+                text(format_time(
+                    negative,
+                    ((hms >> 12) % (1 << 10), (hms >> 6) % 64, hms % 64),
+                    (packed % (1 << 24)) as u32,
+                    fsp,
+                ))
+            }
+            ColumnType::DateTime => {
+                let value = self.take_uint_le(8)?;
+                let (date, time) = (value / 1_000_000, value % 1_000_000);
+                text(format_date_time(
+                    (date / 10000, date / 100 % 100, date % 100),
+                    (time / 10000, time / 100 % 100, time % 100),
+                    0,
+                    0,
+                ))
+            }
+            ColumnType::DateTime2 => {
+                let fsp = metadata as u8;
+                let packed = self.take_uint_be(5)?.wrapping_sub(0x80_0000_0000);
+                let micros = self.take_micros(fsp)?;
+                let (ymd, hms) = (packed >> 17, packed % (1 << 17));
+                text(format_date_time(
+                    ((ymd >> 5) / 13, (ymd >> 5) % 13, ymd % 32),
+                    (hms >> 12, (hms >> 6) % 64, hms % 64),
+                    micros,
+                    fsp,
+                ))
+            }
+            ColumnType::Timestamp => text(format_timestamp(self.take_uint_le(4)?, 0, 0)),
+            ColumnType::Timestamp2 => {
+                let fsp = metadata as u8;
+                let seconds = self.take_uint_be(4)?;
+                let micros = self.take_micros(fsp)?;
+                text(format_timestamp(seconds, micros, fsp))
+            }
+            ColumnType::VarChar | ColumnType::VarString | ColumnType::String => {
+                Value::Bytes(self.take_prefixed(if metadata < 256 { 1 } else { 2 })?)
+            }
+            ColumnType::Enum => Value::UInt(self.take_uint_le(width(1..=2)?)?),
+            ColumnType::Set => Value::UInt(self.take_uint_le(width(1..=8)?)?),
+            ColumnType::Bit => {
+                let (bits, bytes) = (metadata & 0xFF, (metadata >> 8) as usize);
+                Value::Bytes(self.take(bytes + usize::from(bits > 0))?)
+            }
+            ColumnType::TinyBlob
+            | ColumnType::MediumBlob
+            | ColumnType::LongBlob
+            | ColumnType::Blob
+            | ColumnType::Geometry
+            | ColumnType::Vector => Value::Bytes(self.take_prefixed(width(1..=4)?)?),
+            ColumnType::Json => {
+                let json = self.take_prefixed(width(1..=4)?)?;
+                text(json::to_text(&json)?)
+            }
+            other => return Err(EventError::UnsupportedColumnType(other)),
+        };
+        Ok(value)
+    }
+
+    /// Reads the diffs of a JSON column updated in place by `JSON_SET`, `JSON_REPLACE` and
+    /// `JSON_REMOVE`
+    fn read_json_diff(&mut self) -> Result<Vec<JsonDiff>, EventError> {
+        let event_type = self.event_type;
+        let io = |_| EventError::Truncated(event_type);
+        let mut buf = self.buf.get_len_encoded_bytes().map_err(io)?;
+        let mut diffs = Vec::new();
+        while !buf.is_empty() {
+            let operation = match buf.get_u8() {
+                0 => JsonDiffOperation::Replace,
+                1 => JsonDiffOperation::Insert,
+                2 => JsonDiffOperation::Remove,
+                _ => return Err(EventError::Truncated(event_type)),
+            };
+            let path = lossy(&buf.get_len_encoded_bytes().map_err(io)?);
+            let value = if operation == JsonDiffOperation::Remove {
+                None
+            } else {
+                let value = buf.get_len_encoded_bytes().map_err(io)?;
+                Some(json::to_text(&value)?)
+            };
+            diffs.push(JsonDiff {
+                operation,
+                path,
+                value,
+            });
+        }
+        Ok(diffs)
+    }
+}
+
+const DIG2BYTES: [usize; 10] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+
+/// The size of a binary decimal of the given precision and scale
+fn decimal_size(precision: u8, scale: u8) -> usize {
+    let integral = precision.saturating_sub(scale) as usize;
+    let scale = scale as usize;
+    integral / 9 * 4 + DIG2BYTES[integral % 9] + scale / 9 * 4 + DIG2BYTES[scale % 9]
+}
+
+/// Decodes a binary decimal, stored as big endian groups of 9 digits with the sign in the
+/// highest bit
+pub(crate) fn decode_decimal(precision: u8, scale: u8, data: &[u8]) -> Option<String> {
+    let size = decimal_size(precision, scale);
+    let mut bytes = data.get(..size)?.to_vec();
+    let negative = bytes.first()? & 0x80 == 0;
+    bytes[0] ^= 0x80;
+    if negative {
+        bytes.iter_mut().for_each(|b| *b ^= 0xFF);
+    }
+
+    let mut buf = &bytes[..];
+    let integral = precision.saturating_sub(scale) as usize;
+    let mut integer = String::new();
+    let leading = DIG2BYTES[integral % 9];
+    if leading > 0 {
+        integer.push_str(&buf.get_uint(leading).to_string());
+    }
+    for _ in 0..integral / 9 {
+        integer.push_str(&format!("{:09}", buf.get_u32()));
+    }
+    let integer = integer.trim_start_matches('0');
+
+    let scale = scale as usize;
+    let mut fraction = String::new();
+    for _ in 0..scale / 9 {
+        fraction.push_str(&format!("{:09}", buf.get_u32()));
+    }
+    let trailing = DIG2BYTES[scale % 9];
+    if trailing > 0 {
+        fraction.push_str(&format!(
+            "{:0width$}",
+            buf.get_uint(trailing),
+            width = scale % 9
+        ));
+    }
+
+    let mut text = String::new();
+    if negative {
+        text.push('-');
+    }
+    text.push_str(if integer.is_empty() { "0" } else { integer });
+    if !fraction.is_empty() {
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Some(text)
+}
+
+/// Formats seconds since the epoch as a UTC date and time, the zero timestamp as zeros
+fn format_timestamp(seconds: u64, micros: u32, fsp: u8) -> String {
+    if seconds == 0 {
+        return format_date_time((0, 0, 0), (0, 0, 0), micros, fsp);
+    }
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since the epoch, by Howard Hinnant
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format_date_time(
+        (year, month, day),
+        (time / 3600, time / 60 % 60, time % 60),
+        micros,
+        fsp,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+
+    use super::*;
+    use crate::BufMutExt;
+
+    fn table_map(enum_width: u8) -> Bytes {
+        let mut body = BytesMut::new();
+        body.put_uint_le(42, 6);
+        body.put_u16_le(1);
+        body.put_u8(4);
+        body.put_slice(b"shop\0");
+        body.put_u8(6);
+        body.put_slice(b"orders\0");
+        body.put_len_encoded_int(5);
+        body.put_slice(&[
+            ColumnType::Long as u8,
+            ColumnType::VarChar as u8,
+            ColumnType::NewDecimal as u8,
+            ColumnType::DateTime2 as u8,
+            ColumnType::String as u8,
+        ]);
+        body.put_len_encoded_str([
+            0x2C,
+            0x01, // varchar(300)
+            10,
+            2, // decimal(10, 2)
+            3, // datetime(3)
+            ColumnType::Enum as u8,
+            enum_width, // enum
+        ]);
+        body.put_u8(0b11110);
+
+        let mut signedness = BytesMut::new();
+        signedness.put_u8(0b1000_0000);
+        body.put_u8(optional_metadata::SIGNEDNESS);
+        body.put_len_encoded_str(signedness);
+
+        let mut names = BytesMut::new();
+        for name in ["id", "note", "total", "created_at", "status"] {
+            names.put_len_encoded_str(name);
+        }
+        body.put_u8(optional_metadata::COLUMN_NAME);
+        body.put_len_encoded_str(names);
+
+        let mut values = BytesMut::new();
+        values.put_len_encoded_int(2);
+        values.put_len_encoded_str("open");
+        values.put_len_encoded_str("closed");
+        body.put_u8(optional_metadata::ENUM_STR_VALUE);
+        body.put_len_encoded_str(values);
+
+        body.put_u8(optional_metadata::SIMPLE_PRIMARY_KEY);
+        body.put_len_encoded_str([0]);
+        body.freeze()
+    }
+
+    #[test]
+    fn table_map_should_be_decoded_with_full_metadata() {
+        let mut tables = TableMaps::default();
+        let table = tables.decode_table_map(None, table_map(1)).unwrap();
+        assert_eq!(table.table_id, 42);
+        assert_eq!(
+            (table.schema.as_str(), table.table.as_str()),
+            ("shop", "orders")
+        );
+        assert_eq!(table.primary_key, [0]);
+
+        let id = &table.columns[0];
+        assert!(id.unsigned && !id.nullable);
+        assert_eq!(table.columns[1].metadata, 300);
+        assert_eq!(table.columns[4].column_type, ColumnType::Enum);
+        assert_eq!(table.columns[4].values, ["open", "closed"]);
+        assert_eq!(table.column_index("created_at"), Some(3));
+    }
+
+    #[test]
+    fn update_rows_should_be_decoded_into_images() {
+        let mut tables = TableMaps::default();
+        tables.decode_table_map(None, table_map(1)).unwrap();
+
+        let mut body = BytesMut::new();
+        body.put_uint_le(42, 6);
+        body.put_u16_le(STMT_END_F);
+        body.put_u16_le(2);
+        body.put_len_encoded_int(5);
+        body.put_u8(0b11111);
+        body.put_u8(0b10101);
+
+        // Before: 7, NULL, -12.50, 2024-02-29 13:45:01.250, 'open'
+        body.put_u8(0b00010);
+        body.put_u32_le(7);
+        body.put_slice(&[0x7F, 0xFF, 0xFF, 0xF3, 0xCD]);
+        let ymd = (2024 * 13 + 2) << 5 | 29;
+        let hms = 13 << 12 | 45 << 6 | 1;
+        body.put_uint((0x80_0000_0000u64) + ((ymd << 17) | hms), 5);
+        body.put_u16(2500);
+        body.put_u8(1);
+
+        // After: 8, the rest missing or unchanged
+        body.put_u8(0);
+        body.put_u32_le(8);
+        body.put_slice(&[0x80, 0x00, 0x00, 0x00, 0x00]);
+        body.put_u8(2);
+
+        let event = tables
+            .decode_rows(None, EventType::UpdateRowsV2, body.freeze())
+            .unwrap();
+        assert_eq!(event.kind, RowsEventKind::Update);
+        assert_eq!(event.rows.len(), 1);
+
+        let before = event.rows[0].before.as_ref().unwrap();
+        let value = |image: &RowImage, name| image.get_by_name(name).unwrap().clone();
+        assert_eq!(value(before, "id"), RowValue::Value(Value::UInt(7)));
+        assert_eq!(value(before, "note"), RowValue::Value(Value::Null));
+        let total = value(before, "total");
+        assert_eq!(total.as_value().unwrap().as_str(), Some("-12.50"));
+        let created_at = value(before, "created_at");
+        assert_eq!(
+            created_at.as_value().unwrap().as_str(),
+            Some("2024-02-29 13:45:01.250")
+        );
+
+        let after = event.rows[0].after.as_ref().unwrap();
+        assert_eq!(value(after, "note"), RowValue::Missing);
+        assert_eq!(
+            value(after, "total").as_value().unwrap().as_str(),
+            Some("0.00")
+        );
+        assert_eq!(value(after, "status"), RowValue::Value(Value::UInt(2)));
+
+        // The statement ended, so its table ids are forgotten
+        let unknown = tables.decode_rows(
+            None,
+            EventType::DeleteRowsV1,
+            Bytes::from_static(&[42, 0, 0, 0, 0, 0, 0, 0]),
+        );
+        assert!(matches!(unknown, Err(EventError::UnknownTable(42))));
+    }
+
+    #[test]
+    fn invalid_widths_should_be_rejected() {
+        let mut tables = TableMaps::default();
+        tables.decode_table_map(None, table_map(3)).unwrap();
+
+        let mut body = BytesMut::new();
+        body.put_uint_le(42, 6);
+        body.put_u16_le(STMT_END_F);
+        body.put_u16_le(2);
+        body.put_len_encoded_int(5);
+        body.put_u8(0b11111);
+        body.put_u8(0b01110);
+        body.put_u32_le(7);
+        body.put_slice(&[1, 0, 0]);
+
+        let event = tables.decode_rows(None, EventType::WriteRowsV2, body.freeze());
+        assert!(matches!(
+            event,
+            Err(EventError::ColumnMetadata {
+                column_type: ColumnType::Enum,
+                metadata: 3,
+            })
+        ));
+    }
+
+    #[test]
+    fn negative_times_should_be_decoded() {
+        let table = Arc::new(TableMapEvent {
+            table_id: 42,
+            flags: 0,
+            schema: "shop".into(),
+            table: "shifts".into(),
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+        });
+        let time2 = |fsp: u16, bytes: &[u8]| {
+            let column = TableColumn {
+                column_type: ColumnType::Time2,
+                metadata: fsp,
+                nullable: false,
+                unsigned: false,
+                name: None,
+                values: Vec::new(),
+            };
+            let mut buf = Bytes::copy_from_slice(bytes);
+            let mut reader = ImageReader {
+                table: &table,
+                buf: &mut buf,
+                event_type: EventType::WriteRowsV2,
+            };
+            let value = reader.read_value(&column).unwrap();
+            assert!(buf.is_empty());
+            value.as_str().unwrap().to_owned()
+        };
+
+        assert_eq!(time2(0, &[0x80, 0xC8, 0xB8]), "12:34:56");
+        assert_eq!(time2(1, &[0x7F, 0xFF, 0xFF, 0xCE]), "-00:00:00.5");
+        assert_eq!(time2(3, &[0x7F, 0xEF, 0x7C, 0xF6, 0x3C]), "-01:02:03.250");
+        assert_eq!(
+            time2(6, &[0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+            "-00:00:00.000001"
+        );
+        assert_eq!(
+            time2(6, &[0x7F, 0x5F, 0xFF, 0xF8, 0x5E, 0xE0]),
+            "-10:00:00.500000"
+        );
+    }
+
+    #[test]
+    fn timestamps_should_format_as_utc() {
+        assert_eq!(format_timestamp(1_709_214_301, 0, 0), "2024-02-29 13:45:01");
+        assert_eq!(format_timestamp(0, 0, 0), "0000-00-00 00:00:00");
+    }
+}
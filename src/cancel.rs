@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use crate::{
+    config::Config,
+    connection::{CommandError, ConnectError, Connection},
+};
+
+/// A handle that interrupts the statement currently running on the connection it was taken
+/// from, see [`Connection::cancel_token`].
+///
+/// Cancelling opens a short-lived second connection with the same options and issues
+/// `KILL QUERY <id>`. The interrupted statement then fails with
+/// [`CommandError::QueryInterrupted`].
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    connection_id: u32,
+    config: Arc<Config>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CancelError {
+    #[error("failed to open the cancel connection")]
+    Connect(#[from] ConnectError),
+
+    #[error("failed to kill the running query")]
+    Command(#[from] CommandError),
+}
+
+impl CancelToken {
+    pub(crate) fn new(connection_id: u32, config: Arc<Config>) -> Self {
+        Self {
+            connection_id,
+            config,
+        }
+    }
+
+    /// The id of the connection whose queries are cancelled by this token
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    pub async fn cancel(&self) -> Result<(), CancelError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Cancelling the query of connection {}", self.connection_id);
+
+        let mut connection = Connection::connect_config(self.config.clone()).await?;
+        connection
+            .query(&format!("KILL QUERY {}", self.connection_id))
+            .await?;
+        connection.close().await?;
+        Ok(())
+    }
+
+    /// Cancels in a background task, logging any failure
+    pub(crate) fn spawn_cancel(self) {
+        tokio::spawn(async move {
+            if let Err(_err) = self.cancel().await {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("Failed to cancel the timed out query: {}", _err);
+            }
+        });
+    }
+}
@@ -22,6 +22,27 @@ impl PacketFrame {
     pub fn take_buffer(self) -> bytes::Bytes {
         self.buffer
     }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// The first byte of the payload, which identifies OK, ERR and EOF packets
+    #[inline]
+    pub fn header(&self) -> Option<u8> {
+        self.buffer.first().copied()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -42,6 +63,12 @@ impl PacketCodec {
     pub fn sequence(&self) -> u8 {
         self.expected_sequence
     }
+
+    /// Resumes the sequence of a pipelined command whose response is read next
+    #[inline]
+    pub fn set_sequence(&mut self, sequence: u8) {
+        self.expected_sequence = sequence;
+    }
 }
 
 /// The maximum chunk size is 16MB (3 bytes)
@@ -131,7 +158,7 @@ impl Encoder<PacketFrame> for PacketCodec {
             self.expected_sequence = self.expected_sequence.wrapping_add(1);
         }
 
-        if !item.buffer.is_empty() && (item.buffer.len() % MAX_BUFFER_SIZE) == 0 {
+        if !item.buffer.is_empty() && item.buffer.len().is_multiple_of(MAX_BUFFER_SIZE) {
             dst.put_uint_le(0, 3);
             dst.put_u8(self.expected_sequence);
         }
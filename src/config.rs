@@ -0,0 +1,665 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    connection::ConnectionOption,
+    ssl::{TlsBackend, TlsInput, TlsMode, TlsOptions, TlsPin, TlsProvider, TlsRoots, UpgradeError},
+    stream::StreamType,
+    timeout::Timeouts,
+};
+
+/// An owned, cloneable version of [`ConnectionOption`] that can be kept around to open
+/// connections later on, for example by a pool or a cancel token.
+///
+/// It can be built with [`Config::builder`] or parsed from a `mysql://` or `mariadb://` URL,
+/// see [`Config::from_url`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct Config {
+    pub(crate) host: String,
+    pub(crate) username: String,
+    pub(crate) password: Vec<u8>,
+    pub(crate) database: Option<String>,
+    pub(crate) stream_type: StreamType,
+    pub(crate) collation: Option<u8>,
+    pub(crate) tls: TlsConfig,
+    pub(crate) tls_provider: Option<TlsProvider>,
+    pub(crate) timeouts: Timeouts,
+}
+
+/// The owned version of [`TlsOptions`]
+#[derive(Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub(crate) mode: TlsMode,
+    pub(crate) backend: TlsBackend,
+    pub(crate) roots: TlsRoots,
+    pub(crate) pem: Option<Source>,
+    pub(crate) key: Option<Source>,
+    pub(crate) pkcs12: Option<Source>,
+    pub(crate) pkcs12_password: Option<String>,
+    pub(crate) root: Vec<Source>,
+    pub(crate) domain: Option<String>,
+    pub(crate) pins: Vec<TlsPin>,
+}
+
+/// Where PEM material comes from. Files are read every time a connection is opened, or on
+/// reload with a [`TlsProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("failed to read {path}")]
+pub struct LoadSourceError {
+    pub path: PathBuf,
+    #[source]
+    pub source: std::io::Error,
+}
+
+impl Source {
+    pub(crate) fn load(&self) -> Result<Cow<'_, [u8]>, LoadSourceError> {
+        match self {
+            Self::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+            Self::File(path) => {
+                std::fs::read(path)
+                    .map(Cow::Owned)
+                    .map_err(|source| LoadSourceError {
+                        path: path.clone(),
+                        source,
+                    })
+            }
+        }
+    }
+}
+
+/// The TLS material of a [`TlsConfig`] with its files read
+#[derive(Debug, Default)]
+pub(crate) struct LoadedTls<'a> {
+    pem: Option<Cow<'a, [u8]>>,
+    key: Option<Cow<'a, [u8]>>,
+    pkcs12: Option<Cow<'a, [u8]>>,
+    root: Vec<Cow<'a, [u8]>>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::from(&TlsOptions::default())
+    }
+}
+
+impl TlsConfig {
+    pub fn mode(&self) -> TlsMode {
+        self.mode
+    }
+
+    pub fn backend(&self) -> TlsBackend {
+        self.backend
+    }
+
+    pub fn roots(&self) -> TlsRoots {
+        self.roots
+    }
+
+    pub fn pem(&self) -> Option<&Source> {
+        self.pem.as_ref()
+    }
+
+    pub fn key(&self) -> Option<&Source> {
+        self.key.as_ref()
+    }
+
+    pub fn pkcs12(&self) -> Option<&Source> {
+        self.pkcs12.as_ref()
+    }
+
+    pub fn root(&self) -> &[Source] {
+        &self.root
+    }
+
+    /// The name the server certificate is verified against when it is not the host
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    pub fn pins(&self) -> &[TlsPin] {
+        &self.pins
+    }
+
+    pub(crate) fn load(&self) -> Result<LoadedTls<'_>, LoadSourceError> {
+        Ok(LoadedTls {
+            pem: self.pem.as_ref().map(Source::load).transpose()?,
+            key: self.key.as_ref().map(Source::load).transpose()?,
+            pkcs12: self.pkcs12.as_ref().map(Source::load).transpose()?,
+            root: self
+                .root
+                .iter()
+                .map(Source::load)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Borrows the configuration as options, using the material loaded with
+    /// [`TlsConfig::load`]
+    pub(crate) fn options<'a>(&'a self, tls: &'a LoadedTls<'a>) -> TlsOptions<'a> {
+        TlsOptions {
+            mode: self.mode,
+            backend: self.backend,
+            roots: self.roots,
+            pem: tls.pem.as_deref(),
+            key: tls.key.as_deref(),
+            pkcs12: tls.pkcs12.as_deref(),
+            pkcs12_password: self.pkcs12_password.as_deref(),
+            root: tls.root.iter().map(|root| &**root).collect(),
+            domain: self.domain.as_deref(),
+            pins: &self.pins,
+        }
+    }
+
+    /// The files of the material, which a [`TlsProvider`] watches
+    pub(crate) fn files(&self) -> impl Iterator<Item = &Path> {
+        (self.pem.iter().chain(&self.key).chain(&self.pkcs12))
+            .chain(&self.root)
+            .filter_map(|source| match source {
+                Source::File(path) => Some(path.as_path()),
+                Source::Bytes(_) => None,
+            })
+    }
+
+    /// Adds the path of the file that failed to parse to the error
+    pub(crate) fn locate(&self, err: UpgradeError) -> UpgradeError {
+        let UpgradeError::Parse(mut err) = err else {
+            return err;
+        };
+        let source = match err.input {
+            TlsInput::Certificate => self.pem.as_ref(),
+            TlsInput::Key => self.key.as_ref(),
+            TlsInput::Pkcs12 => self.pkcs12.as_ref(),
+            TlsInput::Root(i) => self.root.get(i),
+        };
+        if let Some(Source::File(path)) = source {
+            err.path = Some(path.clone());
+        }
+        UpgradeError::Parse(err)
+    }
+}
+
+impl<'a> From<&TlsOptions<'a>> for TlsConfig {
+    fn from(options: &TlsOptions<'a>) -> Self {
+        let bytes = |bytes: &[u8]| Source::Bytes(bytes.to_vec());
+        Self {
+            mode: options.mode,
+            backend: options.backend,
+            roots: options.roots,
+            pem: options.pem.map(bytes),
+            key: options.key.map(bytes),
+            pkcs12: options.pkcs12.map(bytes),
+            pkcs12_password: options.pkcs12_password.map(ToOwned::to_owned),
+            root: options.root.iter().map(|root| bytes(root)).collect(),
+            domain: options.domain.map(ToOwned::to_owned),
+            pins: options.pins.to_vec(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::from(&ConnectionOption::default())
+    }
+}
+
+impl<'a> From<&ConnectionOption<'a>> for Config {
+    fn from(options: &ConnectionOption<'a>) -> Self {
+        Self {
+            host: options.host.to_owned(),
+            username: options.username.to_owned(),
+            password: options.password.to_vec(),
+            database: options.database.map(ToOwned::to_owned),
+            stream_type: options.stream_type,
+            collation: options.collation,
+            tls: TlsConfig::from(&options.tls),
+            tls_provider: None,
+            timeouts: options.timeouts,
+        }
+    }
+}
+
+impl Config {
+    /// The `host:port` address, or the socket path for unix sockets
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn password(&self) -> &[u8] {
+        &self.password
+    }
+
+    pub fn database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+
+    pub fn stream_type(&self) -> StreamType {
+        self.stream_type
+    }
+
+    pub fn collation(&self) -> Option<u8> {
+        self.collation
+    }
+
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    /// The provider of the TLS connectors, if the material is reloaded at runtime
+    pub fn tls_provider(&self) -> Option<&TlsProvider> {
+        self.tls_provider.as_ref()
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Borrows the configuration as connection options, using the TLS material loaded with
+    /// [`TlsConfig::load`]
+    pub(crate) fn options<'a>(&'a self, tls: &'a LoadedTls<'a>) -> ConnectionOption<'a> {
+        ConnectionOption {
+            host: &self.host,
+            username: &self.username,
+            password: &self.password,
+            database: self.database.as_deref(),
+            stream_type: self.stream_type,
+            collation: self.collation,
+            tls: self.tls.options(tls),
+            timeouts: self.timeouts,
+        }
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("database", &self.database)
+            .field("stream_type", &self.stream_type)
+            .field("collation", &self.collation)
+            .field("tls", &self.tls)
+            .field("tls_provider", &self.tls_provider.is_some())
+            .field("timeouts", &self.timeouts)
+            .finish()
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("mode", &self.mode)
+            .field("backend", &self.backend)
+            .field("roots", &self.roots)
+            .field("pem", &self.pem)
+            .field("key", &self.key)
+            .field("pkcs12", &self.pkcs12)
+            .field(
+                "pkcs12_password",
+                &self.pkcs12_password.as_ref().map(|_| "<redacted>"),
+            )
+            .field("root", &self.root)
+            .field("domain", &self.domain)
+            .field("pins", &self.pins)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigError {
+    #[error("the host must not be empty")]
+    MissingHost,
+
+    #[error("tls mode VERIFY_IDENTITY needs a domain to verify the server certificate against")]
+    MissingDomain,
+
+    #[error("a client key was given without its certificate")]
+    KeyWithoutCert,
+
+    #[error("a client certificate was given without its key")]
+    CertWithoutKey,
+
+    #[error("a PKCS#12 bundle was given along with a client certificate or key")]
+    Pkcs12WithCert,
+
+    #[error("tls options were changed after setting a tls provider, which builds the connectors")]
+    TlsWithProvider,
+
+    #[error("unix socket {0} is not supported on this platform")]
+    UnsupportedSocket(String),
+}
+
+impl From<Vec<u8>> for Source {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Source {
+    fn from(bytes: &[u8]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<PathBuf> for Source {
+    fn from(path: PathBuf) -> Self {
+        Self::File(path)
+    }
+}
+
+impl From<&Path> for Source {
+    fn from(path: &Path) -> Self {
+        Self::File(path.to_path_buf())
+    }
+}
+
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Connects over the unix socket at `path`
+    #[cfg(unix)]
+    pub(crate) fn set_socket(&mut self, path: &str) -> Result<(), ConfigError> {
+        self.host = path.to_owned();
+        self.stream_type = StreamType::Unix;
+        Ok(())
+    }
+
+    /// Fails as other platforms do not support unix sockets
+    #[cfg(not(unix))]
+    pub(crate) fn set_socket(&mut self, path: &str) -> Result<(), ConfigError> {
+        Err(ConfigError::UnsupportedSocket(path.to_owned()))
+    }
+
+    /// A builder starting from this configuration, to derive a slightly different one
+    pub fn to_builder(&self) -> ConfigBuilder {
+        ConfigBuilder {
+            config: self.clone(),
+        }
+    }
+
+    /// Checks that the options are consistent with each other
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.host.is_empty() {
+            return Err(ConfigError::MissingHost);
+        }
+        if self.tls.mode == TlsMode::VerifyFull {
+            // A unix socket has no host name to verify against
+            match self.tls.domain.as_deref() {
+                Some("") => return Err(ConfigError::MissingDomain),
+                #[cfg(unix)]
+                None if self.stream_type == StreamType::Unix => {
+                    return Err(ConfigError::MissingDomain)
+                }
+                _ => {}
+            }
+        }
+        if let Some(provider) = &self.tls_provider {
+            if *provider.config() != self.tls {
+                return Err(ConfigError::TlsWithProvider);
+            }
+        }
+        match (&self.tls.pem, &self.tls.key) {
+            (Some(_), _) | (_, Some(_)) if self.tls.pkcs12.is_some() => {
+                Err(ConfigError::Pkcs12WithCert)
+            }
+            (None, Some(_)) => Err(ConfigError::KeyWithoutCert),
+            (Some(_), None) => Err(ConfigError::CertWithoutKey),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A fluent builder for [`Config`], validated when built
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// The `host:port` address to connect to over TCP
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.config.host = host.into();
+        self.config.stream_type = StreamType::Tcp;
+        self
+    }
+
+    /// The path of a unix socket to connect to
+    #[cfg(unix)]
+    pub fn socket(mut self, path: impl Into<String>) -> Self {
+        self.config.host = path.into();
+        self.config.stream_type = StreamType::Unix;
+        self
+    }
+
+    /// Sets a unix socket read from an option file, failing on platforms without them
+    pub(crate) fn try_socket(mut self, path: &str) -> Result<Self, ConfigError> {
+        self.config.set_socket(path)?;
+        Ok(self)
+    }
+
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.config.username = username.into();
+        self
+    }
+
+    pub fn password(mut self, password: impl AsRef<[u8]>) -> Self {
+        self.config.password = password.as_ref().to_vec();
+        self
+    }
+
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.config.database = Some(database.into());
+        self
+    }
+
+    pub fn collation(mut self, collation: u8) -> Self {
+        self.config.collation = Some(collation);
+        self
+    }
+
+    pub fn tls_mode(mut self, mode: TlsMode) -> Self {
+        self.config.tls.mode = mode;
+        self
+    }
+
+    /// The TLS library, when both the `native-tls` and `rustls` features are enabled
+    pub fn tls_backend(mut self, backend: TlsBackend) -> Self {
+        self.config.tls.backend = backend;
+        self
+    }
+
+    /// The certificate authorities trusted in addition to [`ConfigBuilder::tls_root`]
+    pub fn tls_roots(mut self, roots: TlsRoots) -> Self {
+        self.config.tls.roots = roots;
+        self
+    }
+
+    /// The PEM client certificate followed by its intermediates, as bytes or a file path
+    pub fn tls_cert(mut self, cert: impl Into<Source>) -> Self {
+        self.config.tls.pem = Some(cert.into());
+        self
+    }
+
+    /// The PEM client key in the PKCS#8, PKCS#1 or SEC1 format, as bytes or a file path
+    pub fn tls_key(mut self, key: impl Into<Source>) -> Self {
+        self.config.tls.key = Some(key.into());
+        self
+    }
+
+    /// A DER PKCS#12 bundle of the client key and certificates with its password, instead
+    /// of [`ConfigBuilder::tls_cert`] and [`ConfigBuilder::tls_key`]
+    pub fn tls_pkcs12(mut self, bundle: impl Into<Source>, password: impl Into<String>) -> Self {
+        self.config.tls.pkcs12 = Some(bundle.into());
+        self.config.tls.pkcs12_password = Some(password.into());
+        self
+    }
+
+    /// Adds a PEM bundle of root certificates to trust, as bytes or a file path
+    pub fn tls_root(mut self, root: impl Into<Source>) -> Self {
+        self.config.tls.root.push(root.into());
+        self
+    }
+
+    /// The name the server certificate is verified against, instead of the host
+    pub fn tls_domain(mut self, domain: impl Into<String>) -> Self {
+        self.config.tls.domain = Some(domain.into());
+        self
+    }
+
+    /// Adds a fingerprint the server certificate may match, instead of being verified by a
+    /// certificate authority
+    pub fn tls_pin(mut self, pin: TlsPin) -> Self {
+        self.config.tls.pins.push(pin);
+        self
+    }
+
+    /// Takes the TLS connectors from a provider that can be reloaded, replacing the TLS
+    /// options by those of the provider. Later `tls_*` calls are rejected when building.
+    pub fn tls_provider(mut self, provider: TlsProvider) -> Self {
+        self.config.tls = provider.config().clone();
+        self.config.tls_provider = Some(provider);
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.config.timeouts = timeouts;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.connect = Some(timeout);
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.read = Some(timeout);
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.write = Some(timeout);
+        self
+    }
+
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.statement = Some(timeout);
+        self
+    }
+
+    pub fn cancel_on_timeout(mut self, cancel: bool) -> Self {
+        self.config.timeouts.cancel_on_timeout = cancel;
+        self
+    }
+
+    pub fn build(self) -> Result<Config, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_owned<T: Clone + Send + Sync + 'static>() {}
+
+    #[test]
+    fn config_should_be_owned() {
+        assert_owned::<Config>();
+        assert_owned::<ConfigBuilder>();
+    }
+
+    #[test]
+    fn builder_should_build_a_config() {
+        let config = Config::builder()
+            .host("db:3306")
+            .username("app")
+            .password("secret")
+            .database("shop")
+            .tls_mode(TlsMode::VerifyFull)
+            .tls_domain("db.internal")
+            .tls_cert(PathBuf::from("/etc/client.pem"))
+            .tls_key(PathBuf::from("/etc/client.key"))
+            .statement_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        assert_eq!(config.host(), "db:3306");
+        assert_eq!(config.password(), b"secret");
+        assert_eq!(config.database(), Some("shop"));
+        assert_eq!(config.tls().domain(), Some("db.internal"));
+        assert_eq!(config.timeouts().statement, Some(Duration::from_secs(5)));
+
+        let other = config.to_builder().database("other").build().unwrap();
+        assert_eq!(other.database(), Some("other"));
+        assert_eq!(other.username(), "app");
+    }
+
+    #[test]
+    fn inconsistent_configs_should_be_rejected() {
+        let verify = Config::builder()
+            .tls_mode(TlsMode::VerifyFull)
+            .tls_domain("")
+            .build();
+        assert_eq!(verify.unwrap_err(), ConfigError::MissingDomain);
+        #[cfg(unix)]
+        {
+            let socket = Config::builder()
+                .socket("/run/mysqld/mysqld.sock")
+                .tls_mode(TlsMode::VerifyFull);
+            assert_eq!(
+                socket.clone().build().unwrap_err(),
+                ConfigError::MissingDomain
+            );
+            assert!(socket.tls_domain("db.internal").build().is_ok());
+        }
+
+        let key = Config::builder().tls_key(b"key".as_slice()).build();
+        assert_eq!(key.unwrap_err(), ConfigError::KeyWithoutCert);
+
+        let cert = Config::builder().tls_cert(b"cert".as_slice()).build();
+        assert_eq!(cert.unwrap_err(), ConfigError::CertWithoutKey);
+
+        let pkcs12 = Config::builder()
+            .tls_pkcs12(b"bundle".as_slice(), "secret")
+            .tls_key(b"key".as_slice())
+            .build();
+        assert_eq!(pkcs12.unwrap_err(), ConfigError::Pkcs12WithCert);
+
+        let host = Config::builder().host("").build();
+        assert_eq!(host.unwrap_err(), ConfigError::MissingHost);
+
+        // The connector of a disabled provider would silently connect in plaintext
+        let disabled = Config::builder()
+            .tls_mode(TlsMode::Disable)
+            .build()
+            .unwrap();
+        let provider = TlsProvider::new(disabled.tls().clone()).unwrap();
+        let require = Config::builder()
+            .tls_provider(provider.clone())
+            .tls_mode(TlsMode::Require)
+            .build();
+        assert_eq!(require.unwrap_err(), ConfigError::TlsWithProvider);
+        let config = Config::builder()
+            .tls_mode(TlsMode::Require)
+            .tls_provider(provider)
+            .build()
+            .unwrap();
+        assert_eq!(config.tls().mode(), TlsMode::Disable);
+    }
+}
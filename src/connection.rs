@@ -1,22 +1,55 @@
-use tokio::net::{TcpStream, UnixStream};
+use std::sync::Arc;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{net::TcpStream, sync::watch};
 use tokio_util::codec::Framed;
 
 use crate::{
-    codec::PacketCodec,
+    cancel::CancelToken,
+    codec::{PacketCodec, PacketFrame},
+    config::{Config, LoadSourceError, LoadedTls},
     my::{stream::StreamTransporter, MyStream},
     protocol::{
-        client::{com::ComPing, HandshakeResponsePacket, SslPacket},
+        client::{
+            com::{
+                ComInitDb, ComPing, ComProcessKill, ComQuery, ComQuit, ComResetConnection,
+                ComSetOption, ComStatistics, SetOption,
+            },
+            HandshakeResponsePacket, SslPacket,
+        },
+        collation,
+        error::ColumnDefinitionError,
         plugin::{AuthType, AuthTypeError},
-        server::{error::InitialHandshakeError, InitialHanshakePacket},
-        Capability,
+        server::{
+            error::{InitialHandshakeError, ParseStatisticsError},
+            ErrPacket, InitialHanshakePacket, OkPacket, Progress, Statistics,
+        },
+        Capability, ColumnDefinition, ServerStatus,
     },
-    ssl::{into_tls_parts, TlsMode, TlsOptions, UpgradeStream},
-    stream::{Stream, StreamType},
+    result::{ResultSet, Row},
+    ssl::{host_name, into_tls_parts, TlsInfo, TlsMode, TlsOptions, UpgradeStream},
+    stream::{Stream, StreamType, Transport},
+    timeout::{BrokenConnection, Timeouts},
+    transaction::{self, TransactionState},
+    BytesExt, DecodePacket, EncodePacket,
 };
 
+/// Decodes a row of the text or the binary protocol
+pub(crate) type DecodeRow = fn(PacketFrame, Arc<[ColumnDefinition]>) -> Result<Row, std::io::Error>;
+
+/// The start of a response that may hold rows
+pub(crate) enum ResultHead {
+    Ok(OkPacket),
+    Columns(Arc<[ColumnDefinition]>),
+}
+
 #[derive(Debug)]
 pub struct Connection {
     stream: MyStream,
+    config: Arc<Config>,
+    transaction: TransactionState,
+    progress: watch::Sender<Option<Progress>>,
 }
 
 #[derive(Debug)]
@@ -26,7 +59,20 @@ pub struct ConnectionOption<'a> {
     pub password: &'a [u8],
     pub database: Option<&'a str>,
     pub stream_type: StreamType,
+    /// Overrides the collation announced by the server in its handshake
+    pub collation: Option<u8>,
     pub tls: TlsOptions<'a>,
+    pub timeouts: Timeouts,
+}
+
+impl<'a> ConnectionOption<'a> {
+    /// The name used for SNI and to verify the server certificate: the TLS domain override,
+    /// or else the host being dialed
+    pub fn tls_domain(&self) -> &'a str {
+        self.tls
+            .domain
+            .unwrap_or_else(|| host_name(self.host, self.stream_type))
+    }
 }
 
 impl<'a> Default for ConnectionOption<'a> {
@@ -36,8 +82,10 @@ impl<'a> Default for ConnectionOption<'a> {
             username: "",
             password: &[],
             stream_type: StreamType::default(),
+            collation: None,
             tls: TlsOptions::default(),
             database: None,
+            timeouts: Timeouts::default(),
         }
     }
 }
@@ -61,13 +109,113 @@ pub enum ConnectError {
 
     #[error("failed to upgrade to tls")]
     UpgradeError(#[from] crate::ssl::UpgradeError),
+
+    #[error("timed out while connecting")]
+    Timeout,
+
+    #[error("failed to load the tls material")]
+    LoadTls(#[from] LoadSourceError),
+
+    #[error(transparent)]
+    Server(#[from] ErrPacket),
+
+    #[error("unexpected packet with header {0:#04x} in reply to the login")]
+    UnexpectedPacket(u8),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error(transparent)]
+    Io(std::io::Error),
+
+    #[error("the command timed out, the connection is now broken")]
+    Timeout,
+
+    #[error(transparent)]
+    Broken(#[from] BrokenConnection),
+
+    #[error(transparent)]
+    Server(#[from] ErrPacket),
+
+    #[error("unexpected packet with header {0:#04x}")]
+    UnexpectedPacket(u8),
+
+    #[error("failed to parse the server statistics")]
+    Statistics(#[from] ParseStatisticsError),
+
+    #[error("failed to decode a column definition")]
+    ColumnDefinition(#[from] ColumnDefinitionError),
+
+    #[error("query execution was interrupted")]
+    QueryInterrupted,
+
+    #[error("the statement expects {expected} parameters but {actual} were given")]
+    ParamCount { expected: usize, actual: usize },
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::TimedOut {
+            CommandError::Timeout
+        } else if BrokenConnection::is_io_error(&err) {
+            CommandError::Broken(BrokenConnection)
+        } else {
+            CommandError::Io(err)
+        }
+    }
 }
 
 impl Connection {
     pub async fn connect<'a>(options: &'a ConnectionOption<'a>) -> Result<Self, ConnectError> {
-        let stream = match options.stream_type {
-            StreamType::Tcp => Stream::Tcp(TcpStream::connect(options.host).await?),
-            StreamType::Unix => Stream::Unix(UnixStream::connect(options.host).await?),
+        Self::connect_config(Arc::new(Config::from(options))).await
+    }
+
+    pub async fn connect_with_config(config: &Config) -> Result<Self, ConnectError> {
+        Self::connect_config(Arc::new(config.clone())).await
+    }
+
+    /// Connects over a transport opened by the caller instead of dialing the host, which is
+    /// still used as the TLS server name. Cancel tokens of the connection dial the host.
+    pub async fn connect_with_stream<'a>(
+        options: &'a ConnectionOption<'a>,
+        stream: impl Transport + 'static,
+    ) -> Result<Self, ConnectError> {
+        let config = Arc::new(Config::from(options));
+        Self::connect_stream(config, Some(Stream::custom(stream))).await
+    }
+
+    pub(crate) async fn connect_config(config: Arc<Config>) -> Result<Self, ConnectError> {
+        Self::connect_stream(config, None).await
+    }
+
+    /// Connects over the stream, or else dials the host of the config
+    pub(crate) async fn connect_stream(
+        config: Arc<Config>,
+        stream: Option<Stream>,
+    ) -> Result<Self, ConnectError> {
+        match config.timeouts().connect {
+            Some(timeout) => tokio::time::timeout(timeout, Self::connect_inner(config, stream))
+                .await
+                .map_err(|_| ConnectError::Timeout)?,
+            None => Self::connect_inner(config, stream).await,
+        }
+    }
+
+    async fn connect_inner(
+        config: Arc<Config>,
+        stream: Option<Stream>,
+    ) -> Result<Self, ConnectError> {
+        // A provider holds its connector already built
+        let tls = match config.tls_provider() {
+            Some(_) => LoadedTls::default(),
+            None => config.tls().load()?,
+        };
+        let options = config.options(&tls);
+        let stream = match (stream, options.stream_type) {
+            (Some(stream), _) => stream,
+            (None, StreamType::Tcp) => Stream::Tcp(TcpStream::connect(options.host).await?),
+            #[cfg(unix)]
+            (None, StreamType::Unix) => Stream::Unix(UnixStream::connect(options.host).await?),
         };
         let stream = StreamTransporter::Left(stream);
         let codec = PacketCodec::new();
@@ -80,6 +228,14 @@ impl Connection {
         tracing::debug!("Received handshake packet");
 
         mystream.handshake_packet(handshake);
+        for capability in [Capability::PROGRESS, Capability::COM_MULTI] {
+            if mystream.context().has_server_capability(capability) {
+                mystream.context_mut().set_client_capability(capability);
+            }
+        }
+        if let Some(collation) = options.collation {
+            mystream.context_mut().set_client_collation(collation);
+        }
 
         if matches!(
             options.tls.mode,
@@ -89,7 +245,18 @@ impl Connection {
             return Err(ConnectError::TlsCapability);
         }
 
-        let parts = into_tls_parts(&options.tls).await?;
+        // Prefer falls back to plaintext when the server has no TLS
+        let parts = if !mystream.context().has_server_capability(Capability::SSL) {
+            None
+        } else if let Some(provider) = config.tls_provider() {
+            provider
+                .connector()
+                .map(|connector| (options.tls_domain(), connector))
+        } else {
+            into_tls_parts(&options.tls, options.tls_domain())
+                .await
+                .map_err(|err| config.tls().locate(err))?
+        };
 
         let stream = if parts.is_some() {
             #[cfg(feature = "tracing")]
@@ -130,26 +297,402 @@ impl Connection {
         stream.send_packet(handshake).await?;
 
         let packet = stream.recv_packet().await?;
+        match packet.header() {
+            Some(OkPacket::HEADER) => {
+                let ok = OkPacket::decode_packet(packet, stream.context())?;
+                stream.context_mut().for_ok_packet(&ok);
+            }
+            Some(ErrPacket::HEADER) => {
+                return Err(ErrPacket::decode_packet(packet, stream.context())?.into());
+            }
+            Some(header) => return Err(ConnectError::UnexpectedPacket(header)),
+            None => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        }
 
-        println!("{:?}", packet);
+        let database = options.database.map(ToOwned::to_owned);
+        stream.context_mut().set_database(database);
+        stream.set_timeouts(options.timeouts);
+
+        Ok(Self {
+            stream,
+            config,
+            transaction: TransactionState::default(),
+            progress: watch::channel(None).0,
+        })
+    }
+
+    /// Whether a timeout or an I/O failure poisoned the connection. A broken connection refuses
+    /// every command with [`CommandError::Broken`] and should be dropped.
+    pub fn is_broken(&self) -> bool {
+        self.stream.is_broken()
+    }
+
+    /// The negotiated TLS version and cipher, or `None` for a plaintext connection
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        self.stream.tls_info()
+    }
 
-        Ok(Self { stream })
+    /// The id the server assigned to this connection
+    pub fn connection_id(&self) -> u32 {
+        self.stream.context().connection_id()
     }
 
-    pub async fn ping(&mut self) -> Result<(), std::io::Error> {
+    /// Creates a [`CancelToken`] that can interrupt the queries of this connection from
+    /// another task
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken::new(self.connection_id(), self.config.clone())
+    }
+
+    /// The schema currently in use, as tracked from the handshake, `COM_INIT_DB` and session
+    /// state changes
+    pub fn database(&self) -> Option<&str> {
+        self.stream.context().database()
+    }
+
+    pub async fn ping(&mut self) -> Result<(), CommandError> {
         #[cfg(feature = "tracing")]
         tracing::debug!("Sending ping packet");
         let ping = ComPing::new();
-        self.stream.send_packet(ping).await?;
-        let packet = self.stream.recv_packet().await?;
-        println!("{:?}", packet);
+        self.send(ping).await?;
+        self.recv_ok().await?;
+        Ok(())
+    }
+
+    /// Changes the default schema of the connection with `COM_INIT_DB`
+    pub async fn use_database(&mut self, database: &str) -> Result<(), CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending init db packet");
+        self.send(ComInitDb::new(database)).await?;
+        self.recv_ok().await?;
+        self.stream
+            .context_mut()
+            .set_database(Some(database.to_owned()));
+        Ok(())
+    }
+
+    /// Sends `COM_QUIT` and shuts down the underlying stream
+    pub async fn close(mut self) -> Result<(), CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending quit packet");
+        self.stream.send_packet(ComQuit::new()).await?;
+        self.stream.shutdown().await?;
+        Ok(())
+    }
+
+    /// Fetches the server statistics with `COM_STATISTICS`
+    pub async fn statistics(&mut self) -> Result<Statistics, CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending statistics packet");
+        self.send(ComStatistics::new()).await?;
+        let packet = self.recv_non_err().await?;
+        let payload = packet.take_buffer();
+        let statistics = String::from_utf8_lossy(&payload).parse()?;
+        Ok(statistics)
+    }
+
+    /// Enables or disables multiple statements per query with `COM_SET_OPTION`
+    pub async fn set_multi_statements(&mut self, enabled: bool) -> Result<(), CommandError> {
+        let option = if enabled {
+            SetOption::MultiStatementsOn
+        } else {
+            SetOption::MultiStatementsOff
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending set option packet {:?}", option);
+        self.send(ComSetOption::new(option)).await?;
+        self.recv_ok().await?;
+
+        let context = self.stream.context_mut();
+        if enabled {
+            context.set_client_capability(Capability::MULTI_STATEMENTS);
+        } else {
+            context.remove_client_capability(Capability::MULTI_STATEMENTS);
+        }
+        Ok(())
+    }
+
+    /// Resets the session state with `COM_RESET_CONNECTION`, rolling back any transaction and
+    /// dropping temporary tables, user variables and prepared statements
+    pub async fn reset(&mut self) -> Result<(), CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending reset connection packet");
+        self.stream.send_packet(ComResetConnection::new()).await?;
+        self.transaction.clear();
+        self.recv_ok().await?;
+        Ok(())
+    }
+
+    /// Terminates the connection with the given id with `COM_PROCESS_KILL`
+    pub async fn kill(&mut self, connection_id: u32) -> Result<(), CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending process kill packet for {}", connection_id);
+        self.send(ComProcessKill::new(connection_id)).await?;
+        self.recv_ok().await?;
+        Ok(())
+    }
+
+    /// Runs a query with the text protocol and returns its first result set, discarding any
+    /// following ones
+    pub async fn query(&mut self, query: &str) -> Result<ResultSet, CommandError> {
+        let mut results = self.query_multi(query).await?;
+        Ok(if results.is_empty() {
+            ResultSet::default()
+        } else {
+            results.swap_remove(0)
+        })
+    }
+
+    /// Runs a query with the text protocol and returns every result set it produced
+    pub async fn query_multi(&mut self, query: &str) -> Result<Vec<ResultSet>, CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending query packet");
+        self.send(ComQuery::new(query)).await?;
+
+        self.recv_results(Row::decode_text).await
+    }
+
+    /// The status flags of the last OK or EOF packet
+    pub(crate) fn status_flags(&self) -> ServerStatus {
+        self.stream.context().status_flags()
+    }
+
+    /// The character set strings are sent in, `None` for collations that are not known
+    pub(crate) fn client_charset(&self) -> Option<&'static str> {
+        collation::charset(self.stream.context().client_collation())
+    }
+
+    /// Whether both sides negotiated MariaDB's `COM_MULTI`
+    pub(crate) fn supports_com_multi(&self) -> bool {
+        self.stream
+            .context()
+            .has_client_capability(Capability::COM_MULTI)
+    }
+
+    pub(crate) fn stream(&self) -> &MyStream {
+        &self.stream
+    }
+
+    pub(crate) fn stream_mut(&mut self) -> &mut MyStream {
+        &mut self.stream
+    }
+
+    /// Gives up the connection for a dedicated protocol like replication
+    pub(crate) fn into_stream(self) -> MyStream {
+        self.stream
+    }
+
+    pub(crate) fn transaction_mut(&mut self) -> &mut TransactionState {
+        &mut self.transaction
+    }
+
+    /// Rolls back a [`Transaction`](crate::transaction::Transaction) whose guard was dropped
+    /// without commit
+    pub(crate) async fn rollback_abandoned(&mut self) -> Result<(), CommandError> {
+        if let Some(level) = self.transaction.take_pending_rollback() {
+            let statement = transaction::rollback_statement(level);
+            #[cfg(feature = "tracing")]
+            tracing::debug!("Sending query packet {}", statement);
+            self.stream.send_packet(ComQuery::new(&statement)).await?;
+            self.recv_result_set().await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a command packet, first rolling back any abandoned transaction
+    pub(crate) async fn send<P>(&mut self, packet: P) -> Result<(), CommandError>
+    where
+        P: EncodePacket<PacketFrame>,
+        P::Error: Into<std::io::Error>,
+    {
+        self.rollback_abandoned().await?;
+        // Reports of the previous statement do not belong to this one
+        self.progress.send_replace(None);
+        self.stream.send_packet(packet).await?;
         Ok(())
     }
+
+    /// Receives a [text protocol result set](https://mariadb.com/kb/en/result-set-packets/)
+    async fn recv_result_set(&mut self) -> Result<ResultSet, CommandError> {
+        self.recv_result(Row::decode_text).await
+    }
+
+    /// Receives every result set of a command, as announced by `MORE_RESULTS_EXISTS`
+    pub(crate) async fn recv_results(
+        &mut self,
+        decode_row: DecodeRow,
+    ) -> Result<Vec<ResultSet>, CommandError> {
+        let mut results = vec![self.recv_result(decode_row).await?];
+        while self
+            .status_flags()
+            .contains(ServerStatus::MORE_RESULTS_EXISTS)
+        {
+            results.push(self.recv_result(decode_row).await?);
+        }
+        Ok(results)
+    }
+
+    /// Receives a result set whose rows are decoded with `decode_row`
+    pub(crate) async fn recv_result(
+        &mut self,
+        decode_row: DecodeRow,
+    ) -> Result<ResultSet, CommandError> {
+        let columns = match self.recv_result_head().await? {
+            ResultHead::Ok(status) => {
+                return Ok(ResultSet {
+                    status,
+                    ..Default::default()
+                })
+            }
+            ResultHead::Columns(columns) => columns,
+        };
+        let mut rows = Vec::new();
+        let status = self.recv_rows(&columns, decode_row, &mut rows).await?;
+        Ok(ResultSet {
+            columns,
+            rows,
+            status,
+        })
+    }
+
+    /// Receives either the OK packet of a statement without rows or the column definitions of
+    /// a result set
+    pub(crate) async fn recv_result_head(&mut self) -> Result<ResultHead, CommandError> {
+        let packet = self.recv_non_err().await?;
+        match packet.header() {
+            Some(OkPacket::HEADER) => {
+                let status = OkPacket::decode_packet(packet, self.stream.context())?;
+                self.stream.context_mut().for_ok_packet(&status);
+                return Ok(ResultHead::Ok(status));
+            }
+            Some(header @ 0xFB) => {
+                // The server waits for the content of a `LOCAL INFILE`, which is never sent
+                self.stream.mark_broken();
+                return Err(CommandError::UnexpectedPacket(header));
+            }
+            _ => {}
+        }
+
+        let mut payload = packet.take_buffer();
+        let column_count = payload.get_len_encoded_int()? as usize;
+        Ok(ResultHead::Columns(self.recv_columns(column_count).await?))
+    }
+
+    /// Receives `count` column definitions and the EOF packet that ends them
+    pub(crate) async fn recv_columns(
+        &mut self,
+        count: usize,
+    ) -> Result<Arc<[ColumnDefinition]>, CommandError> {
+        let mut columns = Vec::with_capacity(count);
+        for _ in 0..count {
+            let packet = self.recv().await?;
+            columns.push(ColumnDefinition::decode_packet(
+                packet,
+                self.stream.context(),
+            )?);
+        }
+
+        if count > 0
+            && !self
+                .stream
+                .context()
+                .has_client_capability(Capability::CLIENT_DEPRECATE_EOF)
+        {
+            self.recv_ok().await?;
+        }
+        Ok(columns.into())
+    }
+
+    /// Receives rows into `rows` until the EOF packet that ends them, which is returned
+    pub(crate) async fn recv_rows(
+        &mut self,
+        columns: &Arc<[ColumnDefinition]>,
+        decode_row: DecodeRow,
+        rows: &mut Vec<Row>,
+    ) -> Result<OkPacket, CommandError> {
+        loop {
+            let packet = self.recv_non_err().await?;
+            if OkPacket::is_eof(&packet) {
+                let status = OkPacket::decode_packet(packet, self.stream.context())?;
+                self.stream.context_mut().for_ok_packet(&status);
+                return Ok(status);
+            }
+            rows.push(decode_row(packet, columns.clone())?);
+        }
+    }
+
+    /// Watches the progress reports of MariaDB for the running statement, e.g. to draw a
+    /// progress bar during `ALTER TABLE`. The channel keeps the last report until the next
+    /// command starts.
+    pub fn progress(&self) -> watch::Receiver<Option<Progress>> {
+        self.progress.subscribe()
+    }
+
+    /// Receives the next packet of a command response, publishing progress reports on the way.
+    /// When it times out and the connection is configured to, the running query is killed from
+    /// a side connection.
+    pub(crate) async fn recv(&mut self) -> Result<PacketFrame, CommandError> {
+        loop {
+            let packet = match self.stream.recv_packet().await {
+                Ok(packet) => packet,
+                Err(err) => {
+                    let err = CommandError::from(err);
+                    if matches!(err, CommandError::Timeout)
+                        && self.config.timeouts().cancel_on_timeout
+                    {
+                        self.cancel_token().spawn_cancel();
+                    }
+                    return Err(err);
+                }
+            };
+
+            let context = self.stream.context();
+            if !(context.has_client_capability(Capability::PROGRESS)
+                && Progress::is_progress(&packet))
+            {
+                return Ok(packet);
+            }
+            let progress = Progress::decode_packet(packet, context)?;
+            #[cfg(feature = "tracing")]
+            tracing::debug!("Received progress {:?}", progress);
+            self.progress.send_replace(Some(progress));
+        }
+    }
+
+    /// Receives the next packet, turning an ERR packet into a [`CommandError::Server`]
+    pub(crate) async fn recv_non_err(&mut self) -> Result<PacketFrame, CommandError> {
+        let packet = self.recv().await?;
+        if packet.header() == Some(ErrPacket::HEADER) {
+            let err = ErrPacket::decode_packet(packet, self.stream.context())?;
+            if err.is_query_interrupted() {
+                return Err(CommandError::QueryInterrupted);
+            }
+            return Err(err.into());
+        }
+        Ok(packet)
+    }
+
+    /// Receives an OK or EOF packet and applies its status to the context
+    pub(crate) async fn recv_ok(&mut self) -> Result<OkPacket, CommandError> {
+        let packet = self.recv_non_err().await?;
+        match packet.header() {
+            Some(OkPacket::HEADER | OkPacket::EOF_HEADER) => {
+                let ok = OkPacket::decode_packet(packet, self.stream.context())?;
+                self.stream.context_mut().for_ok_packet(&ok);
+                Ok(ok)
+            }
+            Some(header) => Err(CommandError::UnexpectedPacket(header)),
+            None => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use bytes::{BufMut, BytesMut};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+
     use super::*;
+    use crate::{protocol::ColumnType, BufMutExt};
 
     // Helper trait to assert Send and Sync
     trait AssertSendSync: Send + Sync {}
@@ -164,4 +707,318 @@ mod tests {
         let _connection = Connection::connect(&options).await.unwrap();
         assert_send_sync::<Connection>();
     }
+
+    pub(crate) const SERVER_CAPABILITIES: Capability = Capability::CLIENT_MYSQL
+        .union(Capability::CLIENT_PROTOCOL_41)
+        .union(Capability::TRANSACTIONS)
+        .union(Capability::SECURE_CONNECTION)
+        .union(Capability::PLUGIN_AUTH);
+
+    pub(crate) const OK: &[u8] = &[0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00];
+
+    /// The initial handshake of a MySQL 8 server with `mysql_native_password`
+    pub(crate) fn initial_handshake(capabilities: Capability) -> Vec<u8> {
+        let extended = capabilities.to_extended();
+        let capabilities = capabilities.to_default();
+        let mut packet = vec![0x0A];
+        packet.extend_from_slice(b"8.0.36\0");
+        packet.extend_from_slice(&7u32.to_le_bytes());
+        packet.extend_from_slice(b"abcdefgh\0");
+        packet.extend_from_slice(&(capabilities as u16).to_le_bytes());
+        packet.push(45);
+        packet.extend_from_slice(&2u16.to_le_bytes());
+        packet.extend_from_slice(&((capabilities >> 16) as u16).to_le_bytes());
+        packet.push(21);
+        packet.extend_from_slice(&[0; 6]);
+        // MariaDB's extended capabilities, read when `CLIENT_MYSQL` is unset
+        packet.extend_from_slice(&extended.to_le_bytes());
+        packet.extend_from_slice(b"ijklmnopqrst\0");
+        packet.extend_from_slice(b"mysql_native_password\0");
+        packet
+    }
+
+    pub(crate) async fn write_packet<S: AsyncWrite + Unpin>(
+        stream: &mut S,
+        sequence: u8,
+        payload: &[u8],
+    ) {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(sequence);
+        packet.extend_from_slice(payload);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    pub(crate) async fn read_packet<S: AsyncRead + Unpin>(stream: &mut S) -> (u8, Vec<u8>) {
+        let mut header = [0; 4];
+        stream.read_exact(&mut header).await.unwrap();
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        (header[3], payload)
+    }
+
+    /// The definition of a `BIGINT` column
+    pub(crate) fn column(name: &str) -> Vec<u8> {
+        let mut bytes = BytesMut::new();
+        for part in ["def", "app", "", "", name, name] {
+            bytes.put_len_encoded_str(part);
+        }
+        bytes.put_len_encoded_int(0x0c);
+        bytes.put_u16_le(63);
+        bytes.put_u32_le(20);
+        bytes.put_u8(ColumnType::LongLong as u8);
+        bytes.put_u16_le(0);
+        bytes.put_u8(0);
+        bytes.put_u16_le(0);
+        bytes.to_vec()
+    }
+
+    pub(crate) fn eof(status: ServerStatus) -> Vec<u8> {
+        let mut bytes = vec![0xFE, 0, 0];
+        bytes.extend_from_slice(&status.bits().to_le_bytes());
+        bytes
+    }
+
+    pub(crate) fn ok(status: ServerStatus) -> Vec<u8> {
+        let mut bytes = vec![0x00, 0, 0];
+        bytes.extend_from_slice(&status.bits().to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    /// The response to `COM_STMT_PREPARE` of statement 1, before the definitions
+    pub(crate) fn prepare_ok(num_columns: u16, num_params: u16) -> Vec<u8> {
+        let mut bytes = vec![0x00];
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&num_columns.to_le_bytes());
+        bytes.extend_from_slice(&num_params.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0, 0]);
+        bytes
+    }
+
+    pub(crate) fn err(code: u16, message: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF];
+        bytes.extend_from_slice(&code.to_le_bytes());
+        bytes.extend_from_slice(b"#HY000");
+        bytes.extend_from_slice(message.as_bytes());
+        bytes
+    }
+
+    /// A text protocol row with a single column
+    pub(crate) fn text_row(value: &str) -> Vec<u8> {
+        let mut bytes = BytesMut::new();
+        bytes.put_len_encoded_str(value);
+        bytes.to_vec()
+    }
+
+    /// A binary protocol row with a single `BIGINT` column
+    pub(crate) fn binary_row(value: i64) -> Vec<u8> {
+        let mut row = vec![0x00, 0x00];
+        row.extend_from_slice(&value.to_le_bytes());
+        row
+    }
+
+    /// Writes the packets of a response, numbered from 1
+    pub(crate) async fn respond<S: AsyncWrite + Unpin>(stream: &mut S, packets: &[Vec<u8>]) {
+        for (i, packet) in packets.iter().enumerate() {
+            write_packet(stream, i as u8 + 1, packet).await;
+        }
+    }
+
+    /// Logs in over an in-memory stream, returning the server end for the test to answer
+    /// the following commands on
+    pub(crate) async fn connect_in_memory() -> (Connection, DuplexStream) {
+        connect_in_memory_with(SERVER_CAPABILITIES).await
+    }
+
+    /// Like [`connect_in_memory`], with a server advertising `capabilities`
+    pub(crate) async fn connect_in_memory_with(
+        capabilities: Capability,
+    ) -> (Connection, DuplexStream) {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let login = async {
+            write_packet(&mut server, 0, &initial_handshake(capabilities)).await;
+            read_packet(&mut server).await;
+            write_packet(&mut server, 2, OK).await;
+        };
+        let options = ConnectionOption {
+            username: "app",
+            ..Default::default()
+        };
+        let (connection, ()) =
+            tokio::join!(Connection::connect_with_stream(&options, client), login);
+        (connection.unwrap(), server)
+    }
+
+    #[tokio::test]
+    async fn connections_should_run_over_in_memory_streams() {
+        let (client, mut server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            write_packet(&mut server, 0, &initial_handshake(SERVER_CAPABILITIES)).await;
+            let (sequence, response) = read_packet(&mut server).await;
+            assert_eq!(sequence, 1);
+            write_packet(&mut server, 2, OK).await;
+
+            assert_eq!(read_packet(&mut server).await, (0, vec![0x0E]));
+            write_packet(&mut server, 1, OK).await;
+            response
+        });
+
+        let options = ConnectionOption {
+            username: "app",
+            ..Default::default()
+        };
+        let mut connection = Connection::connect_with_stream(&options, client)
+            .await
+            .unwrap();
+        assert_eq!(connection.connection_id(), 7);
+        assert!(connection.tls_info().is_none());
+        connection.ping().await.unwrap();
+
+        let response = server.await.unwrap();
+        assert!(response.windows(4).any(|name| name == b"app\0"));
+    }
+
+    #[tokio::test]
+    async fn rejected_logins_should_fail_to_connect() {
+        let (client, mut server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            write_packet(&mut server, 0, &initial_handshake(SERVER_CAPABILITIES)).await;
+            read_packet(&mut server).await;
+            let mut err = vec![0xFF, 0x15, 0x04, b'#'];
+            err.extend_from_slice(b"28000Access denied for user 'app'");
+            write_packet(&mut server, 2, &err).await;
+        });
+
+        let options = ConnectionOption {
+            username: "app",
+            ..Default::default()
+        };
+        let err = Connection::connect_with_stream(&options, client)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConnectError::Server(err) if err.code == 1045 && err.sql_state == "28000"
+        ));
+    }
+
+    #[tokio::test]
+    async fn local_infile_requests_should_break_the_connection() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        tokio::spawn(async move {
+            read_packet(&mut server).await;
+            respond(&mut server, &[b"\xFB/etc/passwd".to_vec()]).await;
+            server
+        });
+
+        let err = connection
+            .query("LOAD DATA LOCAL INFILE '/etc/passwd' INTO TABLE t")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CommandError::UnexpectedPacket(0xFB)));
+        assert!(connection.is_broken());
+        assert!(matches!(
+            connection.ping().await,
+            Err(CommandError::Broken(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn progress_reports_should_be_published_until_the_next_command() {
+        let capabilities =
+            SERVER_CAPABILITIES.difference(Capability::CLIENT_MYSQL) | Capability::PROGRESS;
+        let (mut connection, mut server) = connect_in_memory_with(capabilities).await;
+        let status = ServerStatus::AUTOCOMMIT;
+        tokio::spawn(async move {
+            read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("id"),
+                    eof(status),
+                    b"\xff\xff\xff\x01\x01\x02\x50\xc3\x00\x0bcopy to tmp".to_vec(),
+                    text_row("1"),
+                    eof(status),
+                ],
+            )
+            .await;
+            read_packet(&mut server).await;
+            respond(&mut server, &[ok(status)]).await;
+            server
+        });
+
+        let progress = connection.progress();
+        let result = connection.query("SELECT id FROM t").await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        let report = progress.borrow().clone().unwrap();
+        assert_eq!((report.stage, report.max_stage), (1, 2));
+        assert_eq!(report.stage_name, "copy to tmp");
+
+        connection.ping().await.unwrap();
+        assert!(progress.borrow().is_none());
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn in_memory_streams_should_upgrade_to_tls() {
+        use rustls::{pki_types::PrivateKeyDer, ServerConfig};
+
+        use crate::ssl::{TlsBackend, TlsRoots};
+
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["db.internal".to_owned()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![cert.der().clone()],
+                    PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+                )
+                .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        let (client, mut server) = tokio::io::duplex(16 * 1024);
+        let server = tokio::spawn(async move {
+            let capabilities = SERVER_CAPABILITIES | Capability::SSL;
+            write_packet(&mut server, 0, &initial_handshake(capabilities)).await;
+            let (sequence, _) = read_packet(&mut server).await;
+            assert_eq!(sequence, 1);
+
+            let mut server = acceptor.accept(server).await.unwrap();
+            let (sequence, _) = read_packet(&mut server).await;
+            assert_eq!(sequence, 2);
+            write_packet(&mut server, 3, OK).await;
+
+            assert_eq!(read_packet(&mut server).await, (0, vec![0x0E]));
+            write_packet(&mut server, 1, OK).await;
+        });
+
+        let root = cert.pem();
+        let options = ConnectionOption {
+            host: "db.internal:3306",
+            username: "app",
+            tls: TlsOptions {
+                mode: TlsMode::VerifyFull,
+                backend: TlsBackend::Rustls,
+                roots: TlsRoots::Custom,
+                root: vec![root.as_bytes()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut connection = Connection::connect_with_stream(&options, client)
+            .await
+            .unwrap();
+        let info = connection.tls_info().unwrap();
+        assert_eq!(info.version.as_deref(), Some("TLSv1.3"));
+        connection.ping().await.unwrap();
+        server.await.unwrap();
+    }
 }
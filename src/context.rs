@@ -1,7 +1,9 @@
 use crate::{
     codec::MAX_PACKET_SIZE,
     protocol::{
-        plugin::AuthType, server::InitialHanshakePacket, Capability, ServerStatus, ServerVersion,
+        plugin::AuthType,
+        server::{InitialHanshakePacket, OkPacket},
+        Capability, ServerStatus, ServerVersion,
     },
 };
 
@@ -17,6 +19,7 @@ pub struct Context {
     server_version: ServerVersion,
     connection_id: u32,
     status_flags: ServerStatus,
+    database: Option<String>,
 }
 
 impl Context {
@@ -49,6 +52,7 @@ impl Context {
             server_version: packet.server_version,
             connection_id: packet.connection_id,
             status_flags: packet.status_flags,
+            database: None,
         }
     }
 
@@ -63,6 +67,14 @@ impl Context {
         self.connection_id = initial_handshake_packet.connection_id;
         self.status_flags = initial_handshake_packet.status_flags;
     }
+
+    /// Updates the tracked session state from an OK or EOF packet
+    pub fn for_ok_packet(&mut self, ok: &OkPacket) {
+        self.status_flags = ok.status_flags;
+        if let Some(schema) = &ok.schema {
+            self.database = Some(schema.clone());
+        }
+    }
 }
 
 impl Context {
@@ -81,6 +93,16 @@ impl Context {
         self.status_flags
     }
 
+    #[inline]
+    pub fn database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+
+    #[inline]
+    pub fn set_database(&mut self, database: Option<String>) {
+        self.database = database;
+    }
+
     #[inline]
     pub fn auth_type(&self) -> AuthType {
         self.auth_type.unwrap_or_default()
@@ -101,6 +123,11 @@ impl Context {
         self.client_capabilities.insert(capability);
     }
 
+    #[inline]
+    pub fn remove_client_capability(&mut self, capability: Capability) {
+        self.client_capabilities.remove(capability);
+    }
+
     #[inline]
     pub fn has_server_capability(&self, capability: Capability) -> bool {
         self.server_capabilities.contains(capability)
@@ -121,6 +148,11 @@ impl Context {
         self.client_collation
     }
 
+    #[inline]
+    pub fn set_client_collation(&mut self, collation: u8) {
+        self.client_collation = collation;
+    }
+
     #[inline]
     pub fn server_capabilities(&self) -> Capability {
         self.server_capabilities
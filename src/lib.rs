@@ -2,17 +2,34 @@ use bytes::{Buf, BufMut};
 use codec::PacketFrame;
 use context::Context;
 
+pub mod binlog;
+pub mod cancel;
 mod codec;
+pub mod config;
 pub mod connection;
 pub mod context;
+pub mod pipeline;
+pub mod procedure;
 pub mod protocol;
+pub mod result;
 pub mod ssl;
+pub mod statement;
 pub mod stream;
+pub mod timeout;
+pub mod transaction;
+pub mod url;
+pub mod value;
+pub mod xa;
 
 pub mod my;
+pub mod mylogin;
+pub mod option_file;
+pub mod pool;
 
 pub trait BytesExt {
     fn get_bytes_null(&mut self) -> Result<bytes::Bytes, std::io::Error>;
+    fn get_len_encoded_int(&mut self) -> Result<u64, std::io::Error>;
+    fn get_len_encoded_bytes(&mut self) -> Result<bytes::Bytes, std::io::Error>;
 }
 
 impl BytesExt for bytes::Bytes {
@@ -25,6 +42,34 @@ impl BytesExt for bytes::Bytes {
         self.advance(1);
         Ok(bytes)
     }
+
+    /// [lenec](https://dev.mysql.com/doc/dev/mysql-server/9.0.1/page_protocol_basic_dt_integers.html#sect_protocol_basic_dt_int_le)
+    fn get_len_encoded_int(&mut self) -> Result<u64, std::io::Error> {
+        let eof = || std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        let first = *self.first().ok_or_else(eof)?;
+        let len = match first {
+            0xfc => 2,
+            0xfd => 3,
+            0xfe => 8,
+            _ => {
+                self.advance(1);
+                return Ok(first as u64);
+            }
+        };
+        if self.len() < len + 1 {
+            return Err(eof());
+        }
+        self.advance(1);
+        Ok(self.get_uint_le(len))
+    }
+
+    fn get_len_encoded_bytes(&mut self) -> Result<bytes::Bytes, std::io::Error> {
+        let len = self.get_len_encoded_int()? as usize;
+        if self.len() < len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        Ok(self.split_to(len))
+    }
 }
 
 pub trait BufMutExt {
@@ -1,28 +1,159 @@
 use dibi::{
-    connection::{Connection, ConnectionOption},
-    ssl::{TlsMode, TlsOptions},
-    stream::StreamType,
+    binlog::{BinlogReader, Event, EventData, RowImage, RowValue},
+    connection::Connection,
+    option_file::OptionFiles,
 };
 
 #[tokio::main]
 async fn main() {
-    dotenvy::dotenv().ok();
     tracing_subscriber::fmt::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
-    let password = std::env::var("MYSQL_PASSWORD").unwrap();
-    let tls_opts = TlsOptions {
-        mode: TlsMode::Disable,
-        ..Default::default()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("binlog") {
+        if let Err(err) = binlog(&args[1..]) {
+            eprintln!("dibi binlog: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config = OptionFiles::new()
+        .group("dibi")
+        .config()
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut connection = Connection::connect_with_config(&config).await.unwrap();
+    let statistics = connection.statistics().await.unwrap();
+    println!("{:?}", statistics);
+    connection.close().await.unwrap();
+}
+
+const BINLOG_USAGE: &str = "usage: dibi binlog <file> [--start-position=<pos>] \
+    [--start-datetime=<YYYY-MM-DD HH:MM:SS>] [--stop-datetime=<YYYY-MM-DD HH:MM:SS>] \
+    [--no-verify-checksum]";
+
+/// Prints the events of a binlog or relay log file, like `mysqlbinlog`
+fn binlog(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = None;
+    let mut start_position = None;
+    let mut start_time = None;
+    let mut stop_time = None;
+    let mut verify_checksum = true;
+    for arg in args {
+        match arg.split_once('=') {
+            Some(("--start-position", value)) => start_position = Some(value.parse()?),
+            Some(("--start-datetime", value)) => start_time = Some(parse_datetime(value)?),
+            Some(("--stop-datetime", value)) => stop_time = Some(parse_datetime(value)?),
+            None if arg == "--no-verify-checksum" => verify_checksum = false,
+            None if !arg.starts_with("--") && path.is_none() => path = Some(arg),
+            _ => return Err(BINLOG_USAGE.into()),
+        }
+    }
+    let path = path.ok_or(BINLOG_USAGE)?;
+
+    let mut reader = BinlogReader::open(path)?.verify_checksum(verify_checksum);
+    if let Some(start) = start_time {
+        reader = reader.start_time(start);
+    }
+    if let Some(stop) = stop_time {
+        reader = reader.stop_time(stop);
+    }
+    if let Some(position) = start_position {
+        reader.seek(position)?;
+    }
+
+    loop {
+        let position = reader.position();
+        match reader.next_event()? {
+            Some(event) => print_event(position, &event),
+            None => return Ok(()),
+        }
+    }
+}
+
+fn print_event(position: u64, event: &Event) {
+    let header = &event.header;
+    print!(
+        "# at {} {} server id {} end_log_pos {} {:?}",
+        position, header.timestamp, header.server_id, header.log_pos, header.event_type
+    );
+    match &event.data {
+        EventData::Rotate(rotate) => println!(" {}:{}", rotate.next_file, rotate.position),
+        EventData::FormatDescription(format) => println!(
+            " server {} checksum {:?}",
+            format.server_version, format.checksum
+        ),
+        EventData::Query(query) => println!(" `{}` {}", query.schema, query.query),
+        EventData::Xid(xid) => println!(" xid {}", xid.xid),
+        EventData::Gtid(gtid) => println!(" {}:{}", gtid.uuid, gtid.gno),
+        EventData::PreviousGtids(gtids) => println!(" {}", gtids),
+        EventData::MariaDbGtid(gtid) => println!(" {}", gtid.gtid),
+        EventData::MariaDbGtidList(gtids) => println!(" {}", gtids),
+        EventData::TableMap(table) => {
+            println!(
+                " `{}`.`{}` id {}",
+                table.schema, table.table, table.table_id
+            )
+        }
+        EventData::Rows(rows) => {
+            println!(
+                " {:?} `{}`.`{}`",
+                rows.kind, rows.table.schema, rows.table.table
+            );
+            for row in &rows.rows {
+                if let Some(before) = &row.before {
+                    println!("  before {}", format_image(before));
+                }
+                if let Some(after) = &row.after {
+                    println!("  after  {}", format_image(after));
+                }
+            }
+        }
+        EventData::Heartbeat(_) | EventData::Other(_) => println!(),
+    }
+}
+
+fn format_image(image: &RowImage) -> String {
+    let values: Vec<String> = image
+        .values()
+        .iter()
+        .map(|value| match value {
+            RowValue::Missing => "-".to_owned(),
+            RowValue::Value(value) => match value.as_str() {
+                Some(text) => format!("{:?}", text),
+                None => format!("{:?}", value),
+            },
+            RowValue::JsonDiff(diffs) => format!("{:?}", diffs),
+        })
+        .collect();
+    format!("({})", values.join(", "))
+}
+
+/// Parses `YYYY-MM-DD HH:MM:SS` in UTC to a unix timestamp
+fn parse_datetime(value: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    let invalid = || format!("invalid datetime {}", value);
+    let (date, time) = value.trim().split_once(' ').unwrap_or((value, "00:00:00"));
+    let parts = |s: &str, sep| -> Result<Vec<i64>, String> {
+        s.split(sep)
+            .map(|p| p.parse().map_err(|_| invalid()))
+            .collect()
     };
-    let options = ConnectionOption {
-        host: "127.0.0.1:3306",
-        stream_type: StreamType::Tcp,
-        tls: tls_opts,
-        username: "ovior",
-        password: password.as_bytes(),
-        database: None,
+    let (date, time) = (parts(date, '-')?, parts(time, ':')?);
+    let (&[year, month, day], &[hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+        return Err(invalid().into());
     };
-    let connection = Connection::connect(&options).await.unwrap();
+
+    // Days since the epoch from a civil date, by Howard Hinnant
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let timestamp = days * 86400 + hour * 3600 + minute * 60 + second;
+    Ok(u32::try_from(timestamp).map_err(|_| invalid())?)
 }
@@ -1,5 +1,7 @@
+use std::collections::VecDeque;
+
 use futures::SinkExt;
-use tokio_native_tls::TlsStream;
+use tokio::time::Instant;
 use tokio_stream::StreamExt;
 use tokio_util::{codec::Framed, either::Either};
 
@@ -7,8 +9,9 @@ use crate::{
     codec::{PacketCodec, PacketFrame},
     context::Context,
     protocol::server::InitialHanshakePacket,
-    ssl::UpgradeStream,
+    ssl::{TlsConnector, TlsInfo, TlsStream, UpgradeStream},
     stream::Stream,
+    timeout::{earliest, io_deadline, BrokenConnection, Timeouts},
     EncodePacket,
 };
 
@@ -19,6 +22,11 @@ pub type FramedStream = Framed<StreamTransporter, PacketCodec>;
 pub struct MyStream {
     stream: FramedStream,
     context: Context,
+    timeouts: Timeouts,
+    deadline: Option<Instant>,
+    broken: bool,
+    /// The sequence each pipelined command expects its response to start with
+    outstanding: VecDeque<u8>,
 }
 
 impl MyStream {
@@ -26,6 +34,52 @@ impl MyStream {
         Self {
             stream,
             context: Context::default(),
+            timeouts: Timeouts::default(),
+            deadline: None,
+            broken: false,
+            outstanding: VecDeque::new(),
+        }
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Drops the deadline of the current statement, for commands that stream without end
+    pub fn clear_deadline(&mut self) {
+        self.deadline = None;
+    }
+
+    /// Whether a timeout or an I/O failure left the stream in an unknown state
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// Marks the stream broken after a response that cannot be read to its end
+    pub fn mark_broken(&mut self) {
+        self.broken = true;
+    }
+
+    fn after(duration: Option<std::time::Duration>) -> Option<Instant> {
+        duration.map(|d| Instant::now() + d)
+    }
+
+    fn poison<T>(&mut self, result: Result<T, std::io::Error>) -> Result<T, std::io::Error> {
+        if result.is_err() {
+            self.broken = true;
+        }
+        result
+    }
+
+    /// The parameters of the TLS session, if the stream was upgraded
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        match self.stream.get_ref() {
+            Either::Left(_) => None,
+            Either::Right(stream) => Some(stream.info()),
         }
     }
 
@@ -46,21 +100,85 @@ impl MyStream {
         P: EncodePacket<PacketFrame>,
         P::Error: Into<std::io::Error>,
     {
+        if self.broken {
+            return Err(BrokenConnection::io_error());
+        }
         if packet.is_command_packet() {
+            if !self.outstanding.is_empty() {
+                // The responses of an abandoned pipeline are still on the wire
+                self.broken = true;
+                return Err(BrokenConnection::io_error());
+            }
             let codec_mut = self.stream.codec_mut();
             codec_mut.reset_sequence();
+            self.deadline = Self::after(self.timeouts.statement);
         }
         let frame = packet.encode_packet(&self.context).map_err(Into::into)?;
-        self.stream.send(frame).await?;
+        let deadline = earliest(self.deadline, Self::after(self.timeouts.write));
+        let result = io_deadline(deadline, self.stream.send(frame)).await;
+        self.poison(result)
+    }
 
+    /// Writes a command behind the previous ones without flushing or waiting for its
+    /// response. The responses are read back in order after [`MyStream::next_response`].
+    pub async fn feed_packet<P>(&mut self, packet: P) -> Result<(), std::io::Error>
+    where
+        P: EncodePacket<PacketFrame>,
+        P::Error: Into<std::io::Error>,
+    {
+        if self.broken {
+            return Err(BrokenConnection::io_error());
+        }
+        self.stream.codec_mut().reset_sequence();
+        self.deadline = Self::after(self.timeouts.statement);
+        let frame = packet.encode_packet(&self.context).map_err(Into::into)?;
+        let deadline = earliest(self.deadline, Self::after(self.timeouts.write));
+        let result = io_deadline(deadline, self.stream.feed(frame)).await;
+        self.poison(result)?;
+        self.outstanding.push_back(self.stream.codec().sequence());
         Ok(())
     }
 
+    /// Flushes the commands written with [`MyStream::feed_packet`]
+    pub async fn flush(&mut self) -> Result<(), std::io::Error> {
+        if self.broken {
+            return Err(BrokenConnection::io_error());
+        }
+        let deadline = earliest(self.deadline, Self::after(self.timeouts.write));
+        let result = io_deadline(deadline, self.stream.flush()).await;
+        self.poison(result)
+    }
+
+    /// Moves on to the response of the oldest pipelined command, returning false when no
+    /// command is outstanding
+    pub fn next_response(&mut self) -> bool {
+        match self.outstanding.pop_front() {
+            Some(sequence) => {
+                self.stream.codec_mut().set_sequence(sequence);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The number of pipelined commands whose response was not read yet
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.len()
+    }
+
     pub async fn recv(&mut self) -> Result<PacketFrame, std::io::Error> {
-        self.stream
-            .next()
-            .await
-            .ok_or(std::io::Error::from(std::io::ErrorKind::ConnectionAborted))?
+        if self.broken {
+            return Err(BrokenConnection::io_error());
+        }
+        let deadline = earliest(self.deadline, Self::after(self.timeouts.read));
+        let next = async {
+            self.stream
+                .next()
+                .await
+                .ok_or(std::io::Error::from(std::io::ErrorKind::ConnectionAborted))?
+        };
+        let result = io_deadline(deadline, next).await;
+        self.poison(result)
     }
 
     pub async fn recv_packet(&mut self) -> Result<PacketFrame, std::io::Error> {
@@ -68,12 +186,17 @@ impl MyStream {
         // parse ok err switch packet
         Ok(packet)
     }
+
+    /// Flushes any pending frames and shuts down the write half of the underlying stream
+    pub async fn shutdown(&mut self) -> Result<(), std::io::Error> {
+        self.stream.close().await
+    }
 }
 
 impl UpgradeStream for MyStream {
     async fn maybe_upgrade_tls(
         self,
-        parts: Option<(&str, tokio_native_tls::TlsConnector)>,
+        parts: Option<(&str, TlsConnector)>,
     ) -> Result<Self, crate::ssl::UpgradeError> {
         let stream = if let Some((domain, connector)) = parts {
             let parts = self.stream.into_parts();
@@ -81,7 +204,10 @@ impl UpgradeStream for MyStream {
             let stream = match stream {
                 Either::Left(stream) => stream,
                 Either::Right(stream) => {
-                    return Ok(MyStream::new(Framed::new(Either::Right(stream), codec)));
+                    return Ok(Self {
+                        stream: Framed::new(Either::Right(stream), codec),
+                        ..self
+                    });
                 }
             };
 
@@ -93,9 +219,59 @@ impl UpgradeStream for MyStream {
             self.stream
         };
 
-        Ok(Self {
-            stream,
-            context: self.context,
-        })
+        Ok(Self { stream, ..self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::client::com::ComPing;
+
+    #[tokio::test]
+    async fn read_timeout_should_poison_the_stream() {
+        let (client, _server) = tokio::io::duplex(4096);
+        let transporter = StreamTransporter::Left(Stream::custom(client));
+        let mut stream = MyStream::new(Framed::new(transporter, PacketCodec::new()));
+        stream.set_timeouts(Timeouts {
+            read: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+
+        let err = stream.recv().await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(stream.is_broken());
+
+        let err = stream.send_packet(ComPing::new()).await.unwrap_err();
+        assert!(BrokenConnection::is_io_error(&err));
+    }
+
+    #[tokio::test]
+    async fn pipelined_responses_should_be_read_in_order() {
+        let (client, server) = tokio::io::duplex(4096);
+        let transporter = StreamTransporter::Left(Stream::custom(client));
+        let mut stream = MyStream::new(Framed::new(transporter, PacketCodec::new()));
+        let mut server = Framed::new(server, PacketCodec::new());
+
+        stream.feed_packet(ComPing::new()).await.unwrap();
+        stream.feed_packet(ComPing::new()).await.unwrap();
+        stream.flush().await.unwrap();
+        assert_eq!(stream.outstanding(), 2);
+
+        for header in [0x00, 0xFE] {
+            server.codec_mut().reset_sequence();
+            let ping = server.next().await.unwrap().unwrap();
+            assert_eq!(ping.as_bytes(), [0x0E]);
+            let response = PacketFrame::new(vec![header, 0, 0, 2, 0, 0, 0].into());
+            server.send(response).await.unwrap();
+        }
+
+        for header in [0x00, 0xFE] {
+            assert!(stream.next_response());
+            assert_eq!(stream.recv().await.unwrap().header(), Some(header));
+        }
+        assert!(!stream.next_response());
     }
 }
@@ -0,0 +1,168 @@
+//! Reads the obfuscated `.mylogin.cnf` login path file written by `mysql_config_editor`.
+//!
+//! The file starts with 4 unused bytes and a 20 byte key, folded into an AES-128 key. It is
+//! followed by lines of an option file, each stored as a little endian `u32` length and the
+//! line encrypted with AES-128-ECB and PKCS#7 padding.
+
+use std::path::{Path, PathBuf};
+
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit},
+    Aes128,
+};
+
+const UNUSED_LEN: usize = 4;
+const KEY_LEN: usize = 20;
+const BLOCK_LEN: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoginFileError {
+    #[error("failed to read {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("the login file is too short to hold its key")]
+    MissingKey,
+
+    #[error("the login file has a truncated line at offset {0}")]
+    Truncated(usize),
+
+    #[error("the login file has a line that is not a multiple of the block size at offset {0}")]
+    BlockSize(usize),
+
+    #[error("the login file has invalid padding at offset {0}")]
+    Padding(usize),
+
+    #[error("the login file does not decrypt to utf-8")]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+/// The location of the login file, `MYSQL_TEST_LOGIN_FILE` or `~/.mylogin.cnf`
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("MYSQL_TEST_LOGIN_FILE") {
+        return Some(PathBuf::from(path));
+    }
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".mylogin.cnf"))
+}
+
+/// Reads and decrypts a login file into the contents of an option file
+pub fn read(path: &Path) -> Result<String, LoginFileError> {
+    let bytes = std::fs::read(path).map_err(|source| LoginFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    decrypt(&bytes)
+}
+
+/// Decrypts the contents of a login file into the contents of an option file
+pub fn decrypt(bytes: &[u8]) -> Result<String, LoginFileError> {
+    if bytes.len() < UNUSED_LEN + KEY_LEN {
+        return Err(LoginFileError::MissingKey);
+    }
+    let cipher = Aes128::new(&fold_key(&bytes[UNUSED_LEN..UNUSED_LEN + KEY_LEN]));
+
+    let mut offset = UNUSED_LEN + KEY_LEN;
+    let mut plain = Vec::with_capacity(bytes.len());
+    while offset < bytes.len() {
+        let len = bytes
+            .get(offset..offset + 4)
+            .ok_or(LoginFileError::Truncated(offset))?;
+        let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let start = offset + 4;
+        let line = bytes
+            .get(start..start + len)
+            .ok_or(LoginFileError::Truncated(offset))?;
+        if len == 0 || !len.is_multiple_of(BLOCK_LEN) {
+            return Err(LoginFileError::BlockSize(offset));
+        }
+
+        let mut line = line.to_vec();
+        for block in line.chunks_exact_mut(BLOCK_LEN) {
+            cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        }
+
+        let padding = *line.last().unwrap_or(&0) as usize;
+        if padding == 0
+            || padding > BLOCK_LEN
+            || !line[len - padding..].iter().all(|&b| b as usize == padding)
+        {
+            return Err(LoginFileError::Padding(offset));
+        }
+        line.truncate(len - padding);
+        plain.extend_from_slice(&line);
+
+        offset = start + len;
+    }
+
+    Ok(String::from_utf8(plain)?)
+}
+
+/// Folds the 20 byte key of the file into an AES-128 key by xoring it over itself
+fn fold_key(key: &[u8]) -> GenericArray<u8, aes::cipher::consts::U16> {
+    let mut folded = GenericArray::default();
+    for (i, b) in key.iter().enumerate() {
+        folded[i % BLOCK_LEN] ^= b;
+    }
+    folded
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use aes::cipher::BlockEncrypt;
+
+    use super::*;
+
+    pub(crate) fn encrypt(key: &[u8; KEY_LEN], lines: &[&str]) -> Vec<u8> {
+        let cipher = Aes128::new(&fold_key(key));
+        let mut out = vec![0; UNUSED_LEN];
+        out.extend_from_slice(key);
+        for line in lines {
+            let mut line = line.as_bytes().to_vec();
+            let padding = BLOCK_LEN - line.len() % BLOCK_LEN;
+            line.resize(line.len() + padding, padding as u8);
+            for block in line.chunks_exact_mut(BLOCK_LEN) {
+                cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            }
+            out.extend_from_slice(&(line.len() as u32).to_le_bytes());
+            out.extend_from_slice(&line);
+        }
+        out
+    }
+
+    #[test]
+    fn login_file_should_be_decrypted() {
+        let key = *b"0123456789abcdefghij";
+        let file = encrypt(
+            &key,
+            &[
+                "[client]\n",
+                "user = \"root\"\n",
+                "[backup]\n",
+                "host = \"db.internal\"\n",
+                "password = \"s3cret-with-a-long-enough-value\"\n",
+            ],
+        );
+        let contents = decrypt(&file).unwrap();
+        assert_eq!(
+            contents,
+            "[client]\nuser = \"root\"\n[backup]\nhost = \"db.internal\"\npassword = \"s3cret-with-a-long-enough-value\"\n"
+        );
+    }
+
+    #[test]
+    fn corrupted_login_file_should_fail() {
+        assert!(matches!(decrypt(&[0; 10]), Err(LoginFileError::MissingKey)));
+
+        let mut file = encrypt(b"0123456789abcdefghij", &["[client]\n"]);
+        file.truncate(file.len() - 1);
+        assert!(matches!(decrypt(&file), Err(LoginFileError::Truncated(_))));
+
+        let mut file = encrypt(b"0123456789abcdefghij", &["[client]\n"]);
+        let last = file.len() - 1;
+        file[last] ^= 0xFF;
+        assert!(matches!(decrypt(&file), Err(LoginFileError::Padding(_))));
+    }
+}
@@ -0,0 +1,610 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    config::{ConfigBuilder, ConfigError},
+    mylogin::{self, LoginFileError},
+    protocol::collation,
+    ssl::{ParseTlsModeError, TlsMode},
+};
+
+/// How deep `!include` directives may nest before the files are considered recursive
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OptionFileError {
+    #[error("failed to read {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{path}:{line}: {message}")]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+
+    #[error("too many nested includes in {0}")]
+    IncludeDepth(PathBuf),
+
+    #[error("invalid value {value} for the option {name}")]
+    InvalidValue { name: String, value: String },
+
+    #[error(transparent)]
+    TlsMode(#[from] ParseTlsModeError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    LoginFile(#[from] LoginFileError),
+
+    #[error("the login path {0} is not in the login file")]
+    UnknownLoginPath(String),
+}
+
+/// Reads the [option files](https://dev.mysql.com/doc/refman/8.4/en/option-files.html) and
+/// environment variables every MySQL client reads, and turns them into a [`ConfigBuilder`].
+///
+/// By default the `[client]` and `[mysql]` groups of `/etc/my.cnf`, `/etc/mysql/my.cnf` and
+/// `~/.my.cnf` are read, on top of `MYSQL_HOST`, `MYSQL_TCP_PORT`, `MYSQL_UNIX_PORT` and
+/// `MYSQL_PWD`. The login file `~/.mylogin.cnf` is read last, so credentials stored with
+/// `mysql_config_editor` win. Files read later override earlier ones, and files override the
+/// environment.
+#[derive(Debug, Clone)]
+pub struct OptionFiles {
+    files: Vec<PathBuf>,
+    groups: Vec<String>,
+    environment: bool,
+    login_file: Option<PathBuf>,
+    login_path: Option<String>,
+}
+
+impl Default for OptionFiles {
+    fn default() -> Self {
+        let mut files = vec![
+            PathBuf::from("/etc/my.cnf"),
+            PathBuf::from("/etc/mysql/my.cnf"),
+        ];
+        if let Some(home) = std::env::var_os("HOME") {
+            files.push(Path::new(&home).join(".my.cnf"));
+        }
+        Self {
+            files,
+            groups: vec!["client".into(), "mysql".into()],
+            environment: true,
+            login_file: mylogin::default_path(),
+            login_path: None,
+        }
+    }
+}
+
+impl OptionFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No files, groups or environment variables, to be filled explicitly
+    pub fn empty() -> Self {
+        Self {
+            files: Vec::new(),
+            groups: Vec::new(),
+            environment: false,
+            login_file: None,
+            login_path: None,
+        }
+    }
+
+    /// Reads another file after the ones already added. Missing files are skipped.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Also reads the options of another group, e.g. `[myapp]`
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.groups.push(group.into());
+        self
+    }
+
+    /// Whether the `MYSQL_*` environment variables are read
+    pub fn environment(mut self, enabled: bool) -> Self {
+        self.environment = enabled;
+        self
+    }
+
+    /// The encrypted login file to read, see [`mylogin`]. It is skipped when missing.
+    pub fn login_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.login_file = Some(path.into());
+        self
+    }
+
+    /// Selects a login path of the login file, e.g. `backup` for the entry created with
+    /// `mysql_config_editor set --login-path=backup`
+    pub fn login_path(mut self, name: impl Into<String>) -> Self {
+        self.login_path = Some(name.into());
+        self
+    }
+
+    /// Reads the environment and every file into a single set of options
+    pub fn load(&self) -> Result<OptionValues, OptionFileError> {
+        let mut values = OptionValues::default();
+        if self.environment {
+            values.read_environment(|name| std::env::var(name).ok());
+        }
+        for file in &self.files {
+            values.read_file(file, &self.groups, 0)?;
+        }
+        if let Some(path) = &self.login_file {
+            self.read_login_file(&mut values, path)?;
+        }
+        Ok(values)
+    }
+
+    fn read_login_file(
+        &self,
+        values: &mut OptionValues,
+        path: &Path,
+    ) -> Result<(), OptionFileError> {
+        let contents = match mylogin::read(path) {
+            Ok(contents) => contents,
+            Err(LoginFileError::Io { source, .. })
+                if source.kind() == std::io::ErrorKind::NotFound && self.login_path.is_none() =>
+            {
+                return Ok(())
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut groups = self.groups.clone();
+        if let Some(login_path) = &self.login_path {
+            let header = format!("[{}]", login_path);
+            if !contents.lines().any(|line| line.trim() == header) {
+                return Err(OptionFileError::UnknownLoginPath(login_path.clone()));
+            }
+            groups.push(login_path.clone());
+        }
+        values.read_str(&contents, path, &groups, MAX_INCLUDE_DEPTH)
+    }
+
+    /// Loads the options and applies them to a default [`ConfigBuilder`]
+    pub fn config(&self) -> Result<ConfigBuilder, OptionFileError> {
+        self.load()?.apply(ConfigBuilder::default())
+    }
+}
+
+/// The options read from option files, keyed by their name with `_` normalized to `-`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptionValues {
+    values: HashMap<String, Option<String>>,
+}
+
+impl OptionValues {
+    /// The value of an option, `Some(None)` when it is given without a value
+    pub fn get(&self, name: &str) -> Option<Option<&str>> {
+        self.values
+            .get(&normalize_name(name))
+            .map(|value| value.as_deref())
+    }
+
+    pub fn set(&mut self, name: &str, value: Option<String>) {
+        self.values.insert(normalize_name(name), value);
+    }
+
+    fn read_environment(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let variables = [
+            ("MYSQL_HOST", "host"),
+            ("MYSQL_TCP_PORT", "port"),
+            ("MYSQL_UNIX_PORT", "socket"),
+            ("MYSQL_PWD", "password"),
+        ];
+        for (variable, name) in variables {
+            if let Some(value) = var(variable) {
+                self.set(name, Some(value));
+            }
+        }
+    }
+
+    fn read_file(
+        &mut self,
+        path: &Path,
+        groups: &[String],
+        depth: usize,
+    ) -> Result<(), OptionFileError> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(OptionFileError::IncludeDepth(path.to_path_buf()));
+        }
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && depth == 0 => return Ok(()),
+            Err(source) => {
+                return Err(OptionFileError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        self.read_str(&contents, path, groups, depth)
+    }
+
+    /// Parses the contents of an option file, keeping only the options of the given groups
+    fn read_str(
+        &mut self,
+        contents: &str,
+        path: &Path,
+        groups: &[String],
+        depth: usize,
+    ) -> Result<(), OptionFileError> {
+        let parse_error = |line: usize, message: &str| OptionFileError::Parse {
+            path: path.to_path_buf(),
+            line,
+            message: message.into(),
+        };
+        let base = path.parent().unwrap_or(Path::new("."));
+        let mut in_group = false;
+
+        for (index, line) in contents.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(include) = line.strip_prefix("!includedir") {
+                let dir = base.join(include.trim());
+                self.read_dir(&dir, groups, depth + 1)?;
+                continue;
+            }
+            if let Some(include) = line.strip_prefix("!include") {
+                let file = base.join(include.trim());
+                self.read_file(&file, groups, depth + 1)?;
+                continue;
+            }
+
+            if let Some(group) = line.strip_prefix('[') {
+                let group = group
+                    .strip_suffix(']')
+                    .ok_or_else(|| parse_error(number, "unterminated group header"))?;
+                in_group = groups.iter().any(|g| g == group.trim());
+                continue;
+            }
+
+            if !in_group {
+                continue;
+            }
+
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(parse_value(value.trim()))),
+                None => (strip_comment(line).trim(), None),
+            };
+            if name.is_empty() {
+                return Err(parse_error(number, "missing option name"));
+            }
+            self.set(name, value);
+        }
+
+        Ok(())
+    }
+
+    fn read_dir(
+        &mut self,
+        dir: &Path,
+        groups: &[String],
+        depth: usize,
+    ) -> Result<(), OptionFileError> {
+        let io_error = |source| OptionFileError::Io {
+            path: dir.to_path_buf(),
+            source,
+        };
+        let mut files = std::fs::read_dir(dir)
+            .map_err(io_error)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io_error)?;
+        files.retain(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "cnf" || (cfg!(windows) && ext == "ini"))
+        });
+        files.sort();
+        for file in files {
+            self.read_file(&file, groups, depth)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the options to a builder. `host` and `port` are combined, and `socket` is used
+    /// when no host is given or the host is `localhost`, like the mysql client does.
+    pub fn apply(&self, mut builder: ConfigBuilder) -> Result<ConfigBuilder, OptionFileError> {
+        let value = |name: &str| self.get(name).flatten();
+        let invalid = |name: &str, value: &str| OptionFileError::InvalidValue {
+            name: name.into(),
+            value: value.into(),
+        };
+
+        let host = value("host");
+        let port = match value("port") {
+            Some(port) => port.parse::<u16>().map_err(|_| invalid("port", port))?,
+            None => 3306,
+        };
+        let prefers_tcp = value("protocol").is_some_and(|p| p.eq_ignore_ascii_case("tcp"));
+
+        match (host, value("socket")) {
+            (None | Some("localhost"), Some(socket)) if !prefers_tcp => {
+                builder = builder.try_socket(socket)?;
+            }
+            // IPv6 literals are bracketed to tell the port apart
+            (Some(host), _) if host.contains(':') && !host.starts_with('[') => {
+                builder = builder.host(format!("[{}]:{}", host, port));
+            }
+            (Some(host), _) => builder = builder.host(format!("{}:{}", host, port)),
+            (None, _) => builder = builder.host(format!("localhost:{}", port)),
+        }
+
+        if let Some(user) = value("user") {
+            builder = builder.username(user);
+        }
+        if let Some(password) = value("password") {
+            builder = builder.password(password);
+        }
+        if let Some(database) = value("database") {
+            builder = builder.database(database);
+        }
+        if let Some(charset) = value("default-character-set") {
+            let id = collation::default_for_charset(charset)
+                .ok_or_else(|| invalid("default-character-set", charset))?;
+            builder = builder.collation(id);
+        }
+        if let Some(mode) = value("ssl-mode") {
+            builder = builder.tls_mode(mode.parse::<TlsMode>()?);
+        }
+        if let Some(ca) = value("ssl-ca") {
+            builder = builder.tls_root(PathBuf::from(ca));
+        }
+        if let Some(cert) = value("ssl-cert") {
+            builder = builder.tls_cert(PathBuf::from(cert));
+        }
+        if let Some(key) = value("ssl-key") {
+            builder = builder.tls_key(PathBuf::from(key));
+        }
+        if let Some(timeout) = value("connect-timeout") {
+            let seconds = timeout
+                .parse()
+                .map_err(|_| invalid("connect-timeout", timeout))?;
+            builder = builder.connect_timeout(Duration::from_secs(seconds));
+        }
+
+        Ok(builder)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+fn strip_comment(value: &str) -> &str {
+    match value.find(" #").or_else(|| value.find("\t#")) {
+        Some(index) => &value[..index],
+        None => value,
+    }
+}
+
+/// Unquotes a value and resolves its escape sequences, dropping a trailing comment
+fn parse_value(value: &str) -> String {
+    let quote = value.chars().next().filter(|c| *c == '\'' || *c == '"');
+    let value = match quote.and_then(|quote| value[1..].find(quote)) {
+        Some(end) => &value[1..end + 1],
+        None => strip_comment(value).trim_end(),
+    };
+
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('b') => out.push('\u{8}'),
+            Some('s') => out.push(' '),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Source;
+    #[cfg(unix)]
+    use crate::stream::StreamType;
+
+    fn groups() -> Vec<String> {
+        vec!["client".into(), "myapp".into()]
+    }
+
+    #[test]
+    fn option_file_should_be_parsed() {
+        let contents = r#"
+# global settings
+[mysqld]
+port = 3307
+
+[client]
+host = db.internal
+port=3308
+user = "app user"
+password = 's3cret # not a comment'
+ssl_mode = VERIFY_CA  # trailing comment
+ssl-ca = /etc/mysql/ca.pem
+skip-column-names
+
+[myapp]
+database = shop
+"#;
+        let mut values = OptionValues::default();
+        values
+            .read_str(contents, Path::new("/etc/my.cnf"), &groups(), 0)
+            .unwrap();
+
+        assert_eq!(values.get("port"), Some(Some("3308")));
+        assert_eq!(values.get("user"), Some(Some("app user")));
+        assert_eq!(values.get("password"), Some(Some("s3cret # not a comment")));
+        assert_eq!(values.get("ssl-mode"), Some(Some("VERIFY_CA")));
+        assert_eq!(values.get("skip_column_names"), Some(None));
+
+        let config = values
+            .apply(ConfigBuilder::default())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.host(), "db.internal:3308");
+        assert_eq!(config.username(), "app user");
+        assert_eq!(config.database(), Some("shop"));
+        assert_eq!(config.tls().mode(), TlsMode::VerifyCa);
+        assert_eq!(
+            config.tls().root(),
+            [Source::File(PathBuf::from("/etc/mysql/ca.pem"))]
+        );
+
+        values.set("host", Some("::1".into()));
+        let builder = values.apply(ConfigBuilder::default()).unwrap();
+        assert_eq!(builder.build().unwrap().host(), "[::1]:3308");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn environment_should_be_overridden_by_files() {
+        let mut values = OptionValues::default();
+        values.read_environment(|name| match name {
+            "MYSQL_UNIX_PORT" => Some("/run/mysqld/mysqld.sock".into()),
+            "MYSQL_PWD" => Some("from-env".into()),
+            _ => None,
+        });
+        values
+            .read_str(
+                "[client]\npassword=from-file\n",
+                Path::new("my.cnf"),
+                &groups(),
+                0,
+            )
+            .unwrap();
+
+        let config = values
+            .apply(ConfigBuilder::default())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.stream_type(), StreamType::Unix);
+        assert_eq!(config.host(), "/run/mysqld/mysqld.sock");
+        assert_eq!(config.password(), b"from-file");
+    }
+
+    #[test]
+    fn includes_should_be_followed() {
+        let dir = std::env::temp_dir().join(format!("dibi-option-file-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(
+            dir.join("my.cnf"),
+            "[client]\nuser=root\n!include extra.cnf\n!includedir conf.d\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("extra.cnf"), "[client]\nhost=primary\n").unwrap();
+        std::fs::write(dir.join("conf.d/a.cnf"), "[client]\nport=3310\n").unwrap();
+        std::fs::write(dir.join("conf.d/ignored.txt"), "[client]\nport=1\n").unwrap();
+
+        let values = OptionFiles::empty()
+            .file(dir.join("my.cnf"))
+            .group("client")
+            .load()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(values.get("user"), Some(Some("root")));
+        assert_eq!(values.get("host"), Some(Some("primary")));
+        assert_eq!(values.get("port"), Some(Some("3310")));
+    }
+
+    #[test]
+    fn login_path_should_override_option_files() {
+        let path = std::env::temp_dir().join(format!("dibi-mylogin-{}.cnf", std::process::id()));
+        std::fs::write(
+            &path,
+            mylogin::tests::encrypt(
+                b"0123456789abcdefghij",
+                &[
+                    "[client]\n",
+                    "user = \"reader\"\n",
+                    "[backup]\n",
+                    "user = \"backup\"\n",
+                    "password = \"hunter2\"\n",
+                    "socket = \"/run/mysqld.sock\"\n",
+                ],
+            ),
+        )
+        .unwrap();
+
+        let files = OptionFiles::empty().group("client").login_file(&path);
+        let client = files.load().unwrap();
+        let backup = files.clone().login_path("backup").load().unwrap();
+        let unknown = files.login_path("nope").load();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(client.get("user"), Some(Some("reader")));
+        assert_eq!(client.get("password"), None);
+
+        assert_eq!(backup.get("password"), Some(Some("hunter2")));
+        #[cfg(unix)]
+        {
+            let config = backup
+                .apply(ConfigBuilder::default())
+                .unwrap()
+                .build()
+                .unwrap();
+            assert_eq!(config.username(), "backup");
+            assert_eq!(config.password(), b"hunter2");
+            assert_eq!(config.stream_type(), StreamType::Unix);
+            assert_eq!(config.host(), "/run/mysqld.sock");
+        }
+
+        assert!(matches!(unknown, Err(OptionFileError::UnknownLoginPath(_))));
+    }
+
+    #[test]
+    fn invalid_option_files_should_fail() {
+        let mut values = OptionValues::default();
+        let err = values
+            .read_str("[client\nuser=root\n", Path::new("my.cnf"), &groups(), 0)
+            .unwrap_err();
+        assert!(matches!(err, OptionFileError::Parse { line: 1, .. }));
+
+        values.set("port", Some("abc".into()));
+        let err = values.apply(ConfigBuilder::default()).unwrap_err();
+        assert!(matches!(err, OptionFileError::InvalidValue { .. }));
+
+        #[cfg(not(unix))]
+        {
+            let mut values = OptionValues::default();
+            values.set("socket", Some("/run/mysqld.sock".into()));
+            let err = values.apply(ConfigBuilder::default()).unwrap_err();
+            assert!(matches!(
+                err,
+                OptionFileError::Config(ConfigError::UnsupportedSocket(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Pipelining of several commands in a single write, without waiting for each response.
+
+use crate::{
+    codec::PacketFrame,
+    connection::{CommandError, Connection, DecodeRow},
+    context::Context,
+    protocol::client::com::{ComMulti, ComQuery, ComStmtExecute, CursorType},
+    result::{ResultSet, Row},
+    statement::{self, Statement},
+    value::Value,
+    EncodePacket,
+};
+
+#[derive(Debug)]
+enum PipelineCommand<'a> {
+    Query(&'a str),
+    Execute(&'a Statement, Vec<Value>),
+}
+
+/// A queue of commands written in one flush by [`Pipeline::run`]. The responses are read
+/// back in order, so a failing command does not affect the next ones. MariaDB servers with
+/// `COM_MULTI` receive the commands wrapped in a single packet instead.
+///
+/// ```no_run
+/// # async fn example(connection: &mut dibi::connection::Connection) -> Result<(), dibi::connection::CommandError> {
+/// let statement = connection.prepare("SELECT name FROM users WHERE id = ?").await?;
+/// let responses = connection
+///     .pipeline()
+///     .query("SELECT 1")
+///     .execute(&statement, &[42.into()])
+///     .query("SELECT 2")
+///     .run()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Pipeline<'a> {
+    connection: &'a mut Connection,
+    commands: Vec<PipelineCommand<'a>>,
+}
+
+/// The response of a pipelined command: its result sets, or the error the server returned
+pub type PipelineResponse = Result<Vec<ResultSet>, CommandError>;
+
+impl<'a> Pipeline<'a> {
+    /// Queues a query with the text protocol
+    pub fn query(mut self, query: &'a str) -> Self {
+        self.commands.push(PipelineCommand::Query(query));
+        self
+    }
+
+    /// Queues the execution of a prepared statement
+    pub fn execute(mut self, statement: &'a Statement, params: &[Value]) -> Self {
+        self.commands
+            .push(PipelineCommand::Execute(statement, params.to_vec()));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Writes every command, then reads their responses in order. Errors sent by the server
+    /// are returned per command, while I/O failures abort the whole pipeline.
+    pub async fn run(self) -> Result<Vec<PipelineResponse>, CommandError> {
+        let Self {
+            connection,
+            commands,
+        } = self;
+        for command in &commands {
+            if let PipelineCommand::Execute(statement, params) = command {
+                statement.check_params(params)?;
+            }
+        }
+        connection.rollback_abandoned().await?;
+
+        let com_multi = connection.supports_com_multi()
+            && commands.len() > 1
+            && commands.iter().all(PipelineCommand::has_one_result);
+        if com_multi {
+            return run_com_multi(connection, &commands).await;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending {} pipelined commands", commands.len());
+        let stream = connection.stream_mut();
+        for command in &commands {
+            match command {
+                PipelineCommand::Query(query) => {
+                    stream.feed_packet(ComQuery::new(query)).await?;
+                }
+                PipelineCommand::Execute(statement, params) => {
+                    let execute = ComStmtExecute::new(statement.id(), CursorType::NoCursor, params);
+                    stream.feed_packet(execute).await?;
+                }
+            }
+        }
+        stream.flush().await?;
+
+        recv_responses(connection, &commands).await
+    }
+}
+
+impl PipelineCommand<'_> {
+    fn encode(&self, context: &Context) -> Result<PacketFrame, std::io::Error> {
+        match self {
+            Self::Query(query) => ComQuery::new(query).encode_packet(context),
+            Self::Execute(statement, params) => {
+                ComStmtExecute::new(statement.id(), CursorType::NoCursor, params)
+                    .encode_packet(context)
+            }
+        }
+    }
+
+    /// Whether the response is a single result set. MariaDB flags every response of a
+    /// `COM_MULTI` but the last with `MORE_RESULTS_EXISTS`, so commands with several result
+    /// sets could not be told apart.
+    fn has_one_result(&self) -> bool {
+        match self {
+            Self::Query(query) => !statement::is_call(query) && !query.contains(';'),
+            Self::Execute(statement, _) => !statement.is_call(),
+        }
+    }
+
+    fn decode_row(&self) -> DecodeRow {
+        match self {
+            Self::Query(_) => Row::decode_text,
+            Self::Execute(..) => Row::decode_binary,
+        }
+    }
+}
+
+/// Sends the commands wrapped in a single `COM_MULTI` packet and reads one result set for
+/// each of them
+async fn run_com_multi(
+    connection: &mut Connection,
+    commands: &[PipelineCommand<'_>],
+) -> Result<Vec<PipelineResponse>, CommandError> {
+    let context = connection.stream().context();
+    let frames = commands
+        .iter()
+        .map(|command| command.encode(context))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!("Sending multi packet with {} commands", frames.len());
+    connection
+        .stream_mut()
+        .send_packet(ComMulti::new(frames))
+        .await?;
+
+    let mut responses = Vec::with_capacity(commands.len());
+    for command in commands {
+        match connection.recv_result(command.decode_row()).await {
+            Ok(result) => responses.push(Ok(vec![result])),
+            Err(err @ (CommandError::Server(_) | CommandError::QueryInterrupted)) => {
+                responses.push(Err(err))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(responses)
+}
+
+/// Reads the responses of commands already written to the stream
+async fn recv_responses(
+    connection: &mut Connection,
+    commands: &[PipelineCommand<'_>],
+) -> Result<Vec<PipelineResponse>, CommandError> {
+    let mut responses = Vec::with_capacity(commands.len());
+    for command in commands {
+        connection.stream_mut().next_response();
+        match connection.recv_results(command.decode_row()).await {
+            Ok(results) => responses.push(Ok(results)),
+            Err(err @ (CommandError::Server(_) | CommandError::QueryInterrupted)) => {
+                responses.push(Err(err))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(responses)
+}
+
+impl Connection {
+    /// Starts a [`Pipeline`] of commands
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline {
+            connection: self,
+            commands: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        connection::tests::{
+            binary_row, column, connect_in_memory, connect_in_memory_with, eof, err, ok,
+            prepare_ok, read_packet, respond, text_row, SERVER_CAPABILITIES,
+        },
+        protocol::{Capability, ServerStatus},
+    };
+
+    #[tokio::test]
+    async fn pipelines_should_return_every_response_in_order() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let status = ServerStatus::AUTOCOMMIT;
+        let server = tokio::spawn(async move {
+            read_packet(&mut server).await;
+            respond(&mut server, &[prepare_ok(1, 0), column("id"), eof(status)]).await;
+
+            let mut commands = Vec::new();
+            for _ in 0..4 {
+                commands.push(read_packet(&mut server).await);
+            }
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("a"),
+                    eof(status),
+                    text_row("1"),
+                    eof(status),
+                ],
+            )
+            .await;
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("id"),
+                    eof(status),
+                    binary_row(2),
+                    eof(status),
+                ],
+            )
+            .await;
+            respond(&mut server, &[err(1054, "Unknown column 'nope'")]).await;
+            respond(&mut server, &[ok(status)]).await;
+            commands
+        });
+
+        assert!(!connection.supports_com_multi());
+        let statement = connection.prepare("SELECT id FROM t").await.unwrap();
+        let responses = connection
+            .pipeline()
+            .query("SELECT 1")
+            .execute(&statement, &[])
+            .query("SELECT nope")
+            .query("DO 2")
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 4);
+        let text = responses[0].as_ref().unwrap();
+        assert_eq!(text[0].rows[0].get(0), Some(&Value::from("1")));
+        let binary = responses[1].as_ref().unwrap();
+        assert_eq!(binary[0].rows[0].get(0), Some(&Value::Int(2)));
+        assert!(matches!(
+            &responses[2],
+            Err(CommandError::Server(err)) if err.code == 1054
+        ));
+        assert!(responses[3].as_ref().unwrap()[0].rows.is_empty());
+
+        // Each command is a packet of its own, starting a new sequence
+        let commands = server.await.unwrap();
+        assert_eq!(commands[0], (0, b"\x03SELECT 1".to_vec()));
+        assert_eq!((commands[1].0, commands[1].1[0]), (0, 0x17));
+        assert_eq!(commands[2], (0, b"\x03SELECT nope".to_vec()));
+        assert_eq!(commands[3], (0, b"\x03DO 2".to_vec()));
+        assert_eq!(connection.stream().outstanding(), 0);
+    }
+
+    /// A MariaDB server with `COM_MULTI`
+    async fn connect_com_multi() -> (Connection, tokio::io::DuplexStream) {
+        let capabilities =
+            SERVER_CAPABILITIES.difference(Capability::CLIENT_MYSQL) | Capability::COM_MULTI;
+        let (connection, server) = connect_in_memory_with(capabilities).await;
+        assert!(connection.supports_com_multi());
+        (connection, server)
+    }
+
+    #[tokio::test]
+    async fn pipelines_should_be_wrapped_in_com_multi() {
+        let (mut connection, mut server) = connect_com_multi().await;
+        let status = ServerStatus::AUTOCOMMIT;
+        let more = status | ServerStatus::MORE_RESULTS_EXISTS;
+        let server = tokio::spawn(async move {
+            let multi = read_packet(&mut server).await;
+            // The responses continue the sequence of the single packet
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("a"),
+                    eof(more),
+                    text_row("1"),
+                    eof(more),
+                    err(1054, "Unknown column 'nope'"),
+                    ok(status),
+                ],
+            )
+            .await;
+            multi
+        });
+
+        let responses = connection
+            .pipeline()
+            .query("SELECT 1")
+            .query("SELECT nope")
+            .query("DO 1")
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 3);
+        let first = responses[0].as_ref().unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].rows[0].get(0), Some(&Value::from("1")));
+        assert!(matches!(
+            &responses[1],
+            Err(CommandError::Server(err)) if err.code == 1054
+        ));
+        assert!(responses[2].as_ref().unwrap()[0].rows.is_empty());
+
+        let mut expected = vec![0xFE, 9];
+        expected.extend_from_slice(b"\x03SELECT 1\x0c\x03SELECT nope\x05\x03DO 1");
+        assert_eq!(server.await.unwrap(), (0, expected));
+    }
+
+    #[tokio::test]
+    async fn pipelines_with_several_result_sets_should_not_use_com_multi() {
+        let (mut connection, mut server) = connect_com_multi().await;
+        let status = ServerStatus::AUTOCOMMIT;
+        let more = status | ServerStatus::MORE_RESULTS_EXISTS;
+        let server = tokio::spawn(async move {
+            let call = read_packet(&mut server).await;
+            let query = read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("a"),
+                    eof(status),
+                    text_row("1"),
+                    eof(more),
+                    ok(status),
+                ],
+            )
+            .await;
+            respond(&mut server, &[ok(status)]).await;
+            (call, query)
+        });
+
+        let responses = connection
+            .pipeline()
+            .query("CALL p()")
+            .query("DO 1")
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(responses[0].as_ref().unwrap().len(), 2);
+        assert_eq!(responses[1].as_ref().unwrap().len(), 1);
+
+        let (call, query) = server.await.unwrap();
+        assert_eq!(call, (0, b"\x03CALL p()".to_vec()));
+        assert_eq!(query, (0, b"\x03DO 1".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn single_commands_should_not_use_com_multi() {
+        let (mut connection, mut server) = connect_com_multi().await;
+        let server = tokio::spawn(async move {
+            let query = read_packet(&mut server).await;
+            respond(&mut server, &[ok(ServerStatus::AUTOCOMMIT)]).await;
+            query
+        });
+
+        let responses = connection.pipeline().query("DO 1").run().await.unwrap();
+        assert!(responses[0].is_ok());
+        assert_eq!(server.await.unwrap(), (0, b"\x03DO 1".to_vec()));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Weak},
+};
+
+use tokio::{sync::OwnedSemaphorePermit, time::Instant};
+
+use crate::connection::Connection;
+
+use super::PoolInner;
+
+/// A connection owned by the pool, counted in its size for as long as it lives
+#[derive(Debug)]
+pub(crate) struct Live {
+    pub(crate) connection: Connection,
+    pub(crate) expires_at: Option<Instant>,
+    pub(crate) _slot: Slot,
+}
+
+/// Decrements the size of the pool when the connection holding it is dropped
+#[derive(Debug)]
+pub(crate) struct Slot {
+    pool: Weak<PoolInner>,
+}
+
+impl Slot {
+    pub(crate) fn new(pool: &Arc<PoolInner>) -> Self {
+        pool.increment_size();
+        Self {
+            pool: Arc::downgrade(pool),
+        }
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            pool.decrement_size();
+        }
+    }
+}
+
+/// A connection checked out of a [`Pool`](super::Pool). It is reset and returned to the pool
+/// when dropped.
+#[derive(Debug)]
+pub struct PooledConnection {
+    live: Option<Live>,
+    permit: Option<OwnedSemaphorePermit>,
+    pool: Arc<PoolInner>,
+}
+
+impl PooledConnection {
+    pub(crate) fn new(live: Live, permit: OwnedSemaphorePermit, pool: Arc<PoolInner>) -> Self {
+        Self {
+            live: Some(live),
+            permit: Some(permit),
+            pool,
+        }
+    }
+
+    /// Takes the connection out of the pool, which then opens another one in its place when
+    /// needed
+    pub fn detach(mut self) -> Connection {
+        let live = self
+            .live
+            .take()
+            .expect("connection is present until dropped");
+        live.connection
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self
+            .live
+            .as_ref()
+            .expect("connection is present until dropped")
+            .connection
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self
+            .live
+            .as_mut()
+            .expect("connection is present until dropped")
+            .connection
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let (Some(live), Some(permit)) = (self.live.take(), self.permit.take()) else {
+            return;
+        };
+        if live.connection.is_broken() || self.pool.is_closed() {
+            return;
+        }
+
+        // Resetting needs a round trip, which can only happen on a runtime. The permit is held
+        // until the connection is back in the pool so the maximum size is never exceeded.
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let pool = self.pool.clone();
+            handle.spawn(async move {
+                pool.release(live).await;
+                drop(permit);
+            });
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// A snapshot of the state of a [`Pool`](super::Pool)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PoolMetrics {
+    /// The number of open connections, idle or in use
+    pub size: usize,
+    /// The number of connections waiting in the pool
+    pub idle: usize,
+    /// The number of connections currently checked out
+    pub in_use: usize,
+    pub max_size: usize,
+    /// The number of successful acquires
+    pub acquired: u64,
+    /// The number of acquires that gave up after the acquire timeout
+    pub acquire_timeouts: u64,
+    /// The time spent waiting in every successful acquire combined
+    pub total_wait: Duration,
+    /// The longest time a successful acquire waited
+    pub max_wait: Duration,
+}
+
+impl PoolMetrics {
+    /// The average time waited by an acquire
+    pub fn average_wait(&self) -> Duration {
+        if self.acquired == 0 {
+            Duration::ZERO
+        } else {
+            let nanos = self.total_wait.as_nanos() / u128::from(self.acquired);
+            Duration::from_nanos(nanos as u64)
+        }
+    }
+
+    /// The fraction of the maximum size currently checked out, between 0 and 1
+    pub fn utilization(&self) -> f64 {
+        if self.max_size == 0 {
+            0.0
+        } else {
+            self.in_use as f64 / self.max_size as f64
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    acquired: AtomicU64,
+    acquire_timeouts: AtomicU64,
+    total_wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn record_acquire(&self, wait: Duration) {
+        let nanos = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        self.acquired.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_timeout(&self) {
+        self.acquire_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fill(&self, metrics: &mut PoolMetrics) {
+        metrics.acquired = self.acquired.load(Ordering::Relaxed);
+        metrics.acquire_timeouts = self.acquire_timeouts.load(Ordering::Relaxed);
+        metrics.total_wait = Duration::from_nanos(self.total_wait_nanos.load(Ordering::Relaxed));
+        metrics.max_wait = Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_should_be_computed_from_counters() {
+        let counters = Counters::default();
+        counters.record_acquire(Duration::from_millis(10));
+        counters.record_acquire(Duration::from_millis(30));
+        counters.record_timeout();
+
+        let mut metrics = PoolMetrics {
+            in_use: 3,
+            max_size: 4,
+            ..Default::default()
+        };
+        counters.fill(&mut metrics);
+
+        assert_eq!(metrics.acquired, 2);
+        assert_eq!(metrics.acquire_timeouts, 1);
+        assert_eq!(metrics.max_wait, Duration::from_millis(30));
+        assert_eq!(metrics.average_wait(), Duration::from_millis(20));
+        assert_eq!(metrics.utilization(), 0.75);
+    }
+
+    #[test]
+    fn average_wait_should_not_truncate_the_count() {
+        let metrics = PoolMetrics {
+            acquired: 1 << 32,
+            total_wait: Duration::from_secs(1 << 32),
+            ..Default::default()
+        };
+        assert_eq!(metrics.average_wait(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,518 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use tokio::{sync::Semaphore, time::Instant};
+
+use crate::{
+    config::Config,
+    connection::{ConnectError, Connection, ConnectionOption},
+    stream::Stream,
+};
+
+mod guard;
+mod metrics;
+mod options;
+
+use guard::{Live, Slot};
+use metrics::Counters;
+
+pub use guard::PooledConnection;
+pub use metrics::PoolMetrics;
+pub use options::PoolOptions;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error("failed to open a pooled connection")]
+    Connect(#[from] ConnectError),
+
+    #[error("timed out waiting for a pooled connection")]
+    AcquireTimeout,
+
+    #[error("the pool is closed")]
+    Closed,
+
+    #[error("invalid pool size, min {min} must not exceed max {max} and max must be positive")]
+    InvalidSize { min: usize, max: usize },
+
+    #[error("the maintenance interval must be positive")]
+    InvalidMaintenanceInterval,
+}
+
+/// Opens the transport of each new connection instead of dialing the host of the config
+pub(crate) type OpenStream = Arc<dyn Fn() -> Stream + Send + Sync>;
+
+/// An async pool of connections to the same server. Cloning the pool is cheap and every clone
+/// shares the same connections.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct Idle {
+    live: Live,
+    since: Instant,
+}
+
+pub(crate) struct PoolInner {
+    config: Arc<Config>,
+    open_stream: Option<OpenStream>,
+    options: PoolOptions,
+    semaphore: Arc<Semaphore>,
+    idle: Mutex<VecDeque<Idle>>,
+    size: AtomicUsize,
+    closed: AtomicBool,
+    counters: Counters,
+}
+
+impl Pool {
+    /// Creates the pool and opens its first `min_size` connections
+    pub async fn connect(
+        options: &ConnectionOption<'_>,
+        pool_options: PoolOptions,
+    ) -> Result<Self, PoolError> {
+        Self::connect_config(Arc::new(Config::from(options)), pool_options, None).await
+    }
+
+    pub async fn connect_with_config(
+        config: Config,
+        pool_options: PoolOptions,
+    ) -> Result<Self, PoolError> {
+        Self::connect_config(Arc::new(config), pool_options, None).await
+    }
+
+    pub(crate) async fn connect_config(
+        config: Arc<Config>,
+        options: PoolOptions,
+        open_stream: Option<OpenStream>,
+    ) -> Result<Self, PoolError> {
+        if options.max_size == 0 || options.min_size > options.max_size {
+            return Err(PoolError::InvalidSize {
+                min: options.min_size,
+                max: options.max_size,
+            });
+        }
+        // A zero period would make the maintenance task panic
+        if options.maintenance_interval.is_zero() {
+            return Err(PoolError::InvalidMaintenanceInterval);
+        }
+
+        let inner = Arc::new(PoolInner {
+            config,
+            open_stream,
+            semaphore: Arc::new(Semaphore::new(options.max_size)),
+            idle: Mutex::new(VecDeque::with_capacity(options.max_size)),
+            size: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            counters: Counters::default(),
+            options,
+        });
+
+        inner.fill().await?;
+        tokio::spawn(PoolInner::maintain(
+            Arc::downgrade(&inner),
+            inner.options.maintenance_interval,
+        ));
+
+        Ok(Self { inner })
+    }
+
+    /// Checks out a connection, reusing an idle one when possible. Waits up to the acquire
+    /// timeout when every connection is in use.
+    pub async fn acquire(&self) -> Result<PooledConnection, PoolError> {
+        let start = Instant::now();
+        let acquire = self.inner.clone().acquire();
+        let connection = match self.inner.options.acquire_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, acquire).await {
+                Ok(connection) => connection,
+                Err(_) => {
+                    self.inner.counters.record_timeout();
+                    return Err(PoolError::AcquireTimeout);
+                }
+            },
+            None => acquire.await,
+        }?;
+        self.inner.counters.record_acquire(start.elapsed());
+        Ok(connection)
+    }
+
+    /// Closes every idle connection and makes any further acquire fail. Checked out
+    /// connections are closed when they are dropped.
+    pub fn close(&self) {
+        self.inner.closed.store(true, Ordering::Release);
+        self.inner.semaphore.close();
+        self.inner.lock_idle().clear();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub fn options(&self) -> &PoolOptions {
+        &self.inner.options
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let max_size = self.inner.options.max_size;
+        let mut metrics = PoolMetrics {
+            size: self.inner.size.load(Ordering::Acquire),
+            idle: self.inner.lock_idle().len(),
+            in_use: max_size.saturating_sub(self.inner.semaphore.available_permits()),
+            max_size,
+            ..Default::default()
+        };
+        self.inner.counters.fill(&mut metrics);
+        metrics
+    }
+}
+
+impl PoolInner {
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, VecDeque<Idle>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn increment_size(&self) {
+        self.size.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub(crate) fn decrement_size(&self) {
+        self.size.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn is_expired(&self, idle: &Idle, now: Instant) -> bool {
+        let lifetime_expired = idle.live.expires_at.is_some_and(|at| at <= now);
+        let idle_expired = self
+            .options
+            .idle_timeout
+            .is_some_and(|timeout| idle.since + timeout <= now);
+        lifetime_expired || idle_expired
+    }
+
+    async fn acquire(self: Arc<Self>) -> Result<PooledConnection, PoolError> {
+        if self.is_closed() {
+            return Err(PoolError::Closed);
+        }
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| PoolError::Closed)?;
+
+        loop {
+            let Some(idle) = self.lock_idle().pop_front() else {
+                break;
+            };
+            if self.is_expired(&idle, Instant::now()) {
+                continue;
+            }
+            let mut live = idle.live;
+            if self.options.health_check && live.connection.ping().await.is_err() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("Discarding pooled connection that failed its health check");
+                continue;
+            }
+            return Ok(PooledConnection::new(live, permit, self));
+        }
+
+        let live = self.open().await?;
+        Ok(PooledConnection::new(live, permit, self))
+    }
+
+    async fn open(self: &Arc<Self>) -> Result<Live, PoolError> {
+        let slot = Slot::new(self);
+        let stream = self.open_stream.as_ref().map(|open| open());
+        let connection = Connection::connect_stream(self.config.clone(), stream).await?;
+        let expires_at = self.options.max_lifetime.map(|lifetime| {
+            let jitter = self.options.max_lifetime_jitter.min(lifetime);
+            Instant::now() + lifetime - jitter.mul_f64(random_fraction())
+        });
+        Ok(Live {
+            connection,
+            expires_at,
+            _slot: slot,
+        })
+    }
+
+    /// Resets a connection that was checked out and puts it back in the idle queue
+    pub(crate) async fn release(&self, mut live: Live) {
+        if let Err(_err) = live.connection.reset().await {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "Discarding pooled connection that failed to reset: {}",
+                _err
+            );
+            return;
+        }
+        if self.is_closed() {
+            return;
+        }
+        self.lock_idle().push_back(Idle {
+            live,
+            since: Instant::now(),
+        });
+    }
+
+    /// Opens idle connections until the pool holds at least `min_size` of them
+    async fn fill(self: &Arc<Self>) -> Result<(), PoolError> {
+        while self.size.load(Ordering::Acquire) < self.options.min_size && !self.is_closed() {
+            let Ok(permit) = self.semaphore.clone().try_acquire_owned() else {
+                break;
+            };
+            let live = self.open().await?;
+            self.lock_idle().push_back(Idle {
+                live,
+                since: Instant::now(),
+            });
+            drop(permit);
+        }
+        Ok(())
+    }
+
+    /// Drops expired idle connections and refills the pool, until the pool is dropped or closed
+    async fn maintain(pool: Weak<PoolInner>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let Some(pool) = pool.upgrade() else {
+                break;
+            };
+            if pool.is_closed() {
+                break;
+            }
+
+            let now = Instant::now();
+            pool.lock_idle().retain(|idle| !pool.is_expired(idle, now));
+
+            if let Err(_err) = pool.fill().await {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("Failed to refill the pool: {}", _err);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PoolInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolInner")
+            .field("config", &self.config)
+            .field("options", &self.options)
+            .field("size", &self.size)
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A random number in `[0, 1)`, seeded from the per-instance random keys of the standard
+/// library hasher
+fn random_fraction() -> f64 {
+    let hash = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::*;
+    use crate::connection::tests::{
+        initial_handshake, read_packet, write_packet, OK, SERVER_CAPABILITIES,
+    };
+
+    /// Answers the logins, pings and resets of the pooled connections in memory
+    #[derive(Default)]
+    struct FakeServer {
+        opened: AtomicUsize,
+        commands: Mutex<Vec<u8>>,
+    }
+
+    impl FakeServer {
+        fn opener(self: &Arc<Self>) -> OpenStream {
+            let server = self.clone();
+            Arc::new(move || {
+                let (client, stream) = tokio::io::duplex(4096);
+                server.opened.fetch_add(1, Ordering::AcqRel);
+                tokio::spawn(server.clone().serve(stream));
+                Stream::custom(client)
+            })
+        }
+
+        async fn serve(self: Arc<Self>, mut stream: DuplexStream) {
+            write_packet(&mut stream, 0, &initial_handshake(SERVER_CAPABILITIES)).await;
+            read_packet(&mut stream).await;
+            write_packet(&mut stream, 2, OK).await;
+
+            let mut header = [0; 4];
+            while stream.read_exact(&mut header).await.is_ok() {
+                let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+                let mut payload = vec![0; len];
+                if stream.read_exact(&mut payload).await.is_err() {
+                    break;
+                }
+                self.commands.lock().unwrap().push(payload[0]);
+                if payload[0] == 0x01 {
+                    break;
+                }
+                let mut ok = vec![OK.len() as u8, 0, 0, 1];
+                ok.extend_from_slice(OK);
+                if stream.write_all(&ok).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        fn opened(&self) -> usize {
+            self.opened.load(Ordering::Acquire)
+        }
+
+        /// Takes the command bytes received so far
+        fn commands(&self) -> Vec<u8> {
+            std::mem::take(&mut self.commands.lock().unwrap())
+        }
+    }
+
+    async fn fake_pool(server: &Arc<FakeServer>, options: PoolOptions) -> Pool {
+        let config = Arc::new(Config::from(&ConnectionOption::default()));
+        Pool::connect_config(config, options, Some(server.opener()))
+            .await
+            .unwrap()
+    }
+
+    /// Waits for the connections dropped so far to be reset back into the pool
+    async fn until_idle(pool: &Pool, idle: usize) {
+        while pool.metrics().idle < idle {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn random_fraction_should_be_in_range() {
+        for _ in 0..100 {
+            let fraction = random_fraction();
+            assert!((0.0..1.0).contains(&fraction));
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_sizes_should_be_rejected() {
+        let options = PoolOptions {
+            min_size: 4,
+            max_size: 2,
+            ..Default::default()
+        };
+        let result = Pool::connect(&ConnectionOption::default(), options).await;
+        assert!(matches!(
+            result,
+            Err(PoolError::InvalidSize { min: 4, max: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn zero_maintenance_intervals_should_be_rejected() {
+        let options = PoolOptions {
+            maintenance_interval: Duration::ZERO,
+            ..Default::default()
+        };
+        let result = Pool::connect(&ConnectionOption::default(), options).await;
+        assert!(matches!(result, Err(PoolError::InvalidMaintenanceInterval)));
+    }
+
+    #[tokio::test]
+    async fn connections_should_be_reset_and_reused() {
+        let server = Arc::new(FakeServer::default());
+        let pool = fake_pool(&server, PoolOptions::default()).await;
+
+        let connection = pool.acquire().await.unwrap();
+        assert_eq!(pool.metrics().in_use, 1);
+        drop(connection);
+        until_idle(&pool, 1).await;
+        // COM_RESET_CONNECTION when dropped
+        assert_eq!(server.commands(), [0x1F]);
+
+        let _connection = pool.acquire().await.unwrap();
+        // COM_PING as the health check of the idle connection
+        assert_eq!(server.commands(), [0x0E]);
+        assert_eq!(server.opened(), 1);
+        assert_eq!(pool.metrics().size, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquires_should_wait_for_a_connection_up_to_the_timeout() {
+        let server = Arc::new(FakeServer::default());
+        let options = PoolOptions {
+            max_size: 1,
+            acquire_timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let pool = fake_pool(&server, options).await;
+
+        let connection = pool.acquire().await.unwrap();
+        assert!(matches!(
+            pool.acquire().await,
+            Err(PoolError::AcquireTimeout)
+        ));
+
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire().await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        drop(connection);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(server.opened(), 1);
+        assert_eq!(pool.metrics().acquire_timeouts, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_connections_should_be_replaced() {
+        let server = Arc::new(FakeServer::default());
+        let options = PoolOptions {
+            idle_timeout: Some(Duration::from_secs(10)),
+            max_lifetime: None,
+            ..Default::default()
+        };
+        let pool = fake_pool(&server, options).await;
+        drop(pool.acquire().await.unwrap());
+        until_idle(&pool, 1).await;
+        tokio::time::advance(Duration::from_secs(5)).await;
+        drop(pool.acquire().await.unwrap());
+        until_idle(&pool, 1).await;
+        assert_eq!(server.opened(), 1);
+        tokio::time::advance(Duration::from_secs(10)).await;
+        drop(pool.acquire().await.unwrap());
+        assert_eq!(server.opened(), 2);
+
+        let server = Arc::new(FakeServer::default());
+        let options = PoolOptions {
+            idle_timeout: None,
+            max_lifetime: Some(Duration::from_secs(60)),
+            max_lifetime_jitter: Duration::ZERO,
+            ..Default::default()
+        };
+        let pool = fake_pool(&server, options).await;
+        drop(pool.acquire().await.unwrap());
+        until_idle(&pool, 1).await;
+        tokio::time::advance(Duration::from_secs(59)).await;
+        drop(pool.acquire().await.unwrap());
+        until_idle(&pool, 1).await;
+        assert_eq!(server.opened(), 1);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        drop(pool.acquire().await.unwrap());
+        assert_eq!(server.opened(), 2);
+    }
+}
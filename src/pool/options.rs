@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+/// The sizing and recycling rules of a [`Pool`](super::Pool)
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    /// The number of connections the pool keeps open, even when idle
+    pub min_size: usize,
+    /// The maximum number of open connections, idle or in use
+    pub max_size: usize,
+    /// Bounds waiting for a connection in [`Pool::acquire`](super::Pool::acquire)
+    pub acquire_timeout: Option<Duration>,
+    /// Closes connections that stayed idle for longer than this
+    pub idle_timeout: Option<Duration>,
+    /// Closes connections that have been open for longer than this
+    pub max_lifetime: Option<Duration>,
+    /// Shortens the lifetime of each connection by a random amount up to this, so
+    /// connections opened together are not all recycled at once
+    pub max_lifetime_jitter: Duration,
+    /// Pings idle connections before handing them out
+    pub health_check: bool,
+    /// How often idle connections are expired and the pool is refilled up to `min_size`, must
+    /// not be zero
+    pub maintenance_interval: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            min_size: 0,
+            max_size: 10,
+            acquire_timeout: Some(Duration::from_secs(30)),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+            max_lifetime_jitter: Duration::from_secs(60),
+            health_check: true,
+            maintenance_interval: Duration::from_secs(30),
+        }
+    }
+}
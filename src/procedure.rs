@@ -0,0 +1,403 @@
+//! Calls of stored procedures that return their `OUT` and `INOUT` parameters.
+
+use std::fmt::Write;
+
+use crate::{
+    connection::{CommandError, Connection, ResultHead},
+    protocol::{server::OkPacket, ServerStatus},
+    result::{ResultSet, Row},
+    statement::Statement,
+    value::Value,
+};
+
+/// A parameter of a stored procedure call
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallParam {
+    In(Value),
+    Out,
+    InOut(Value),
+}
+
+impl CallParam {
+    fn is_out(&self) -> bool {
+        matches!(self, Self::Out | Self::InOut(_))
+    }
+
+    /// The value sent to the server, `NULL` for `OUT` parameters
+    fn value(&self) -> Value {
+        match self {
+            Self::In(value) | Self::InOut(value) => value.clone(),
+            Self::Out => Value::Null,
+        }
+    }
+}
+
+/// The outcome of [`Connection::call`]
+#[derive(Debug, Clone, Default)]
+pub struct CallResult {
+    /// The result sets produced by the procedure body
+    pub results: Vec<ResultSet>,
+    /// The `OUT` and `INOUT` parameters in their declaration order, if the call has any
+    pub out_params: Option<Row>,
+    /// The OK packet that ends the call
+    pub status: OkPacket,
+}
+
+/// The user variable holding the `i`th parameter for a text protocol call
+fn variable(i: usize) -> String {
+    format!("@dibi_param_{}", i)
+}
+
+/// How the connection reads string literals
+#[derive(Debug, Clone, Copy)]
+struct Literals {
+    /// The character set of the connection, if known
+    charset: Option<&'static str>,
+    /// Whether `NO_BACKSLASH_ESCAPES` is set, making backslashes plain characters
+    no_backslash_escapes: bool,
+}
+
+/// Formats a value as an SQL literal. Strings are hex literals converted to the connection
+/// character set, as a bare hex literal is binary and even a number in numeric context.
+fn literal(value: &Value, literals: Literals) -> String {
+    match value {
+        Value::Null => "NULL".to_owned(),
+        Value::Bytes(bytes) if bytes.is_empty() => "''".to_owned(),
+        Value::Bytes(bytes) => match literals.charset {
+            Some(charset) => {
+                let mut literal = String::with_capacity(bytes.len() * 2 + charset.len() + 20);
+                literal.push_str("CONVERT(X'");
+                for b in bytes.iter() {
+                    let _ = write!(literal, "{:02X}", b);
+                }
+                let _ = write!(literal, "' USING {})", charset);
+                literal
+            }
+            None => quoted(bytes, literals.no_backslash_escapes),
+        },
+        Value::Int(value) => value.to_string(),
+        Value::UInt(value) => value.to_string(),
+        Value::Float(value) => format!("{:e}", value),
+        Value::Double(value) => format!("{:e}", value),
+    }
+}
+
+/// A quoted string literal, escaped for the given `NO_BACKSLASH_ESCAPES` mode
+fn quoted(bytes: &[u8], no_backslash_escapes: bool) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('\'');
+    for c in text.chars() {
+        match c {
+            '\'' => literal.push_str("''"),
+            '\\' if !no_backslash_escapes => literal.push_str("\\\\"),
+            '\0' if !no_backslash_escapes => literal.push_str("\\0"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('\'');
+    literal
+}
+
+impl Connection {
+    fn literals(&self) -> Literals {
+        Literals {
+            charset: self.client_charset(),
+            no_backslash_escapes: self
+                .status_flags()
+                .contains(ServerStatus::NO_BACKSLASH_ESCAPES),
+        }
+    }
+
+    /// Calls a stored procedure with a prepared statement. The server returns the `OUT` and
+    /// `INOUT` parameters as an extra result set flagged with `PS_OUT_PARAMS`.
+    pub async fn call(
+        &mut self,
+        procedure: &str,
+        params: &[CallParam],
+    ) -> Result<CallResult, CommandError> {
+        let placeholders = vec!["?"; params.len()].join(", ");
+        let statement = self
+            .prepare(&format!("CALL {}({})", procedure, placeholders))
+            .await?;
+        let values: Vec<Value> = params.iter().map(CallParam::value).collect();
+
+        let result = self.call_prepared(&statement, &values).await;
+        let closed = self.close_statement(statement).await;
+        let call = result?;
+        closed?;
+        Ok(call)
+    }
+
+    async fn call_prepared(
+        &mut self,
+        statement: &Statement,
+        values: &[Value],
+    ) -> Result<CallResult, CommandError> {
+        self.send_execute(statement, values).await?;
+
+        let mut call = CallResult::default();
+        loop {
+            match self.recv_result_head().await? {
+                ResultHead::Ok(status) => call.status = status,
+                ResultHead::Columns(columns) => {
+                    let is_out = self.status_flags().contains(ServerStatus::PS_OUT_PARAMS);
+                    let mut rows = Vec::new();
+                    let status = self
+                        .recv_rows(&columns, Row::decode_binary, &mut rows)
+                        .await?;
+                    if is_out || status.status_flags.contains(ServerStatus::PS_OUT_PARAMS) {
+                        call.out_params = rows.into_iter().next();
+                    } else {
+                        call.results.push(ResultSet {
+                            columns,
+                            rows,
+                            status,
+                        });
+                    }
+                }
+            }
+            if !self
+                .status_flags()
+                .contains(ServerStatus::MORE_RESULTS_EXISTS)
+            {
+                return Ok(call);
+            }
+        }
+    }
+
+    /// Calls a stored procedure with the text protocol, passing the `OUT` and `INOUT`
+    /// parameters through user variables that are selected after the call
+    pub async fn call_text(
+        &mut self,
+        procedure: &str,
+        params: &[CallParam],
+    ) -> Result<CallResult, CommandError> {
+        let literals = self.literals();
+        let inout: Vec<String> = params
+            .iter()
+            .enumerate()
+            .filter_map(|(i, param)| match param {
+                CallParam::InOut(value) => {
+                    Some(format!("{} = {}", variable(i), literal(value, literals)))
+                }
+                _ => None,
+            })
+            .collect();
+        if !inout.is_empty() {
+            self.query(&format!("SET {}", inout.join(", "))).await?;
+        }
+
+        let args: Vec<String> = params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| match param {
+                CallParam::In(value) => literal(value, literals),
+                CallParam::Out | CallParam::InOut(_) => variable(i),
+            })
+            .collect();
+        let mut results = self
+            .query_multi(&format!("CALL {}({})", procedure, args.join(", ")))
+            .await?;
+        let status = results
+            .pop()
+            .map(|result| result.status)
+            .unwrap_or_default();
+
+        let outs: Vec<String> = params
+            .iter()
+            .enumerate()
+            .filter(|(_, param)| param.is_out())
+            .map(|(i, _)| variable(i))
+            .collect();
+        let out_params = if outs.is_empty() {
+            None
+        } else {
+            let select = self.query(&format!("SELECT {}", outs.join(", "))).await?;
+            select.rows.into_iter().next()
+        };
+
+        Ok(CallResult {
+            results,
+            out_params,
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{
+        binary_row, column, connect_in_memory, eof, ok, prepare_ok, read_packet, respond, text_row,
+    };
+
+    const UTF8MB4: Literals = Literals {
+        charset: Some("utf8mb4"),
+        no_backslash_escapes: false,
+    };
+
+    #[test]
+    fn values_should_format_as_sql_literals() {
+        assert_eq!(literal(&Value::Null, UTF8MB4), "NULL");
+        assert_eq!(
+            literal(&Value::from("a'b"), UTF8MB4),
+            "CONVERT(X'612762' USING utf8mb4)"
+        );
+        assert_eq!(literal(&Value::from(""), UTF8MB4), "''");
+        assert_eq!(literal(&Value::Int(-3), UTF8MB4), "-3");
+        assert_eq!(literal(&Value::Double(1.5), UTF8MB4), "1.5e0");
+
+        let unknown = Literals {
+            charset: None,
+            no_backslash_escapes: false,
+        };
+        assert_eq!(literal(&Value::from("a'b\\c"), unknown), "'a''b\\\\c'");
+        let no_backslash_escapes = Literals {
+            no_backslash_escapes: true,
+            ..unknown
+        };
+        assert_eq!(
+            literal(&Value::from("a'b\\c"), no_backslash_escapes),
+            "'a''b\\c'"
+        );
+    }
+
+    #[tokio::test]
+    async fn prepared_calls_should_return_the_out_params() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let more = ServerStatus::AUTOCOMMIT | ServerStatus::MORE_RESULTS_EXISTS;
+        let out = more | ServerStatus::PS_OUT_PARAMS;
+        let server = tokio::spawn(async move {
+            let (_, prepare) = read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[
+                    prepare_ok(0, 2),
+                    column("?"),
+                    column("?"),
+                    eof(ServerStatus::AUTOCOMMIT),
+                ],
+            )
+            .await;
+
+            let (_, execute) = read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("total"),
+                    eof(ServerStatus::AUTOCOMMIT),
+                    binary_row(5),
+                    eof(more),
+                    vec![1],
+                    column("result"),
+                    eof(out),
+                    binary_row(12),
+                    eof(out),
+                    ok(ServerStatus::AUTOCOMMIT),
+                ],
+            )
+            .await;
+
+            let (_, close) = read_packet(&mut server).await;
+            (prepare, execute[0], close)
+        });
+
+        let call = connection
+            .call(
+                "shop.total",
+                &[CallParam::In(Value::Int(4)), CallParam::Out],
+            )
+            .await
+            .unwrap();
+        assert_eq!(call.results.len(), 1);
+        assert_eq!(call.results[0].rows[0].get(0), Some(&Value::Int(5)));
+        let out_params = call.out_params.unwrap();
+        assert_eq!(out_params.get_by_name("result"), Some(&Value::Int(12)));
+        assert!(!call
+            .status
+            .status_flags
+            .contains(ServerStatus::MORE_RESULTS_EXISTS));
+
+        let (prepare, execute, close) = server.await.unwrap();
+        assert_eq!(prepare, b"\x16CALL shop.total(?, ?)");
+        assert_eq!(execute, 0x17);
+        assert_eq!(close, [0x19, 1, 0, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn text_calls_should_select_the_out_params() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let more = ServerStatus::AUTOCOMMIT | ServerStatus::MORE_RESULTS_EXISTS;
+        let server = tokio::spawn(async move {
+            let mut queries = Vec::new();
+            let (_, set) = read_packet(&mut server).await;
+            queries.push(set);
+            respond(&mut server, &[ok(ServerStatus::AUTOCOMMIT)]).await;
+
+            let (_, call) = read_packet(&mut server).await;
+            queries.push(call);
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("total"),
+                    eof(ServerStatus::AUTOCOMMIT),
+                    text_row("5"),
+                    eof(more),
+                    ok(ServerStatus::AUTOCOMMIT),
+                ],
+            )
+            .await;
+
+            let (_, select) = read_packet(&mut server).await;
+            queries.push(select);
+            let mut row = text_row("12");
+            row.extend_from_slice(&text_row("3"));
+            respond(
+                &mut server,
+                &[
+                    vec![2],
+                    column("@dibi_param_1"),
+                    column("@dibi_param_2"),
+                    eof(ServerStatus::AUTOCOMMIT),
+                    row,
+                    eof(ServerStatus::AUTOCOMMIT),
+                ],
+            )
+            .await;
+            queries
+        });
+
+        let params = [
+            CallParam::In(Value::from("7")),
+            CallParam::Out,
+            CallParam::InOut(Value::Int(2)),
+        ];
+        let call = connection.call_text("shop.total", &params).await.unwrap();
+        assert_eq!(call.results.len(), 1);
+        assert_eq!(
+            call.results[0].rows[0].get(0).and_then(Value::as_u64),
+            Some(5)
+        );
+        let out_params = call.out_params.unwrap();
+        assert_eq!(out_params.get(0).and_then(Value::as_u64), Some(12));
+        assert_eq!(out_params.get(1).and_then(Value::as_u64), Some(3));
+        assert!(!call
+            .status
+            .status_flags
+            .contains(ServerStatus::MORE_RESULTS_EXISTS));
+
+        let queries = server.await.unwrap();
+        let queries: Vec<&[u8]> = queries.iter().map(|query| &query[1..]).collect();
+        assert_eq!(
+            queries,
+            [
+                b"SET @dibi_param_2 = 2".as_slice(),
+                b"CALL shop.total(CONVERT(X'37' USING utf8mb4), @dibi_param_1, @dibi_param_2)",
+                b"SELECT @dibi_param_1, @dibi_param_2",
+            ]
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+/// Asks the server to return an EOF packet at the end of the binlog instead of waiting for
+/// new events
+pub const BINLOG_DUMP_NON_BLOCK: u16 = 0x01;
+
+/// Requests a binlog stream from a file and position with
+/// [`COM_BINLOG_DUMP`](https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_binlog_dump.html)
+#[derive(Debug)]
+pub struct ComBinlogDump<'a> {
+    position: u32,
+    flags: u16,
+    server_id: u32,
+    file: &'a str,
+}
+
+impl<'a> ComBinlogDump<'a> {
+    pub fn new(server_id: u32, file: &'a str, position: u32, flags: u16) -> Self {
+        Self {
+            position,
+            flags,
+            server_id,
+            file,
+        }
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComBinlogDump<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(11 + self.file.len());
+        bytes.put_u8(0x12);
+        bytes.put_u32_le(self.position);
+        bytes.put_u16_le(self.flags);
+        bytes.put_u32_le(self.server_id);
+        bytes.put_slice(self.file.as_bytes());
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,56 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+/// The encoded GTID set follows the binlog position
+pub const BINLOG_THROUGH_GTID: u16 = 0x04;
+
+/// Requests a binlog stream skipping the transactions of a GTID set with
+/// [`COM_BINLOG_DUMP_GTID`](https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_binlog_dump_gtid.html)
+#[derive(Debug)]
+pub struct ComBinlogDumpGtid<'a> {
+    flags: u16,
+    server_id: u32,
+    file: &'a str,
+    position: u64,
+    gtid_set: &'a [u8],
+}
+
+impl<'a> ComBinlogDumpGtid<'a> {
+    pub fn new(
+        server_id: u32,
+        file: &'a str,
+        position: u64,
+        flags: u16,
+        gtid_set: &'a [u8],
+    ) -> Self {
+        Self {
+            flags: flags | BINLOG_THROUGH_GTID,
+            server_id,
+            file,
+            position,
+            gtid_set,
+        }
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComBinlogDumpGtid<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(27 + self.file.len() + self.gtid_set.len());
+        bytes.put_u8(0x1E);
+        bytes.put_u16_le(self.flags);
+        bytes.put_u32_le(self.server_id);
+        bytes.put_u32_le(self.file.len() as u32);
+        bytes.put_slice(self.file.as_bytes());
+        bytes.put_u64_le(self.position);
+        bytes.put_u32_le(self.gtid_set.len() as u32);
+        bytes.put_slice(self.gtid_set);
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,29 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+#[derive(Debug)]
+pub struct ComInitDb<'a> {
+    schema: &'a str,
+}
+
+impl<'a> ComInitDb<'a> {
+    pub fn new(schema: &'a str) -> Self {
+        Self { schema }
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComInitDb<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(1 + self.schema.len());
+        bytes.put_u8(0x02);
+        bytes.put_slice(self.schema.as_bytes());
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -1,3 +1,33 @@
+mod binlog_dump;
+mod binlog_dump_gtid;
+mod init_db;
+mod multi;
 mod ping;
+mod process_kill;
+mod query;
+mod quit;
+mod register_slave;
+mod reset_connection;
+mod set_option;
+mod statistics;
+mod stmt_close;
+mod stmt_execute;
+mod stmt_fetch;
+mod stmt_prepare;
 
+pub use binlog_dump::{ComBinlogDump, BINLOG_DUMP_NON_BLOCK};
+pub use binlog_dump_gtid::{ComBinlogDumpGtid, BINLOG_THROUGH_GTID};
+pub use init_db::ComInitDb;
+pub use multi::ComMulti;
 pub use ping::ComPing;
+pub use process_kill::ComProcessKill;
+pub use query::ComQuery;
+pub use quit::ComQuit;
+pub use register_slave::ComRegisterSlave;
+pub use reset_connection::ComResetConnection;
+pub use set_option::{ComSetOption, SetOption};
+pub use statistics::ComStatistics;
+pub use stmt_close::ComStmtClose;
+pub use stmt_execute::{ComStmtExecute, CursorType};
+pub use stmt_fetch::ComStmtFetch;
+pub use stmt_prepare::ComStmtPrepare;
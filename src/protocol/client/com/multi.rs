@@ -0,0 +1,56 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, BufMutExt, EncodePacket};
+
+/// Wraps several encoded commands into a single MariaDB
+/// [`COM_MULTI`](https://mariadb.com/kb/en/com_multi/) packet. The server answers each
+/// command in order, continuing the sequence of the packet.
+#[derive(Debug)]
+pub struct ComMulti {
+    commands: Vec<PacketFrame>,
+}
+
+impl ComMulti {
+    pub fn new(commands: Vec<PacketFrame>) -> Self {
+        Self { commands }
+    }
+}
+
+impl EncodePacket<PacketFrame> for ComMulti {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let len = self.commands.iter().map(|c| 9 + c.len()).sum::<usize>();
+        let mut bytes = BytesMut::with_capacity(1 + len);
+        bytes.put_u8(0xFE);
+        for command in self.commands {
+            bytes.put_len_encoded_int(command.len() as u64);
+            bytes.put_slice(command.as_bytes());
+        }
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::client::com::{ComPing, ComQuery};
+
+    #[test]
+    fn multi_packet_should_prefix_commands_with_their_length() {
+        let context = Context::default();
+        let commands = vec![
+            ComPing::new().encode_packet(&context).unwrap(),
+            ComQuery::new("DO 1").encode_packet(&context).unwrap(),
+        ];
+        let packet = ComMulti::new(commands).encode_packet(&context).unwrap();
+        assert_eq!(
+            packet.as_bytes(),
+            [0xFE, 1, 0x0E, 5, 0x03, b'D', b'O', b' ', b'1']
+        );
+    }
+}
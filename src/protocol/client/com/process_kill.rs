@@ -0,0 +1,29 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+#[derive(Debug)]
+pub struct ComProcessKill {
+    connection_id: u32,
+}
+
+impl ComProcessKill {
+    pub fn new(connection_id: u32) -> Self {
+        Self { connection_id }
+    }
+}
+
+impl EncodePacket<PacketFrame> for ComProcessKill {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(5);
+        bytes.put_u8(0x0C);
+        bytes.put_u32_le(self.connection_id);
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,29 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+#[derive(Debug)]
+pub struct ComQuery<'a> {
+    query: &'a str,
+}
+
+impl<'a> ComQuery<'a> {
+    pub fn new(query: &'a str) -> Self {
+        Self { query }
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComQuery<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(1 + self.query.len());
+        bytes.put_u8(0x03);
+        bytes.put_slice(self.query.as_bytes());
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,48 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+/// Registers the connection as a replica with
+/// [`COM_REGISTER_SLAVE`](https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_register_slave.html)
+#[derive(Debug)]
+pub struct ComRegisterSlave<'a> {
+    server_id: u32,
+    hostname: &'a str,
+    port: u16,
+}
+
+impl<'a> ComRegisterSlave<'a> {
+    pub fn new(server_id: u32, hostname: &'a str, port: u16) -> Self {
+        Self {
+            server_id,
+            hostname,
+            port,
+        }
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComRegisterSlave<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let hostname = &self.hostname.as_bytes()[..self.hostname.len().min(u8::MAX as usize)];
+        let mut bytes = BytesMut::with_capacity(18 + hostname.len());
+        bytes.put_u8(0x15);
+        bytes.put_u32_le(self.server_id);
+        bytes.put_u8(hostname.len() as u8);
+        bytes.put_slice(hostname);
+        // user and password reported in SHOW REPLICAS
+        bytes.put_u8(0);
+        bytes.put_u8(0);
+        bytes.put_u16_le(self.port);
+        // replication rank, ignored
+        bytes.put_u32_le(0);
+        // master id, filled in by the server
+        bytes.put_u32_le(0);
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
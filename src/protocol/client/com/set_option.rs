@@ -0,0 +1,36 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum SetOption {
+    MultiStatementsOn = 0,
+    MultiStatementsOff = 1,
+}
+
+#[derive(Debug)]
+pub struct ComSetOption {
+    option: SetOption,
+}
+
+impl ComSetOption {
+    pub fn new(option: SetOption) -> Self {
+        Self { option }
+    }
+}
+
+impl EncodePacket<PacketFrame> for ComSetOption {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(3);
+        bytes.put_u8(0x1B);
+        bytes.put_u16_le(self.option as u16);
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,31 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+/// Deallocates a prepared statement with
+/// [`COM_STMT_CLOSE`](https://mariadb.com/kb/en/com_stmt_close/). The server does not respond.
+#[derive(Debug)]
+pub struct ComStmtClose {
+    statement_id: u32,
+}
+
+impl ComStmtClose {
+    pub fn new(statement_id: u32) -> Self {
+        Self { statement_id }
+    }
+}
+
+impl EncodePacket<PacketFrame> for ComStmtClose {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(5);
+        bytes.put_u8(0x19);
+        bytes.put_u32_le(self.statement_id);
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,116 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    codec::PacketFrame, context::Context, protocol::ColumnType, value::Value, BufMutExt,
+    EncodePacket,
+};
+
+/// How the server returns the rows of an executed statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CursorType {
+    /// The rows follow the column definitions
+    NoCursor = 0,
+    /// The rows are kept in a cursor and fetched with `COM_STMT_FETCH`
+    ReadOnly = 1,
+}
+
+/// Executes a prepared statement with its parameters in the binary protocol with
+/// [`COM_STMT_EXECUTE`](https://mariadb.com/kb/en/com_stmt_execute/)
+#[derive(Debug)]
+pub struct ComStmtExecute<'a> {
+    statement_id: u32,
+    cursor: CursorType,
+    params: &'a [Value],
+}
+
+impl<'a> ComStmtExecute<'a> {
+    pub fn new(statement_id: u32, cursor: CursorType, params: &'a [Value]) -> Self {
+        Self {
+            statement_id,
+            cursor,
+            params,
+        }
+    }
+}
+
+/// The binary type of a parameter and whether it is unsigned
+fn param_type(value: &Value) -> (ColumnType, bool) {
+    match value {
+        Value::Null => (ColumnType::Null, false),
+        Value::Bytes(_) => (ColumnType::VarString, false),
+        Value::Int(_) => (ColumnType::LongLong, false),
+        Value::UInt(_) => (ColumnType::LongLong, true),
+        Value::Float(_) => (ColumnType::Float, false),
+        Value::Double(_) => (ColumnType::Double, false),
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComStmtExecute<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(64);
+        bytes.put_u8(0x17);
+        bytes.put_u32_le(self.statement_id);
+        bytes.put_u8(self.cursor as u8);
+        // The iteration count is always 1
+        bytes.put_u32_le(1);
+
+        if !self.params.is_empty() {
+            let mut nulls = vec![0u8; self.params.len().div_ceil(8)];
+            for (i, param) in self.params.iter().enumerate() {
+                if param.is_null() {
+                    nulls[i / 8] |= 1 << (i % 8);
+                }
+            }
+            bytes.put_slice(&nulls);
+
+            // The types are sent with every execution
+            bytes.put_u8(1);
+            for param in self.params {
+                let (column_type, unsigned) = param_type(param);
+                bytes.put_u8(column_type as u8);
+                bytes.put_u8(if unsigned { 0x80 } else { 0 });
+            }
+
+            for param in self.params {
+                match param {
+                    Value::Null => {}
+                    Value::Bytes(value) => bytes.put_len_encoded_str(value),
+                    Value::Int(value) => bytes.put_i64_le(*value),
+                    Value::UInt(value) => bytes.put_u64_le(*value),
+                    Value::Float(value) => bytes.put_f32_le(*value),
+                    Value::Double(value) => bytes.put_f64_le(*value),
+                }
+            }
+        }
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn execute_packet_should_bind_params() {
+        let params = [Value::Int(-1), Value::Null, Value::from("ab")];
+        let packet = ComStmtExecute::new(7, CursorType::ReadOnly, &params)
+            .encode_packet(&Context::default())
+            .unwrap();
+        assert_eq!(
+            packet.as_bytes(),
+            [
+                0x17, 7, 0, 0, 0, 1, 1, 0, 0, 0, // header
+                0b010, 1, // null bitmap and new params bound
+                8, 0, 6, 0, 253, 0, // types
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 2, b'a', b'b',
+            ]
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+/// Fetches rows from the cursor of an executed statement with
+/// [`COM_STMT_FETCH`](https://mariadb.com/kb/en/com_stmt_fetch/)
+#[derive(Debug)]
+pub struct ComStmtFetch {
+    statement_id: u32,
+    rows: u32,
+}
+
+impl ComStmtFetch {
+    pub fn new(statement_id: u32, rows: u32) -> Self {
+        Self { statement_id, rows }
+    }
+}
+
+impl EncodePacket<PacketFrame> for ComStmtFetch {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(9);
+        bytes.put_u8(0x1C);
+        bytes.put_u32_le(self.statement_id);
+        bytes.put_u32_le(self.rows);
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
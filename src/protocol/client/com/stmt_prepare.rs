@@ -0,0 +1,31 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{codec::PacketFrame, context::Context, EncodePacket};
+
+/// Prepares a statement with
+/// [`COM_STMT_PREPARE`](https://mariadb.com/kb/en/com_stmt_prepare/)
+#[derive(Debug)]
+pub struct ComStmtPrepare<'a> {
+    query: &'a str,
+}
+
+impl<'a> ComStmtPrepare<'a> {
+    pub fn new(query: &'a str) -> Self {
+        Self { query }
+    }
+}
+
+impl<'a> EncodePacket<PacketFrame> for ComStmtPrepare<'a> {
+    type Error = std::io::Error;
+
+    fn encode_packet(self, _context: &Context) -> Result<PacketFrame, Self::Error> {
+        let mut bytes = BytesMut::with_capacity(1 + self.query.len());
+        bytes.put_u8(0x16);
+        bytes.put_slice(self.query.as_bytes());
+        Ok(PacketFrame::new(bytes.freeze()))
+    }
+
+    fn is_command_packet(&self) -> bool {
+        true
+    }
+}
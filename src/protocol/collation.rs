@@ -0,0 +1,73 @@
+//! Lookups between the collation ids sent in the handshake and their names
+
+/// Known collations as `(id, name, charset, is the charset default)`
+const COLLATIONS: &[(u8, &str, &str, bool)] = &[
+    (8, "latin1_swedish_ci", "latin1", true),
+    (11, "ascii_general_ci", "ascii", true),
+    (28, "gbk_chinese_ci", "gbk", true),
+    (33, "utf8mb3_general_ci", "utf8mb3", true),
+    (45, "utf8mb4_general_ci", "utf8mb4", true),
+    (46, "utf8mb4_bin", "utf8mb4", false),
+    (47, "latin1_bin", "latin1", false),
+    (63, "binary", "binary", true),
+    (83, "utf8mb3_bin", "utf8mb3", false),
+    (192, "utf8mb3_unicode_ci", "utf8mb3", false),
+    (224, "utf8mb4_unicode_ci", "utf8mb4", false),
+    (255, "utf8mb4_0900_ai_ci", "utf8mb4", false),
+];
+
+/// The id of a collation from its name, e.g. `utf8mb4_unicode_ci`
+pub fn by_name(name: &str) -> Option<u8> {
+    let name = name.to_ascii_lowercase();
+    let name = name.replace("utf8_", "utf8mb3_");
+    COLLATIONS
+        .iter()
+        .find(|(_, n, _, _)| *n == name)
+        .map(|(id, ..)| *id)
+}
+
+/// The id of the default collation of a character set, e.g. `utf8mb4`
+pub fn default_for_charset(charset: &str) -> Option<u8> {
+    let charset = charset.to_ascii_lowercase();
+    let charset = if charset == "utf8" {
+        "utf8mb3"
+    } else {
+        &charset
+    };
+    COLLATIONS
+        .iter()
+        .find(|(_, _, c, default)| *default && *c == charset)
+        .map(|(id, ..)| *id)
+}
+
+/// The name of a collation from its id
+pub fn name(id: u8) -> Option<&'static str> {
+    COLLATIONS
+        .iter()
+        .find(|(i, ..)| *i == id)
+        .map(|(_, name, ..)| *name)
+}
+
+/// The character set of a collation from its id
+pub fn charset(id: u8) -> Option<&'static str> {
+    COLLATIONS
+        .iter()
+        .find(|(i, ..)| *i == id)
+        .map(|(_, _, charset, _)| *charset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collations_should_be_looked_up() {
+        assert_eq!(by_name("utf8mb4_unicode_ci"), Some(224));
+        assert_eq!(by_name("utf8_general_ci"), Some(33));
+        assert_eq!(default_for_charset("utf8mb4"), Some(45));
+        assert_eq!(default_for_charset("UTF8"), Some(33));
+        assert_eq!(name(63), Some("binary"));
+        assert_eq!(charset(255), Some("utf8mb4"));
+        assert_eq!(default_for_charset("klingon"), None);
+    }
+}
@@ -0,0 +1,214 @@
+use bytes::{Buf, Bytes};
+
+use crate::{codec::PacketFrame, context::Context, protocol::Capability, BytesExt, DecodePacket};
+
+/// [Field types](https://mariadb.com/kb/en/result-set-packets/#field-types)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ColumnType {
+    Decimal = 0,
+    Tiny = 1,
+    Short = 2,
+    Long = 3,
+    Float = 4,
+    Double = 5,
+    Null = 6,
+    Timestamp = 7,
+    LongLong = 8,
+    Int24 = 9,
+    Date = 10,
+    Time = 11,
+    DateTime = 12,
+    Year = 13,
+    NewDate = 14,
+    VarChar = 15,
+    Bit = 16,
+    Timestamp2 = 17,
+    DateTime2 = 18,
+    Time2 = 19,
+    TypedArray = 20,
+    Vector = 242,
+    Json = 245,
+    NewDecimal = 246,
+    Enum = 247,
+    Set = 248,
+    TinyBlob = 249,
+    MediumBlob = 250,
+    LongBlob = 251,
+    Blob = 252,
+    VarString = 253,
+    String = 254,
+    Geometry = 255,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("failed to parse the column type from {0}")]
+pub struct ParseColumnTypeError(u8);
+
+impl TryFrom<u8> for ColumnType {
+    type Error = ParseColumnTypeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Decimal,
+            1 => Self::Tiny,
+            2 => Self::Short,
+            3 => Self::Long,
+            4 => Self::Float,
+            5 => Self::Double,
+            6 => Self::Null,
+            7 => Self::Timestamp,
+            8 => Self::LongLong,
+            9 => Self::Int24,
+            10 => Self::Date,
+            11 => Self::Time,
+            12 => Self::DateTime,
+            13 => Self::Year,
+            14 => Self::NewDate,
+            15 => Self::VarChar,
+            16 => Self::Bit,
+            17 => Self::Timestamp2,
+            18 => Self::DateTime2,
+            19 => Self::Time2,
+            20 => Self::TypedArray,
+            242 => Self::Vector,
+            245 => Self::Json,
+            246 => Self::NewDecimal,
+            247 => Self::Enum,
+            248 => Self::Set,
+            249 => Self::TinyBlob,
+            250 => Self::MediumBlob,
+            251 => Self::LongBlob,
+            252 => Self::Blob,
+            253 => Self::VarString,
+            254 => Self::String,
+            255 => Self::Geometry,
+            _ => return Err(ParseColumnTypeError(value)),
+        })
+    }
+}
+
+bitflags::bitflags! {
+    // https://mariadb.com/kb/en/result-set-packets/#field-details-flag
+    #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+    pub struct ColumnFlags: u16 {
+        const NOT_NULL = 1;
+        const PRIMARY_KEY = 2;
+        const UNIQUE_KEY = 4;
+        const MULTIPLE_KEY = 8;
+        const BLOB = 16;
+        const UNSIGNED = 32;
+        const ZEROFILL = 64;
+        const BINARY_COLLATION = 128;
+        const ENUM = 256;
+        const AUTO_INCREMENT = 512;
+        const TIMESTAMP = 1024;
+        const SET = 2048;
+        const NO_DEFAULT_VALUE = 4096;
+        const ON_UPDATE_NOW = 8192;
+        const NUM = 32768;
+    }
+}
+
+/// The [column definition packet](https://mariadb.com/kb/en/result-set-packets/#column-definition-packet)
+/// that precedes the rows of a result set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDefinition {
+    pub schema: String,
+    pub table_alias: String,
+    pub table: String,
+    pub name: String,
+    pub org_name: String,
+    pub collation: u16,
+    pub length: u32,
+    pub column_type: ColumnType,
+    pub flags: ColumnFlags,
+    pub decimals: u8,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ColumnDefinitionError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    ColumnType(#[from] ParseColumnTypeError),
+}
+
+fn lossy(bytes: Bytes) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl DecodePacket for ColumnDefinition {
+    type Error = ColumnDefinitionError;
+
+    fn decode_packet(packet: PacketFrame, context: &Context) -> Result<Self, Self::Error> {
+        let mut payload = packet.take_buffer();
+        let _catalog = payload.get_len_encoded_bytes()?;
+        let schema = lossy(payload.get_len_encoded_bytes()?);
+        let table_alias = lossy(payload.get_len_encoded_bytes()?);
+        let table = lossy(payload.get_len_encoded_bytes()?);
+        let name = lossy(payload.get_len_encoded_bytes()?);
+        let org_name = lossy(payload.get_len_encoded_bytes()?);
+
+        if context.has_client_capability(Capability::EXTENDED_METADATA)
+            && context.has_server_capability(Capability::EXTENDED_METADATA)
+        {
+            let _extended = payload.get_len_encoded_bytes()?;
+        }
+
+        let _fixed_len = payload.get_len_encoded_int()?;
+        if payload.len() < 10 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let collation = payload.get_u16_le();
+        let length = payload.get_u32_le();
+        let column_type = ColumnType::try_from(payload.get_u8())?;
+        let flags = ColumnFlags::from_bits_retain(payload.get_u16_le());
+        let decimals = payload.get_u8();
+
+        Ok(Self {
+            schema,
+            table_alias,
+            table,
+            name,
+            org_name,
+            collation,
+            length,
+            column_type,
+            flags,
+            decimals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+
+    use super::*;
+    use crate::BufMutExt;
+
+    #[test]
+    fn column_definition_should_be_decoded() {
+        let mut bytes = BytesMut::new();
+        for part in ["def", "app", "u", "users", "id", "id"] {
+            bytes.put_len_encoded_str(part);
+        }
+        bytes.put_len_encoded_int(0x0c);
+        bytes.put_u16_le(63);
+        bytes.put_u32_le(20);
+        bytes.put_u8(ColumnType::LongLong as u8);
+        bytes.put_u16_le((ColumnFlags::NOT_NULL | ColumnFlags::UNSIGNED).bits());
+        bytes.put_u8(0);
+        bytes.put_u16_le(0);
+
+        let packet = PacketFrame::new(bytes.freeze());
+        let column = ColumnDefinition::decode_packet(packet, &Context::default()).unwrap();
+        assert_eq!(column.schema, "app");
+        assert_eq!(column.table, "users");
+        assert_eq!(column.name, "id");
+        assert_eq!(column.column_type, ColumnType::LongLong);
+        assert!(column.flags.contains(ColumnFlags::UNSIGNED));
+    }
+}
@@ -1,5 +1,7 @@
 mod capability;
 pub mod client;
+pub mod collation;
+mod column;
 pub mod plugin;
 pub mod server;
 mod state;
@@ -7,12 +9,14 @@ mod status;
 mod version;
 
 pub mod error {
+    pub use super::column::{ColumnDefinitionError, ParseColumnTypeError};
     pub use super::plugin::PluginParseError;
     pub use super::state::ParseStateChangeError;
     pub use super::version::ParseVersionError;
 }
 
 pub use capability::Capability;
+pub use column::{ColumnDefinition, ColumnFlags, ColumnType};
 pub use state::ConnectionState;
 pub use state::StateChange;
 pub use status::ServerStatus;
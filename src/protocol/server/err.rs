@@ -0,0 +1,71 @@
+use bytes::Buf;
+
+use crate::{codec::PacketFrame, context::Context, DecodePacket};
+
+/// The [ERR packet](https://mariadb.com/kb/en/err_packet/) sent by the server when a command
+/// fails.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("server error {code} ({sql_state}): {message}")]
+pub struct ErrPacket {
+    pub code: u16,
+    pub sql_state: String,
+    pub message: String,
+}
+
+impl ErrPacket {
+    pub const HEADER: u8 = 0xFF;
+
+    /// `ER_QUERY_INTERRUPTED`, sent when a statement is stopped by `KILL QUERY`
+    pub const QUERY_INTERRUPTED: u16 = 1317;
+
+    pub fn is_query_interrupted(&self) -> bool {
+        self.code == Self::QUERY_INTERRUPTED
+    }
+}
+
+impl DecodePacket for ErrPacket {
+    type Error = std::io::Error;
+
+    fn decode_packet(packet: PacketFrame, _context: &Context) -> Result<Self, Self::Error> {
+        let mut payload = packet.take_buffer();
+        if payload.len() < 3 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        payload.advance(1);
+        let code = payload.get_u16_le();
+
+        let sql_state = if payload.first() == Some(&b'#') && payload.len() >= 6 {
+            payload.advance(1);
+            let state = payload.split_to(5);
+            String::from_utf8_lossy(&state).into_owned()
+        } else {
+            String::from("HY000")
+        };
+
+        let message = String::from_utf8_lossy(&payload).into_owned();
+
+        Ok(Self {
+            code,
+            sql_state,
+            message,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn err_packet_should_be_decoded() {
+        let packet = PacketFrame::new(Bytes::from_static(
+            b"\xff\x19\x04#42000Unknown database 'nope'",
+        ));
+        let err = ErrPacket::decode_packet(packet, &Context::default()).unwrap();
+        assert_eq!(err.code, 1049);
+        assert_eq!(err.sql_state, "42000");
+        assert_eq!(err.message, "Unknown database 'nope'");
+    }
+}
@@ -1,7 +1,18 @@
+mod err;
 mod handshake;
+mod ok;
+mod progress;
+mod statistics;
+mod stmt_prepare_ok;
 
+pub use err::ErrPacket;
 pub use handshake::InitialHanshakePacket;
+pub use ok::OkPacket;
+pub use progress::Progress;
+pub use statistics::Statistics;
+pub use stmt_prepare_ok::StmtPrepareOk;
 
 pub mod error {
     pub use super::handshake::InitialHandshakeError;
+    pub use super::statistics::ParseStatisticsError;
 }
@@ -0,0 +1,126 @@
+use bytes::Buf;
+
+use crate::{
+    codec::PacketFrame,
+    context::Context,
+    protocol::{Capability, ServerStatus, StateChange},
+    BytesExt, DecodePacket,
+};
+
+/// The [OK packet](https://mariadb.com/kb/en/ok_packet/) sent by the server when a command
+/// succeeds. EOF packets are decoded into the same structure since they carry the same
+/// warnings and status flags.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OkPacket {
+    pub affected_rows: u64,
+    pub last_insert_id: u64,
+    pub status_flags: ServerStatus,
+    pub warnings: u16,
+    pub info: String,
+    pub schema: Option<String>,
+}
+
+impl OkPacket {
+    pub const HEADER: u8 = 0x00;
+    pub const EOF_HEADER: u8 = 0xFE;
+
+    /// Whether the packet is an EOF packet rather than an OK packet with a 0xFE header
+    pub fn is_eof(packet: &PacketFrame) -> bool {
+        packet.header() == Some(Self::EOF_HEADER) && packet.len() < 9
+    }
+}
+
+impl DecodePacket for OkPacket {
+    type Error = std::io::Error;
+
+    fn decode_packet(packet: PacketFrame, context: &Context) -> Result<Self, Self::Error> {
+        let is_eof = Self::is_eof(&packet);
+        let mut payload = packet.take_buffer();
+        payload.advance(1);
+
+        if is_eof {
+            if payload.len() < 4 {
+                return Ok(Self::default());
+            }
+            let warnings = payload.get_u16_le();
+            let status_flags = ServerStatus::from_bits_truncate(payload.get_u16_le());
+            return Ok(Self {
+                warnings,
+                status_flags,
+                ..Default::default()
+            });
+        }
+
+        let affected_rows = payload.get_len_encoded_int()?;
+        let last_insert_id = payload.get_len_encoded_int()?;
+
+        let mut ok = Self {
+            affected_rows,
+            last_insert_id,
+            ..Default::default()
+        };
+
+        if payload.len() >= 4 {
+            ok.status_flags = ServerStatus::from_bits_truncate(payload.get_u16_le());
+            ok.warnings = payload.get_u16_le();
+        }
+
+        let session_track = context.has_server_capability(Capability::CLIENT_SESSION_TRACK)
+            && context.has_client_capability(Capability::CLIENT_SESSION_TRACK);
+
+        if !session_track {
+            ok.info = String::from_utf8_lossy(&payload).into_owned();
+            return Ok(ok);
+        }
+
+        if payload.has_remaining() {
+            let info = payload.get_len_encoded_bytes()?;
+            ok.info = String::from_utf8_lossy(&info).into_owned();
+        }
+
+        if ok
+            .status_flags
+            .contains(ServerStatus::SESSION_STATE_CHANGED)
+            && payload.has_remaining()
+        {
+            let mut changes = payload.get_len_encoded_bytes()?;
+            while changes.has_remaining() {
+                let kind = changes.get_u8();
+                let mut data = changes.get_len_encoded_bytes()?;
+                if kind == u8::from(StateChange::Schema) {
+                    let schema = data.get_len_encoded_bytes()?;
+                    ok.schema = Some(String::from_utf8_lossy(&schema).into_owned());
+                }
+            }
+        }
+
+        Ok(ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn ok_packet_should_be_decoded() {
+        let packet = PacketFrame::new(Bytes::from_static(&[
+            0x00, 0x01, 0x02, 0x02, 0x00, 0x01, 0x00,
+        ]));
+        let ok = OkPacket::decode_packet(packet, &Context::default()).unwrap();
+        assert_eq!(ok.affected_rows, 1);
+        assert_eq!(ok.last_insert_id, 2);
+        assert_eq!(ok.status_flags, ServerStatus::AUTOCOMMIT);
+        assert_eq!(ok.warnings, 1);
+    }
+
+    #[test]
+    fn eof_packet_should_be_decoded() {
+        let packet = PacketFrame::new(Bytes::from_static(&[0xFE, 0x00, 0x00, 0x02, 0x00]));
+        assert!(OkPacket::is_eof(&packet));
+        let ok = OkPacket::decode_packet(packet, &Context::default()).unwrap();
+        assert_eq!(ok.status_flags, ServerStatus::AUTOCOMMIT);
+    }
+}
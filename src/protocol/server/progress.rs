@@ -0,0 +1,70 @@
+use bytes::Buf;
+
+use crate::{codec::PacketFrame, context::Context, BytesExt, DecodePacket};
+
+/// A [progress report](https://mariadb.com/kb/en/progress-reporting/) sent by MariaDB during
+/// long statements like `ALTER TABLE` and `LOAD DATA`, as an ERR packet with the code `0xFFFF`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// The current stage, starting at 1
+    pub stage: u8,
+    pub max_stage: u8,
+    /// The progress of the current stage in percent
+    pub progress: f64,
+    pub stage_name: String,
+}
+
+impl Progress {
+    pub const CODE: u16 = 0xFFFF;
+
+    pub fn is_progress(packet: &PacketFrame) -> bool {
+        let payload = packet.as_bytes();
+        payload.len() >= 3 && payload[0] == 0xFF && payload[1..3] == Self::CODE.to_le_bytes()
+    }
+}
+
+impl DecodePacket for Progress {
+    type Error = std::io::Error;
+
+    fn decode_packet(packet: PacketFrame, _context: &Context) -> Result<Self, Self::Error> {
+        let mut payload = packet.take_buffer();
+        if payload.len() < 9 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        // The header, the code and the number of strings
+        payload.advance(4);
+        let stage = payload.get_u8();
+        let max_stage = payload.get_u8();
+        let progress = payload.get_uint_le(3) as f64 / 1000.0;
+        let stage_name = payload.get_len_encoded_bytes()?;
+
+        Ok(Self {
+            stage,
+            max_stage,
+            progress,
+            stage_name: String::from_utf8_lossy(&stage_name).into_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn progress_packet_should_be_decoded() {
+        let packet = PacketFrame::new(Bytes::from_static(
+            b"\xff\xff\xff\x01\x01\x02\x50\xc3\x00\x0bcopy to tmp",
+        ));
+        assert!(Progress::is_progress(&packet));
+        let progress = Progress::decode_packet(packet, &Context::default()).unwrap();
+        assert_eq!((progress.stage, progress.max_stage), (1, 2));
+        assert_eq!(progress.progress, 50.0);
+        assert_eq!(progress.stage_name, "copy to tmp");
+
+        let err = PacketFrame::new(Bytes::from_static(b"\xff\x19\x04#42000"));
+        assert!(!Progress::is_progress(&err));
+    }
+}
@@ -0,0 +1,91 @@
+use std::{num::ParseFloatError, num::ParseIntError, str::FromStr};
+
+/// The human readable status string returned by `COM_STATISTICS`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Statistics {
+    pub uptime: u64,
+    pub threads: u64,
+    pub questions: u64,
+    pub slow_queries: u64,
+    pub opens: u64,
+    pub flush_tables: u64,
+    pub open_tables: u64,
+    pub queries_per_second_avg: f64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseStatisticsError {
+    #[error("could not parse the statistic {0} as an integer")]
+    ParseInt(String, #[source] ParseIntError),
+
+    #[error("could not parse the statistic {0} as a float")]
+    ParseFloat(String, #[source] ParseFloatError),
+
+    #[error("the statistic {0} is missing a value")]
+    MissingValue(String),
+}
+
+impl FromStr for Statistics {
+    type Err = ParseStatisticsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut statistics = Statistics::default();
+
+        // Entries are separated by two spaces, e.g. `Uptime: 14  Threads: 1  Questions: 3`
+        for entry in s.split("  ").map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, value) = entry
+                .split_once(':')
+                .ok_or_else(|| ParseStatisticsError::MissingValue(entry.into()))?;
+            let value = value.trim();
+
+            let parse_int = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .map_err(|e| ParseStatisticsError::ParseInt(name.into(), e))
+            };
+
+            match name {
+                "Uptime" => statistics.uptime = parse_int(value)?,
+                "Threads" => statistics.threads = parse_int(value)?,
+                "Questions" => statistics.questions = parse_int(value)?,
+                "Slow queries" => statistics.slow_queries = parse_int(value)?,
+                "Opens" => statistics.opens = parse_int(value)?,
+                "Flush tables" => statistics.flush_tables = parse_int(value)?,
+                "Open tables" => statistics.open_tables = parse_int(value)?,
+                "Queries per second avg" => {
+                    statistics.queries_per_second_avg = value
+                        .parse()
+                        .map_err(|e| ParseStatisticsError::ParseFloat(name.into(), e))?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(statistics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statistics_should_be_parsed() {
+        let input = "Uptime: 14  Threads: 1  Questions: 3  Slow queries: 0  Opens: 17  Flush tables: 3  Open tables: 10  Queries per second avg: 0.214";
+        let statistics = input.parse::<Statistics>().unwrap();
+        assert_eq!(statistics.uptime, 14);
+        assert_eq!(statistics.threads, 1);
+        assert_eq!(statistics.questions, 3);
+        assert_eq!(statistics.slow_queries, 0);
+        assert_eq!(statistics.opens, 17);
+        assert_eq!(statistics.flush_tables, 3);
+        assert_eq!(statistics.open_tables, 10);
+        assert_eq!(statistics.queries_per_second_avg, 0.214);
+    }
+
+    #[test]
+    fn invalid_statistics_should_fail() {
+        let input = "Uptime: abc  Threads: 1";
+        assert!(input.parse::<Statistics>().is_err());
+    }
+}
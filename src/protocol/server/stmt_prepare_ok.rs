@@ -0,0 +1,37 @@
+use bytes::Buf;
+
+use crate::{codec::PacketFrame, context::Context, DecodePacket};
+
+/// The [response](https://mariadb.com/kb/en/com_stmt_prepare/#com_stmt_prepare_ok) to a
+/// successful `COM_STMT_PREPARE`, followed by the parameter and column definitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StmtPrepareOk {
+    pub statement_id: u32,
+    pub num_columns: u16,
+    pub num_params: u16,
+    pub warnings: u16,
+}
+
+impl DecodePacket for StmtPrepareOk {
+    type Error = std::io::Error;
+
+    fn decode_packet(packet: PacketFrame, _context: &Context) -> Result<Self, Self::Error> {
+        let mut payload = packet.take_buffer();
+        if payload.len() < 12 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        payload.advance(1);
+        let statement_id = payload.get_u32_le();
+        let num_columns = payload.get_u16_le();
+        let num_params = payload.get_u16_le();
+        payload.advance(1);
+        let warnings = payload.get_u16_le();
+
+        Ok(Self {
+            statement_id,
+            num_columns,
+            num_params,
+            warnings,
+        })
+    }
+}
@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use bytes::Buf;
+
+use crate::{
+    codec::PacketFrame,
+    protocol::{server::OkPacket, ColumnDefinition, ColumnFlags, ColumnType},
+    value::{format_date_time, format_time, Value},
+    BytesExt,
+};
+
+/// The marker of a `NULL` value in a text protocol row
+const NULL_VALUE: u8 = 0xFB;
+
+/// A row of a result set
+#[derive(Debug, Clone)]
+pub struct Row {
+    columns: Arc<[ColumnDefinition]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Decodes a [text protocol row](https://mariadb.com/kb/en/resultset-row/#text-resultset-row)
+    pub(crate) fn decode_text(
+        packet: PacketFrame,
+        columns: Arc<[ColumnDefinition]>,
+    ) -> Result<Self, std::io::Error> {
+        let mut payload = packet.take_buffer();
+        let mut values = Vec::with_capacity(columns.len());
+        for _ in 0..columns.len() {
+            if payload.first() == Some(&NULL_VALUE) {
+                payload.advance(1);
+                values.push(Value::Null);
+            } else {
+                values.push(Value::Bytes(payload.get_len_encoded_bytes()?));
+            }
+        }
+        Ok(Self { columns, values })
+    }
+
+    /// Decodes a [binary protocol row](https://mariadb.com/kb/en/resultset-row/#binary-resultset-row)
+    /// of a prepared statement. Temporal values are formatted as text like in the text protocol.
+    pub(crate) fn decode_binary(
+        packet: PacketFrame,
+        columns: Arc<[ColumnDefinition]>,
+    ) -> Result<Self, std::io::Error> {
+        let mut payload = packet.take_buffer();
+        // The header and the null bitmap, whose first two bits are reserved
+        let bitmap_len = (columns.len() + 7 + 2) / 8;
+        if payload.len() < 1 + bitmap_len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+        payload.advance(1);
+        let nulls = payload.split_to(bitmap_len);
+
+        let mut values = Vec::with_capacity(columns.len());
+        for (i, column) in columns.iter().enumerate() {
+            let bit = i + 2;
+            if nulls[bit / 8] & (1 << (bit % 8)) != 0 {
+                values.push(Value::Null);
+                continue;
+            }
+            values.push(decode_binary_value(&mut payload, column)?);
+        }
+        Ok(Self { columns, values })
+    }
+
+    pub fn columns(&self) -> &[ColumnDefinition] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)
+    }
+
+    /// Gets a value by its column name
+    pub fn get_by_name(&self, name: &str) -> Option<&Value> {
+        let index = self.columns.iter().position(|c| c.name == name)?;
+        self.values.get(index)
+    }
+}
+
+fn decode_binary_value(
+    payload: &mut bytes::Bytes,
+    column: &ColumnDefinition,
+) -> Result<Value, std::io::Error> {
+    let unsigned = column.flags.contains(ColumnFlags::UNSIGNED);
+    let fixed_len = match column.column_type {
+        ColumnType::Tiny => 1,
+        ColumnType::Short | ColumnType::Year => 2,
+        ColumnType::Long | ColumnType::Int24 | ColumnType::Float => 4,
+        ColumnType::LongLong | ColumnType::Double => 8,
+        ColumnType::Date
+        | ColumnType::NewDate
+        | ColumnType::DateTime
+        | ColumnType::Timestamp
+        | ColumnType::Time => 1 + payload.first().copied().unwrap_or(0) as usize,
+        ColumnType::Null => return Ok(Value::Null),
+        _ => return Ok(Value::Bytes(payload.get_len_encoded_bytes()?)),
+    };
+    if payload.len() < fixed_len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+    }
+
+    let fsp = column.decimals.min(6);
+    let value = match column.column_type {
+        ColumnType::Tiny if unsigned => Value::UInt(payload.get_u8().into()),
+        ColumnType::Tiny => Value::Int(payload.get_i8().into()),
+        ColumnType::Short | ColumnType::Year if unsigned => {
+            Value::UInt(payload.get_u16_le().into())
+        }
+        ColumnType::Short | ColumnType::Year => Value::Int(payload.get_i16_le().into()),
+        ColumnType::Long | ColumnType::Int24 if unsigned => {
+            Value::UInt(payload.get_u32_le().into())
+        }
+        ColumnType::Long | ColumnType::Int24 => Value::Int(payload.get_i32_le().into()),
+        ColumnType::LongLong if unsigned => Value::UInt(payload.get_u64_le()),
+        ColumnType::LongLong => Value::Int(payload.get_i64_le()),
+        ColumnType::Float => Value::Float(payload.get_f32_le()),
+        ColumnType::Double => Value::Double(payload.get_f64_le()),
+        ColumnType::Time => {
+            let len = payload.get_u8();
+            let (mut negative, mut days, mut time, mut micros) = (false, 0, (0, 0, 0), 0);
+            if len >= 8 {
+                negative = payload.get_u8() == 1;
+                days = u64::from(payload.get_u32_le());
+                time = (
+                    u64::from(payload.get_u8()),
+                    u64::from(payload.get_u8()),
+                    u64::from(payload.get_u8()),
+                );
+            }
+            if len >= 12 {
+                micros = payload.get_u32_le();
+            }
+            time.0 += days * 24;
+            Value::Bytes(format_time(negative, time, micros, fsp).into())
+        }
+        _ => {
+            let len = payload.get_u8();
+            let (mut date, mut time, mut micros) = ((0, 0, 0), (0, 0, 0), 0);
+            if len >= 4 {
+                date = (
+                    u64::from(payload.get_u16_le()),
+                    u64::from(payload.get_u8()),
+                    u64::from(payload.get_u8()),
+                );
+            }
+            if len >= 7 {
+                time = (
+                    u64::from(payload.get_u8()),
+                    u64::from(payload.get_u8()),
+                    u64::from(payload.get_u8()),
+                );
+            }
+            if len >= 11 {
+                micros = payload.get_u32_le();
+            }
+            let mut text = format_date_time(date, time, micros, fsp);
+            if matches!(column.column_type, ColumnType::Date | ColumnType::NewDate) {
+                text.truncate(10);
+            }
+            Value::Bytes(text.into())
+        }
+    };
+    Ok(value)
+}
+
+/// A result set returned by a query, or only the OK packet for statements without rows
+#[derive(Debug, Clone, Default)]
+pub struct ResultSet {
+    pub columns: Arc<[ColumnDefinition]>,
+    pub rows: Vec<Row>,
+    pub status: OkPacket,
+}
+
+impl ResultSet {
+    pub fn affected_rows(&self) -> u64 {
+        self.status.affected_rows
+    }
+
+    pub fn last_insert_id(&self) -> u64 {
+        self.status.last_insert_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+
+    use super::*;
+    use crate::BufMutExt;
+
+    fn column(name: &str) -> ColumnDefinition {
+        ColumnDefinition {
+            schema: String::new(),
+            table_alias: String::new(),
+            table: String::new(),
+            name: name.into(),
+            org_name: name.into(),
+            collation: 45,
+            length: 0,
+            column_type: ColumnType::VarString,
+            flags: ColumnFlags::empty(),
+            decimals: 0,
+        }
+    }
+
+    #[test]
+    fn text_row_should_be_decoded() {
+        let columns: Arc<[ColumnDefinition]> = vec![column("a"), column("b")].into();
+        let mut bytes = BytesMut::new();
+        bytes.put_len_encoded_str("42");
+        bytes.put_u8(NULL_VALUE);
+
+        let row = Row::decode_text(PacketFrame::new(bytes.freeze()), columns).unwrap();
+        assert_eq!(row.get(0).and_then(Value::as_u64), Some(42));
+        assert!(row.get_by_name("b").unwrap().is_null());
+    }
+
+    #[test]
+    fn binary_row_should_be_decoded() {
+        let mut id = column("id");
+        id.column_type = ColumnType::LongLong;
+        id.flags = ColumnFlags::UNSIGNED;
+        let mut created = column("created");
+        created.column_type = ColumnType::DateTime;
+        let columns: Arc<[ColumnDefinition]> =
+            vec![id, column("name"), column("note"), created].into();
+
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(0x00);
+        // The third column is null, at bit 2 + 2
+        bytes.put_u8(0b1_0000);
+        bytes.put_u64_le(u64::MAX);
+        bytes.put_len_encoded_str("dibi");
+        bytes.put_slice(&[7, 0xE8, 0x07, 2, 29, 13, 5, 9]);
+
+        let row = Row::decode_binary(PacketFrame::new(bytes.freeze()), columns).unwrap();
+        assert_eq!(row.get(0), Some(&Value::UInt(u64::MAX)));
+        assert_eq!(row.get(1).and_then(Value::as_str), Some("dibi"));
+        assert!(row.get(2).unwrap().is_null());
+        assert_eq!(
+            row.get_by_name("created").and_then(Value::as_str),
+            Some("2024-02-29 13:05:09")
+        );
+    }
+}
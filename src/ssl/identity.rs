@@ -0,0 +1,191 @@
+//! Parsing of the client identity and the root certificates, shared by the TLS backends.
+//! Certificates are read from PEM, possibly with a whole chain or bundle, or from a single DER
+//! certificate. Keys are read in the PKCS#8, PKCS#1 (RSA) or SEC1 (EC) PEM formats.
+
+use std::{fmt, path::PathBuf};
+
+use super::pin::{base64_decode, der_element};
+
+const SEQUENCE: u8 = 0x30;
+
+/// The TLS material an error is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsInput {
+    Certificate,
+    Key,
+    Pkcs12,
+    /// The root certificate bundle at this index
+    Root(usize),
+}
+
+impl fmt::Display for TlsInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Certificate => f.write_str("client certificate"),
+            Self::Key => f.write_str("client key"),
+            Self::Pkcs12 => f.write_str("PKCS#12 bundle"),
+            Self::Root(i) => write!(f, "root certificates #{}", i + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "failed to parse the {input}{}: {reason}",
+    path.as_ref().map_or_else(String::new, |path| format!(" from {}", path.display()))
+)]
+pub struct ParseTlsError {
+    pub input: TlsInput,
+    /// The file the input was read from, if any
+    pub path: Option<PathBuf>,
+    pub reason: String,
+}
+
+impl ParseTlsError {
+    pub(crate) fn new(input: TlsInput, reason: impl fmt::Display) -> Self {
+        Self {
+            input,
+            path: None,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// A DER private key in the format it was read in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PrivateKey {
+    Pkcs8(Vec<u8>),
+    Pkcs1(Vec<u8>),
+    Sec1(Vec<u8>),
+}
+
+/// The certificates of a PEM chain or bundle, or of a single DER certificate
+pub(crate) fn certificates(data: &[u8], input: TlsInput) -> Result<Vec<Vec<u8>>, ParseTlsError> {
+    if is_der(data) {
+        return Ok(vec![data.to_vec()]);
+    }
+    let certificates: Vec<Vec<u8>> = pem_blocks(data)
+        .map_err(|reason| ParseTlsError::new(input, reason))?
+        .into_iter()
+        .filter(|(label, _)| label == "CERTIFICATE")
+        .map(|(_, der)| der)
+        .collect();
+    if certificates.is_empty() {
+        return Err(ParseTlsError::new(input, "no certificate was found"));
+    }
+    Ok(certificates)
+}
+
+/// The first private key of a PEM file, or a DER PKCS#8 key
+pub(crate) fn private_key(data: &[u8]) -> Result<PrivateKey, ParseTlsError> {
+    let err = |reason| ParseTlsError::new(TlsInput::Key, reason);
+    if is_der(data) {
+        return Ok(PrivateKey::Pkcs8(data.to_vec()));
+    }
+    for (label, der) in pem_blocks(data).map_err(err)? {
+        match label.as_str() {
+            "PRIVATE KEY" => return Ok(PrivateKey::Pkcs8(der)),
+            "RSA PRIVATE KEY" => return Ok(PrivateKey::Pkcs1(der)),
+            "EC PRIVATE KEY" => return Ok(PrivateKey::Sec1(der)),
+            "ENCRYPTED PRIVATE KEY" => return Err(err("encrypted keys are not supported")),
+            _ => {}
+        }
+    }
+    Err(err("no private key was found"))
+}
+
+/// The labels and contents of the PEM blocks, skipping the text around them
+fn pem_blocks(pem: &[u8]) -> Result<Vec<(String, Vec<u8>)>, &'static str> {
+    let text = std::str::from_utf8(pem).map_err(|_| "the PEM is not valid UTF-8")?;
+    let mut blocks = Vec::new();
+    let mut lines = text.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some(label) = line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|line| line.strip_suffix("-----"))
+        else {
+            continue;
+        };
+        let end = format!("-----END {}-----", label);
+        let mut encoded = String::new();
+        loop {
+            let line = lines.next().ok_or("a PEM block is not terminated")?;
+            if line == end {
+                break;
+            }
+            // Headers like `Proc-Type: 4,ENCRYPTED` only appear in encrypted blocks
+            if line.contains(':') {
+                return Err("encrypted PEM blocks are not supported");
+            }
+            encoded.push_str(line);
+        }
+        let der = base64_decode(&encoded).ok_or("a PEM block is not valid base64")?;
+        blocks.push((label.to_owned(), der));
+    }
+    Ok(blocks)
+}
+
+/// Whether the data is a single DER structure rather than PEM
+fn is_der(data: &[u8]) -> bool {
+    matches!(der_element(data), Some((SEQUENCE, _, rest)) if rest.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssl::pin::base64_encode;
+
+    #[test]
+    fn certificates_should_be_read_from_chains_and_der() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let params = rcgen::CertificateParams::new(vec!["db.internal".to_owned()]).unwrap();
+        let cert = params.self_signed(&key).unwrap();
+
+        let chain = format!("subject=CN = db\n{}\n{}", cert.pem(), cert.pem());
+        let certificates = certificates(chain.as_bytes(), TlsInput::Root(1)).unwrap();
+        assert_eq!(certificates, vec![cert.der().to_vec(); 2]);
+        assert_eq!(
+            super::certificates(cert.der(), TlsInput::Certificate).unwrap(),
+            vec![cert.der().to_vec()]
+        );
+
+        let err = super::certificates(b"not a certificate", TlsInput::Root(1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to parse the root certificates #2: no certificate was found"
+        );
+    }
+
+    #[test]
+    fn keys_should_be_read_in_every_pem_format() {
+        let pem = |label, der: &[u8]| {
+            format!(
+                "-----BEGIN {0}-----\n{1}\n-----END {0}-----\n",
+                label,
+                base64_encode(der)
+            )
+        };
+        let key = [0x30, 0x00];
+
+        let pkcs1 = pem("RSA PRIVATE KEY", &key);
+        assert_eq!(
+            private_key(pkcs1.as_bytes()).unwrap(),
+            PrivateKey::Pkcs1(key.to_vec())
+        );
+        let sec1 = format!(
+            "{}{}",
+            pem("EC PARAMETERS", &[0x06, 0x00]),
+            pem("EC PRIVATE KEY", &key)
+        );
+        assert_eq!(
+            private_key(sec1.as_bytes()).unwrap(),
+            PrivateKey::Sec1(key.to_vec())
+        );
+        assert_eq!(private_key(&key).unwrap(), PrivateKey::Pkcs8(key.to_vec()));
+
+        let encrypted = pem("ENCRYPTED PRIVATE KEY", &key);
+        let err = private_key(encrypted.as_bytes()).unwrap_err();
+        assert_eq!(err.input, TlsInput::Key);
+        assert_eq!(err.reason, "encrypted keys are not supported");
+    }
+}
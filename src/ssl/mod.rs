@@ -0,0 +1,367 @@
+//! TLS for the connection, with `native-tls` or `rustls` as a backend chosen by cargo
+//! features.
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::stream::StreamType;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("enable the `native-tls` or the `rustls` feature for a tls backend");
+
+mod identity;
+#[cfg(feature = "native-tls")]
+mod native;
+mod pin;
+mod provider;
+#[cfg(feature = "rustls")]
+mod rustls;
+
+pub use identity::{ParseTlsError, TlsInput};
+pub use pin::{ParseTlsPinError, PinMismatch, TlsPin};
+pub use provider::{ReloadError, TlsProvider};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    /// Do not use SSL/TLS.
+    #[default]
+    Disable,
+    /// Use SSL/TLS if the server supports it, but allow a connection without
+    Prefer,
+    /// Use SSL/TLS, fail if the server does not support it.
+    Require,
+    /// Use SSL/TLS, verify that the server certificate is issued by a trusted CA
+    VerifyCa,
+    /// Use SSL/TLS, verify the server certificate CA and matches the server's hostname
+    VerifyFull,
+}
+
+impl TlsMode {
+    /// The name of the mode as used by the `--ssl-mode` option of the mysql client
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Disable => "DISABLED",
+            Self::Prefer => "PREFERRED",
+            Self::Require => "REQUIRED",
+            Self::VerifyCa => "VERIFY_CA",
+            Self::VerifyFull => "VERIFY_IDENTITY",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("failed to parse the tls mode from {0}")]
+pub struct ParseTlsModeError(String);
+
+impl FromStr for TlsMode {
+    type Err = ParseTlsModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().replace('-', "_").as_str() {
+            "DISABLED" | "DISABLE" => Ok(Self::Disable),
+            "PREFERRED" | "PREFER" => Ok(Self::Prefer),
+            "REQUIRED" | "REQUIRE" => Ok(Self::Require),
+            "VERIFY_CA" => Ok(Self::VerifyCa),
+            "VERIFY_IDENTITY" | "VERIFY_FULL" => Ok(Self::VerifyFull),
+            _ => Err(ParseTlsModeError(s.into())),
+        }
+    }
+}
+
+/// The library implementing TLS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsBackend {
+    /// The platform library through `native-tls`, OpenSSL on Linux
+    NativeTls,
+    /// The pure Rust `rustls`, for static builds
+    Rustls,
+}
+
+impl Default for TlsBackend {
+    /// `native-tls` when its feature is enabled, `rustls` otherwise
+    fn default() -> Self {
+        if cfg!(feature = "native-tls") {
+            Self::NativeTls
+        } else {
+            Self::Rustls
+        }
+    }
+}
+
+/// The certificate authorities trusted to verify the server certificate. The `root` of the
+/// [`TlsOptions`] is trusted in addition to them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TlsRoots {
+    /// The certificate store of the operating system
+    #[default]
+    System,
+    /// The Mozilla roots bundled by `webpki-roots`, only with `rustls`
+    WebPki,
+    /// Only the `root` certificate of the options
+    Custom,
+}
+
+#[derive(Debug, Default)]
+pub struct TlsOptions<'a> {
+    pub mode: TlsMode,
+    pub backend: TlsBackend,
+    pub roots: TlsRoots,
+    /// The PEM client certificate, followed by its intermediate certificates
+    pub pem: Option<&'a [u8]>,
+    /// The PEM client key, in the PKCS#8, PKCS#1 (RSA) or SEC1 (EC) format
+    pub key: Option<&'a [u8]>,
+    /// A DER PKCS#12 bundle of the client key and certificates, instead of `pem` and `key`.
+    /// Only supported by `native-tls`.
+    pub pkcs12: Option<&'a [u8]>,
+    pub pkcs12_password: Option<&'a str>,
+    /// PEM bundles of certificate authorities trusted in addition to the `roots`
+    pub root: Vec<&'a [u8]>,
+    /// The name sent with SNI and verified against the server certificate, by default the
+    /// host being dialed
+    pub domain: Option<&'a str>,
+    /// Fingerprints of which the server certificate must match one, replacing the verification
+    /// by certificate authorities
+    pub pins: &'a [TlsPin],
+}
+
+pub trait UpgradeStream: Sized {
+    fn maybe_upgrade_tls(
+        self,
+        parts: Option<(&str, TlsConnector)>,
+    ) -> impl Future<Output = Result<Self, UpgradeError>>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpgradeError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[cfg(feature = "native-tls")]
+    #[error(transparent)]
+    Tls(#[from] tokio_native_tls::native_tls::Error),
+
+    #[cfg(feature = "rustls")]
+    #[error(transparent)]
+    Rustls(#[from] ::rustls::Error),
+
+    #[cfg(feature = "rustls")]
+    #[error("failed to build the certificate verifier")]
+    Verifier(#[from] ::rustls::client::VerifierBuilderError),
+
+    #[error("the {0:?} tls backend is not enabled, see the cargo features")]
+    BackendDisabled(TlsBackend),
+
+    #[error("the {0:?} roots are not supported by the {1:?} tls backend")]
+    UnsupportedRoots(TlsRoots, TlsBackend),
+
+    #[error("{0} is not a valid tls server name")]
+    ServerName(String),
+
+    #[error(transparent)]
+    Parse(#[from] ParseTlsError),
+
+    #[error(transparent)]
+    PinMismatch(#[from] PinMismatch),
+}
+
+/// A connector of the enabled TLS backend, configured from [`TlsOptions`]
+#[derive(Clone)]
+pub enum TlsConnector {
+    /// The connector and the pins checked once connected, as native-tls cannot verify them
+    /// during the handshake
+    #[cfg(feature = "native-tls")]
+    NativeTls(tokio_native_tls::TlsConnector, Vec<TlsPin>),
+    #[cfg(feature = "rustls")]
+    Rustls(tokio_rustls::TlsConnector),
+}
+
+impl fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(..) => f.write_str("TlsConnector::NativeTls"),
+            #[cfg(feature = "rustls")]
+            Self::Rustls(_) => f.write_str("TlsConnector::Rustls"),
+        }
+    }
+}
+
+impl TlsConnector {
+    /// Performs the TLS handshake over `stream`, verifying the server as `domain`
+    pub async fn connect<S>(&self, domain: &str, stream: S) -> Result<TlsStream<S>, UpgradeError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match *self {
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(ref connector, ref pins) => {
+                let stream = connector.connect(domain, stream).await?;
+                if !pins.is_empty() {
+                    let der = match stream.get_ref().peer_certificate()? {
+                        Some(certificate) => certificate.to_der()?,
+                        None => Vec::new(),
+                    };
+                    pin::check(pins, &der)?;
+                }
+                Ok(TlsStream::NativeTls(stream))
+            }
+            #[cfg(feature = "rustls")]
+            Self::Rustls(ref connector) => {
+                let name = rustls::server_name(domain)?;
+                let stream = connector
+                    .connect(name, stream)
+                    .await
+                    .map_err(rustls::handshake_error)?;
+                Ok(TlsStream::Rustls(Box::new(stream)))
+            }
+        }
+    }
+}
+
+/// A stream encrypted by the enabled TLS backend
+#[derive(Debug)]
+pub enum TlsStream<S> {
+    #[cfg(feature = "native-tls")]
+    NativeTls(tokio_native_tls::TlsStream<S>),
+    #[cfg(feature = "rustls")]
+    Rustls(Box<tokio_rustls::client::TlsStream<S>>),
+}
+
+macro_rules! delegate {
+    ($self:ident, $stream:ident => $call:expr) => {
+        match $self.get_mut() {
+            #[cfg(feature = "native-tls")]
+            TlsStream::NativeTls($stream) => $call,
+            #[cfg(feature = "rustls")]
+            TlsStream::Rustls($stream) => $call,
+        }
+    };
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_read(cx, buf))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        delegate!(self, stream => Pin::new(stream).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_shutdown(cx))
+    }
+}
+
+/// The server name of a `host:port` address, or `localhost` for a unix socket. Only SNI uses
+/// it there: [`Config::validate`](crate::config::Config::validate) requires an explicit TLS
+/// domain to verify a server behind a socket.
+pub fn host_name(host: &str, stream_type: StreamType) -> &str {
+    match stream_type {
+        StreamType::Tcp => {}
+        #[cfg(unix)]
+        StreamType::Unix => return "localhost",
+    }
+    if let Some(rest) = host.strip_prefix('[') {
+        // An IPv6 address like [::1]:3306
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.parse::<u16>().is_ok() => name,
+        _ => host,
+    }
+}
+
+/// The negotiated parameters of a TLS session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    pub backend: TlsBackend,
+    /// The protocol version like `TLSv1.3`, unknown with `native-tls`
+    pub version: Option<String>,
+    /// The cipher suite like `TLS13_AES_256_GCM_SHA384`, unknown with `native-tls`
+    pub cipher: Option<String>,
+}
+
+impl<S> TlsStream<S> {
+    pub fn info(&self) -> TlsInfo {
+        match self {
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(_) => TlsInfo {
+                backend: TlsBackend::NativeTls,
+                version: None,
+                cipher: None,
+            },
+            #[cfg(feature = "rustls")]
+            Self::Rustls(stream) => rustls::info(stream.get_ref().1),
+        }
+    }
+}
+
+/// Builds the connector for the options, `None` when TLS is disabled. The connector verifies
+/// the server as `domain`.
+pub async fn into_tls_parts<'a>(
+    ssl_opts: &TlsOptions<'a>,
+    domain: &'a str,
+) -> Result<Option<(&'a str, TlsConnector)>, UpgradeError> {
+    if ssl_opts.mode == TlsMode::Disable {
+        return Ok(None);
+    }
+    Ok(Some((domain, build_connector(ssl_opts)?)))
+}
+
+/// Builds the connector of the backend chosen by the options
+pub(crate) fn build_connector(ssl_opts: &TlsOptions<'_>) -> Result<TlsConnector, UpgradeError> {
+    let connector = match ssl_opts.backend {
+        #[cfg(feature = "native-tls")]
+        TlsBackend::NativeTls => {
+            TlsConnector::NativeTls(native::connector(ssl_opts)?, ssl_opts.pins.to_vec())
+        }
+        #[cfg(feature = "rustls")]
+        TlsBackend::Rustls => TlsConnector::Rustls(rustls::connector(ssl_opts)?),
+        #[allow(unreachable_patterns)]
+        backend => return Err(UpgradeError::BackendDisabled(backend)),
+    };
+    Ok(connector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_name_should_drop_the_port() {
+        assert_eq!(
+            host_name("db.internal:3306", StreamType::Tcp),
+            "db.internal"
+        );
+        assert_eq!(host_name("10.0.0.1", StreamType::Tcp), "10.0.0.1");
+        assert_eq!(host_name("[::1]:3306", StreamType::Tcp), "::1");
+        assert_eq!(host_name("fe80::1", StreamType::Tcp), "fe80::1");
+        #[cfg(unix)]
+        assert_eq!(
+            host_name("/run/mysqld/mysqld.sock", StreamType::Unix),
+            "localhost"
+        );
+    }
+}
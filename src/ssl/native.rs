@@ -0,0 +1,180 @@
+use tokio_native_tls::native_tls;
+
+use super::{
+    identity::{self, ParseTlsError, PrivateKey, TlsInput},
+    pin::{base64_encode, der_element},
+    TlsBackend, TlsMode, TlsOptions, TlsRoots, UpgradeError,
+};
+
+const SEQUENCE: u8 = 0x30;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const EC_PARAMETERS: u8 = 0xA0;
+
+/// The `rsaEncryption` algorithm identifier, with its NULL parameters
+const RSA_ALGORITHM: &[u8] = &[
+    0x06, 0x09, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01, 0x05, 0x00,
+];
+/// The `id-ecPublicKey` algorithm, to be followed by the curve of the key
+const EC_ALGORITHM: &[u8] = &[0x06, 0x07, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+
+pub(super) fn connector(
+    ssl_opts: &TlsOptions<'_>,
+) -> Result<tokio_native_tls::TlsConnector, UpgradeError> {
+    let mut connector = native_tls::TlsConnector::builder();
+
+    // Pins replace the verification and are checked after the handshake
+    let pinned = !ssl_opts.pins.is_empty();
+    connector.danger_accept_invalid_certs(
+        pinned || matches!(ssl_opts.mode, TlsMode::Require | TlsMode::Prefer),
+    );
+
+    connector.danger_accept_invalid_hostnames(
+        pinned
+            || matches!(
+                ssl_opts.mode,
+                TlsMode::Require | TlsMode::Prefer | TlsMode::VerifyCa,
+            ),
+    );
+
+    match ssl_opts.roots {
+        TlsRoots::System => {}
+        TlsRoots::Custom => {
+            connector.disable_built_in_roots(true);
+        }
+        TlsRoots::WebPki => {
+            return Err(UpgradeError::UnsupportedRoots(
+                TlsRoots::WebPki,
+                TlsBackend::NativeTls,
+            ))
+        }
+    }
+
+    if let Some(pkcs12) = ssl_opts.pkcs12 {
+        let password = ssl_opts.pkcs12_password.unwrap_or_default();
+        let identity = native_tls::Identity::from_pkcs12(pkcs12, password)
+            .map_err(|err| ParseTlsError::new(TlsInput::Pkcs12, err))?;
+        connector.identity(identity);
+    } else if let (Some(pem), Some(key)) = (ssl_opts.pem, ssl_opts.key) {
+        connector.identity(identity(pem, key)?);
+    }
+    for (i, bundle) in ssl_opts.root.iter().enumerate() {
+        for der in identity::certificates(bundle, TlsInput::Root(i))? {
+            let ca = native_tls::Certificate::from_der(&der)
+                .map_err(|err| ParseTlsError::new(TlsInput::Root(i), err))?;
+            connector.add_root_certificate(ca);
+        }
+    }
+
+    let connector = connector.build()?;
+    Ok(tokio_native_tls::TlsConnector::from(connector))
+}
+
+/// The identity of a certificate chain and its key, which native-tls only reads as PEM with a
+/// PKCS#8 key
+fn identity(pem: &[u8], key: &[u8]) -> Result<native_tls::Identity, ParseTlsError> {
+    let mut chain = String::new();
+    for der in identity::certificates(pem, TlsInput::Certificate)? {
+        native_tls::Certificate::from_der(&der)
+            .map_err(|err| ParseTlsError::new(TlsInput::Certificate, err))?;
+        chain.push_str(&pem_encode("CERTIFICATE", &der));
+    }
+    let key = pem_encode("PRIVATE KEY", &pkcs8(&identity::private_key(key)?)?);
+    native_tls::Identity::from_pkcs8(chain.as_bytes(), key.as_bytes())
+        .map_err(|err| ParseTlsError::new(TlsInput::Key, err))
+}
+
+/// Wraps a PKCS#1 or SEC1 key in a PKCS#8 structure
+fn pkcs8(key: &PrivateKey) -> Result<Vec<u8>, ParseTlsError> {
+    let (algorithm, key) = match key {
+        PrivateKey::Pkcs8(der) => return Ok(der.clone()),
+        PrivateKey::Pkcs1(der) => (RSA_ALGORITHM.to_vec(), der),
+        PrivateKey::Sec1(der) => {
+            let curve = ec_curve(der).ok_or_else(|| {
+                ParseTlsError::new(TlsInput::Key, "the EC key does not name its curve")
+            })?;
+            ([EC_ALGORITHM, curve].concat(), der)
+        }
+    };
+    let mut info = vec![0x02, 0x01, 0x00];
+    info.extend(der_encode(SEQUENCE, &algorithm));
+    info.extend(der_encode(OCTET_STRING, key));
+    Ok(der_encode(SEQUENCE, &info))
+}
+
+/// The curve OID in the parameters of a SEC1 key, after its version and private key
+fn ec_curve(sec1: &[u8]) -> Option<&[u8]> {
+    let (SEQUENCE, mut key, _) = der_element(sec1)? else {
+        return None;
+    };
+    for _ in 0..2 {
+        key = der_element(key)?.2;
+    }
+    match der_element(key)? {
+        (EC_PARAMETERS, curve, _) if curve.first() == Some(&OID) => Some(curve),
+        _ => None,
+    }
+}
+
+fn der_encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        der.push(0x80 | (bytes.len() - skip) as u8);
+        der.extend(&bytes[skip..]);
+    }
+    der.extend(contents);
+    der
+}
+
+fn pem_encode(label: &str, der: &[u8]) -> String {
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in base64_encode(der).as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_should_be_converted_to_pkcs8() {
+        let rsa = pkcs8(&PrivateKey::Pkcs1(vec![0x30, 0x00])).unwrap();
+        let mut info = vec![0x02, 0x01, 0x00];
+        info.extend(der_encode(SEQUENCE, RSA_ALGORITHM));
+        info.extend([OCTET_STRING, 0x02, 0x30, 0x00]);
+        assert_eq!(rsa, der_encode(SEQUENCE, &info));
+
+        // Rebuild the SEC1 key inside a PKCS#8 one, with the curve in its parameters
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["db.internal".to_owned()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let original = key.serialize_der();
+        let (_, info, _) = der_element(&original).unwrap();
+        let (_, algorithm, rest) = der_element(der_element(info).unwrap().2).unwrap();
+        let curve = der_element(algorithm).unwrap().2;
+        let (_, ec_key, _) = der_element(der_element(rest).unwrap().1).unwrap();
+        let (_, _, public_key) = der_element(der_element(ec_key).unwrap().2).unwrap();
+        let private_key = &ec_key[..ec_key.len() - public_key.len()];
+        let sec1 = der_encode(
+            SEQUENCE,
+            &[private_key, &der_encode(EC_PARAMETERS, curve), public_key].concat(),
+        );
+
+        let pem = pem_encode("EC PRIVATE KEY", &sec1);
+        assert!(identity(cert.pem().as_bytes(), pem.as_bytes()).is_ok());
+
+        let without_curve = PrivateKey::Sec1(der_encode(SEQUENCE, private_key));
+        assert_eq!(pkcs8(&without_curve).unwrap_err().input, TlsInput::Key);
+    }
+}
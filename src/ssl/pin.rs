@@ -0,0 +1,227 @@
+//! Pinning of the server certificate by its SHA-256 fingerprint, or by the fingerprint of its
+//! public key (SPKI) which survives a renewal with the same key.
+
+use std::{fmt, str::FromStr};
+
+use sha2::{Digest, Sha256};
+
+/// A SHA-256 digest the server certificate must match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TlsPin {
+    /// The digest of the DER leaf certificate, as printed by
+    /// `openssl x509 -noout -fingerprint -sha256`
+    Certificate([u8; 32]),
+    /// The digest of the DER subject public key info, written `sha256//<base64>` like curl's
+    /// `--pinnedpubkey`
+    Spki([u8; 32]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "failed to parse the tls pin from {0}, expected a hex sha256 fingerprint or sha256//<base64>"
+)]
+pub struct ParseTlsPinError(String);
+
+/// The server certificate matched none of the pins
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "the server certificate matches no pin, it has the fingerprint {} and the public key pin {}",
+    TlsPin::Certificate(*certificate),
+    spki.map_or_else(|| "<unknown>".to_owned(), |spki| TlsPin::Spki(spki).to_string())
+)]
+pub struct PinMismatch {
+    pub certificate: [u8; 32],
+    /// The SPKI digest, unknown when the certificate could not be parsed
+    pub spki: Option<[u8; 32]>,
+}
+
+impl TlsPin {
+    /// Whether the DER certificate matches the pin
+    pub fn matches(&self, der: &[u8]) -> bool {
+        match self {
+            Self::Certificate(digest) => Sha256::digest(der)[..] == digest[..],
+            Self::Spki(digest) => subject_public_key_info(der)
+                .is_some_and(|spki| Sha256::digest(spki)[..] == digest[..]),
+        }
+    }
+}
+
+/// Checks the DER leaf certificate of the server against a set of pins, any of which may match
+pub(crate) fn check(pins: &[TlsPin], der: &[u8]) -> Result<(), PinMismatch> {
+    if pins.iter().any(|pin| pin.matches(der)) {
+        return Ok(());
+    }
+    Err(PinMismatch {
+        certificate: Sha256::digest(der).into(),
+        spki: subject_public_key_info(der).map(|spki| Sha256::digest(spki).into()),
+    })
+}
+
+impl fmt::Display for TlsPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Certificate(digest) => {
+                for (i, b) in digest.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(":")?;
+                    }
+                    write!(f, "{:02X}", b)?;
+                }
+                Ok(())
+            }
+            Self::Spki(digest) => write!(f, "sha256//{}", base64_encode(digest)),
+        }
+    }
+}
+
+impl FromStr for TlsPin {
+    type Err = ParseTlsPinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseTlsPinError(s.into());
+        if let Some(encoded) = s.strip_prefix("sha256//") {
+            let digest = base64_decode(encoded).ok_or_else(err)?;
+            return Ok(Self::Spki(digest.try_into().map_err(|_| err())?));
+        }
+
+        let hex: Vec<u8> = s.bytes().filter(|&b| b != b':').collect();
+        if hex.len() != 64 {
+            return Err(err());
+        }
+        let mut digest = [0; 32];
+        for (b, pair) in digest.iter_mut().zip(hex.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| err())?;
+            *b = u8::from_str_radix(pair, 16).map_err(|_| err())?;
+        }
+        Ok(Self::Certificate(digest))
+    }
+}
+
+/// Reads a DER tag and length, returning the tag, the contents and what follows them
+pub(super) fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&len, mut rest) = rest.split_first()?;
+    let len = if len < 0x80 {
+        len as usize
+    } else {
+        let count = (len & 0x7F) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let (bytes, tail) = rest.split_at(count);
+        rest = tail;
+        bytes.iter().fold(0, |len, &b| len << 8 | b as usize)
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(len);
+    Some((tag, contents, rest))
+}
+
+/// The DER subject public key info of a certificate, the seventh element of the TBS
+/// certificate when the explicit version is present
+fn subject_public_key_info(der: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xA0;
+
+    let (SEQUENCE, certificate, _) = der_element(der)? else {
+        return None;
+    };
+    let (SEQUENCE, mut tbs, _) = der_element(certificate)? else {
+        return None;
+    };
+    if tbs.first() == Some(&VERSION) {
+        tbs = der_element(tbs)?.2;
+    }
+    // The serial number, the signature algorithm, the issuer, the validity and the subject
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.2;
+    }
+    let (SEQUENCE, _, rest) = der_element(tbs)? else {
+        return None;
+    };
+    Some(&tbs[..tbs.len() - rest.len()])
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(super) fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub(super) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(encoded.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for &c in encoded {
+        let value = BASE64.iter().position(|&b| b == c)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_should_round_trip_through_strings() {
+        let certificate = TlsPin::Certificate([0xAB; 32]);
+        let text = certificate.to_string();
+        assert!(text.starts_with("AB:AB:"));
+        assert_eq!(text.parse::<TlsPin>().unwrap(), certificate);
+        assert_eq!(
+            text.replace(':', "").parse::<TlsPin>().unwrap(),
+            certificate
+        );
+
+        let spki = TlsPin::Spki(std::array::from_fn(|i| i as u8));
+        assert_eq!(
+            spki.to_string(),
+            "sha256//AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8="
+        );
+        assert_eq!(spki.to_string().parse::<TlsPin>().unwrap(), spki);
+
+        assert!("sha256//AAEC".parse::<TlsPin>().is_err());
+        assert!("AB:CD".parse::<TlsPin>().is_err());
+    }
+
+    #[test]
+    fn certificates_should_be_checked_against_the_pins() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["db.internal".to_owned()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let spki = TlsPin::Spki(Sha256::digest(key.public_key_der()).into());
+        let certificate = TlsPin::Certificate(Sha256::digest(cert.der()).into());
+
+        assert!(check(&[spki], cert.der()).is_ok());
+        assert!(check(&[TlsPin::Spki([0; 32]), certificate], cert.der()).is_ok());
+
+        let mismatch = check(&[TlsPin::Certificate([0; 32])], cert.der()).unwrap_err();
+        assert_eq!(TlsPin::Certificate(mismatch.certificate), certificate);
+        assert_eq!(mismatch.spki.map(TlsPin::Spki), Some(spki));
+        assert!(mismatch.to_string().contains(&spki.to_string()));
+    }
+}
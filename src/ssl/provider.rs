@@ -0,0 +1,184 @@
+//! A refreshable source of TLS connectors, so that long-lived pools pick up rotated
+//! certificates without a restart.
+
+use std::{
+    fmt,
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use crate::config::{LoadSourceError, TlsConfig};
+
+use super::{build_connector, TlsConnector, TlsMode, UpgradeError};
+
+/// Holds the connector built from a [`TlsConfig`] until it is reloaded, either explicitly with
+/// [`TlsProvider::reload`] or when its files change with [`TlsProvider::watch`]. New
+/// connections use the current connector while open ones keep their TLS session.
+///
+/// Cloning the provider is cheap and every clone shares the same connector.
+#[derive(Clone)]
+pub struct TlsProvider {
+    inner: Arc<ProviderInner>,
+}
+
+struct ProviderInner {
+    config: TlsConfig,
+    connector: RwLock<Option<TlsConnector>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReloadError {
+    #[error("failed to load the tls material")]
+    Load(#[from] LoadSourceError),
+
+    #[error("failed to build the tls connector")]
+    Upgrade(#[from] UpgradeError),
+}
+
+impl TlsProvider {
+    /// Loads the material of the configuration and builds its first connector
+    pub fn new(config: TlsConfig) -> Result<Self, ReloadError> {
+        let connector = build(&config)?;
+        Ok(Self {
+            inner: Arc::new(ProviderInner {
+                config,
+                connector: RwLock::new(connector),
+            }),
+        })
+    }
+
+    pub fn config(&self) -> &TlsConfig {
+        &self.inner.config
+    }
+
+    /// Reads the material again and swaps the connector. On failure the previous connector is
+    /// kept, so a half-written rotation does not break new connections.
+    pub fn reload(&self) -> Result<(), ReloadError> {
+        self.inner.reload()
+    }
+
+    /// Reloads whenever a file of the material is modified, checking every `interval`. The task
+    /// ends once every clone of the provider is dropped.
+    pub fn watch(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(ProviderInner::watch(Arc::downgrade(&self.inner), interval))
+    }
+
+    /// The current connector, `None` when TLS is disabled
+    pub(crate) fn connector(&self) -> Option<TlsConnector> {
+        self.inner
+            .connector
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl ProviderInner {
+    fn reload(&self) -> Result<(), ReloadError> {
+        let connector = build(&self.config)?;
+        *self.connector.write().unwrap_or_else(|e| e.into_inner()) = connector;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Reloaded the tls material");
+        Ok(())
+    }
+
+    /// The modification times of the files, `None` for those that cannot be read
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.config
+            .files()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    async fn watch(provider: Weak<ProviderInner>, interval: Duration) {
+        let mut modified = match provider.upgrade() {
+            Some(provider) => provider.modified(),
+            None => return,
+        };
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let Some(provider) = provider.upgrade() else {
+                break;
+            };
+
+            let now = provider.modified();
+            if now == modified {
+                continue;
+            }
+            modified = now;
+            if let Err(_err) = provider.reload() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("Failed to reload the tls material: {}", _err);
+            }
+        }
+    }
+}
+
+fn build(config: &TlsConfig) -> Result<Option<TlsConnector>, ReloadError> {
+    if config.mode() == TlsMode::Disable {
+        return Ok(None);
+    }
+    let loaded = config.load()?;
+    let connector = build_connector(&config.options(&loaded)).map_err(|err| config.locate(err))?;
+    Ok(Some(connector))
+}
+
+impl fmt::Debug for TlsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsProvider")
+            .field("config", &self.inner.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for TlsProvider {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for TlsProvider {}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        ssl::{TlsInput, TlsRoots},
+    };
+
+    #[tokio::test]
+    async fn reloads_should_keep_the_last_valid_connector() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let ca = rcgen::CertificateParams::new(Vec::<String>::new())
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("dibi-provider-{}.pem", std::process::id()));
+        std::fs::write(&path, ca.pem()).unwrap();
+
+        let config = Config::builder()
+            .tls_mode(TlsMode::VerifyCa)
+            .tls_roots(TlsRoots::Custom)
+            .tls_root(path.clone())
+            .build()
+            .unwrap();
+        let provider = TlsProvider::new(config.tls().clone()).unwrap();
+        assert!(provider.connector().is_some());
+
+        std::fs::write(&path, "not a certificate").unwrap();
+        let err = provider.reload().unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            err,
+            ReloadError::Upgrade(UpgradeError::Parse(err))
+                if err.input == TlsInput::Root(0) && err.path == Some(PathBuf::from(&path))
+        ));
+        assert!(provider.connector().is_some());
+    }
+}
@@ -0,0 +1,399 @@
+//! Prepared statements executed with the binary protocol, and server-side cursors that fetch
+//! their rows in batches.
+
+use std::sync::Arc;
+
+use futures::Stream;
+
+use crate::{
+    connection::{CommandError, Connection, ResultHead},
+    protocol::{
+        client::com::{ComStmtClose, ComStmtExecute, ComStmtFetch, ComStmtPrepare, CursorType},
+        server::StmtPrepareOk,
+        ColumnDefinition, ServerStatus,
+    },
+    result::{ResultSet, Row},
+    value::Value,
+    DecodePacket,
+};
+
+/// A statement prepared with `COM_STMT_PREPARE`. It stays allocated on the server until it is
+/// closed with [`Connection::close_statement`] or the connection ends.
+#[derive(Debug)]
+pub struct Statement {
+    id: u32,
+    params: Arc<[ColumnDefinition]>,
+    columns: Arc<[ColumnDefinition]>,
+    /// Whether the statement calls a stored procedure, which may return several result sets
+    call: bool,
+}
+
+impl Statement {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The definitions of the `?` placeholders
+    pub fn params(&self) -> &[ColumnDefinition] {
+        &self.params
+    }
+
+    /// The columns of the result set, empty for statements without rows
+    pub fn columns(&self) -> &[ColumnDefinition] {
+        &self.columns
+    }
+
+    pub(crate) fn is_call(&self) -> bool {
+        self.call
+    }
+
+    pub(crate) fn check_params(&self, params: &[Value]) -> Result<(), CommandError> {
+        if params.len() != self.params.len() {
+            return Err(CommandError::ParamCount {
+                expected: self.params.len(),
+                actual: params.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Executes the statement with a read only cursor, leaving the rows on the server until
+    /// they are fetched `fetch_size` at a time with [`Cursor::next_batch`]
+    pub async fn cursor(
+        &self,
+        connection: &mut Connection,
+        params: &[Value],
+        fetch_size: u32,
+    ) -> Result<Cursor, CommandError> {
+        self.check_params(params)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending statement execute packet with a cursor");
+        connection
+            .send(ComStmtExecute::new(self.id, CursorType::ReadOnly, params))
+            .await?;
+
+        let mut cursor = Cursor {
+            statement_id: self.id,
+            columns: self.columns.clone(),
+            fetch_size: fetch_size.max(1),
+            buffered: None,
+            done: true,
+        };
+        match connection.recv_result_head().await? {
+            ResultHead::Ok(_) => {}
+            ResultHead::Columns(columns) => {
+                cursor.columns = columns;
+                if connection
+                    .status_flags()
+                    .contains(ServerStatus::CURSOR_EXISTS)
+                {
+                    cursor.done = false;
+                } else {
+                    // The server ignores the cursor for some statements and sends the rows
+                    // right away
+                    let mut rows = Vec::new();
+                    connection
+                        .recv_rows(&cursor.columns, Row::decode_binary, &mut rows)
+                        .await?;
+                    cursor.buffered = Some(rows);
+                }
+            }
+        }
+        Ok(cursor)
+    }
+}
+
+/// An open server-side cursor of an executed [`Statement`]. Other commands can run on the
+/// connection between two batches.
+#[derive(Debug)]
+pub struct Cursor {
+    statement_id: u32,
+    columns: Arc<[ColumnDefinition]>,
+    fetch_size: u32,
+    buffered: Option<Vec<Row>>,
+    done: bool,
+}
+
+impl Cursor {
+    pub fn columns(&self) -> &[ColumnDefinition] {
+        &self.columns
+    }
+
+    /// Whether every row was fetched
+    pub fn is_done(&self) -> bool {
+        self.done && self.buffered.is_none()
+    }
+
+    /// Fetches the next batch of rows with `COM_STMT_FETCH`, or `None` once the cursor is
+    /// exhausted
+    pub async fn next_batch(
+        &mut self,
+        connection: &mut Connection,
+    ) -> Result<Option<Vec<Row>>, CommandError> {
+        if let Some(rows) = self.buffered.take() {
+            return Ok(Some(rows).filter(|rows| !rows.is_empty()));
+        }
+        if self.done {
+            return Ok(None);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending statement fetch packet");
+        connection
+            .send(ComStmtFetch::new(self.statement_id, self.fetch_size))
+            .await?;
+        let mut rows = Vec::with_capacity(self.fetch_size as usize);
+        let status = connection
+            .recv_rows(&self.columns, Row::decode_binary, &mut rows)
+            .await?;
+        if status.status_flags.contains(ServerStatus::LAST_ROW_SENT)
+            || !status.status_flags.contains(ServerStatus::CURSOR_EXISTS)
+        {
+            self.done = true;
+        }
+        Ok(Some(rows).filter(|rows| !rows.is_empty()))
+    }
+
+    /// Streams the batches of the cursor. The connection is borrowed until the stream is
+    /// dropped; use [`Cursor::next_batch`] to run other commands in between.
+    pub fn batches<'a>(
+        &'a mut self,
+        connection: &'a mut Connection,
+    ) -> impl Stream<Item = Result<Vec<Row>, CommandError>> + 'a {
+        futures::stream::unfold(Some((self, connection)), |state| async move {
+            let (cursor, connection) = state?;
+            match cursor.next_batch(connection).await {
+                Ok(Some(rows)) => Some((Ok(rows), Some((cursor, connection)))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+}
+
+/// Whether a statement is a `CALL`, ignoring leading whitespace and case
+pub(crate) fn is_call(query: &str) -> bool {
+    query
+        .trim_start()
+        .get(..4)
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("call"))
+}
+
+impl Connection {
+    /// Prepares a statement with `?` placeholders for its parameters
+    pub async fn prepare(&mut self, query: &str) -> Result<Statement, CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending statement prepare packet");
+        self.send(ComStmtPrepare::new(query)).await?;
+
+        let packet = self.recv_non_err().await?;
+        let ok = StmtPrepareOk::decode_packet(packet, self.stream().context())?;
+        let params = self.recv_columns(ok.num_params.into()).await?;
+        let columns = self.recv_columns(ok.num_columns.into()).await?;
+        Ok(Statement {
+            id: ok.statement_id,
+            params,
+            columns,
+            call: is_call(query),
+        })
+    }
+
+    /// Executes a prepared statement and returns its first result set, discarding any
+    /// following ones
+    pub async fn execute(
+        &mut self,
+        statement: &Statement,
+        params: &[Value],
+    ) -> Result<ResultSet, CommandError> {
+        let mut results = self.execute_multi(statement, params).await?;
+        Ok(if results.is_empty() {
+            ResultSet::default()
+        } else {
+            results.swap_remove(0)
+        })
+    }
+
+    /// Executes a prepared statement and returns every result set it produced, e.g. for a
+    /// `CALL` of a stored procedure
+    pub async fn execute_multi(
+        &mut self,
+        statement: &Statement,
+        params: &[Value],
+    ) -> Result<Vec<ResultSet>, CommandError> {
+        self.send_execute(statement, params).await?;
+
+        self.recv_results(Row::decode_binary).await
+    }
+
+    /// Sends `COM_STMT_EXECUTE` without a cursor
+    pub(crate) async fn send_execute(
+        &mut self,
+        statement: &Statement,
+        params: &[Value],
+    ) -> Result<(), CommandError> {
+        statement.check_params(params)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending statement execute packet");
+        self.send(ComStmtExecute::new(
+            statement.id,
+            CursorType::NoCursor,
+            params,
+        ))
+        .await
+    }
+
+    /// Deallocates a prepared statement with `COM_STMT_CLOSE`
+    pub async fn close_statement(&mut self, statement: Statement) -> Result<(), CommandError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Sending statement close packet");
+        self.send(ComStmtClose::new(statement.id)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::io::DuplexStream;
+
+    use super::*;
+    use crate::connection::tests::{
+        binary_row, column, connect_in_memory, eof, err, ok, prepare_ok, read_packet, respond,
+    };
+
+    /// Answers `COM_STMT_PREPARE` with statement 1 and a single `id` column
+    async fn answer_prepare(server: &mut DuplexStream) {
+        let (_, prepare) = read_packet(server).await;
+        assert_eq!(prepare[0], 0x16);
+        respond(
+            server,
+            &[
+                prepare_ok(1, 0),
+                column("id"),
+                eof(ServerStatus::AUTOCOMMIT),
+            ],
+        )
+        .await;
+    }
+
+    fn ids(rows: &[Row]) -> Vec<i64> {
+        rows.iter()
+            .map(|row| match row.get(0) {
+                Some(Value::Int(id)) => *id,
+                value => panic!("unexpected value {value:?}"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn cursors_should_fetch_until_the_last_row_is_sent() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let open = ServerStatus::AUTOCOMMIT | ServerStatus::CURSOR_EXISTS;
+        let server = tokio::spawn(async move {
+            answer_prepare(&mut server).await;
+            let (_, execute) = read_packet(&mut server).await;
+            respond(&mut server, &[vec![1], column("id"), eof(open)]).await;
+
+            let (_, first) = read_packet(&mut server).await;
+            respond(&mut server, &[binary_row(1), binary_row(2), eof(open)]).await;
+            let (_, ping) = read_packet(&mut server).await;
+            respond(&mut server, &[ok(ServerStatus::AUTOCOMMIT)]).await;
+            let (_, second) = read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[binary_row(3), eof(open | ServerStatus::LAST_ROW_SENT)],
+            )
+            .await;
+            (execute, first, ping, second)
+        });
+
+        let statement = connection.prepare("SELECT id FROM t").await.unwrap();
+        let mut cursor = statement.cursor(&mut connection, &[], 2).await.unwrap();
+        assert!(!cursor.is_done());
+        let batch = cursor.next_batch(&mut connection).await.unwrap().unwrap();
+        assert_eq!(ids(&batch), [1, 2]);
+        // Another command runs between two fetches
+        connection.ping().await.unwrap();
+        let batch = cursor.next_batch(&mut connection).await.unwrap().unwrap();
+        assert_eq!(ids(&batch), [3]);
+        assert!(cursor.is_done());
+        assert!(cursor.next_batch(&mut connection).await.unwrap().is_none());
+
+        let (execute, first, ping, second) = server.await.unwrap();
+        // Statement 1 with a read only cursor
+        assert_eq!(execute[..6], [0x17, 1, 0, 0, 0, 1]);
+        assert_eq!(first, [0x1C, 1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(ping, [0x0E]);
+        assert_eq!(second, first);
+    }
+
+    #[tokio::test]
+    async fn cursors_should_buffer_the_rows_when_the_server_ignores_them() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let server = tokio::spawn(async move {
+            answer_prepare(&mut server).await;
+            read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[
+                    vec![1],
+                    column("id"),
+                    eof(ServerStatus::AUTOCOMMIT),
+                    binary_row(1),
+                    binary_row(2),
+                    eof(ServerStatus::AUTOCOMMIT),
+                ],
+            )
+            .await;
+            // Nothing is fetched, so the next command is the ping
+            let (_, ping) = read_packet(&mut server).await;
+            respond(&mut server, &[ok(ServerStatus::AUTOCOMMIT)]).await;
+            ping
+        });
+
+        let statement = connection.prepare("SELECT id FROM t").await.unwrap();
+        let mut cursor = statement.cursor(&mut connection, &[], 1).await.unwrap();
+        let batches = cursor
+            .batches(&mut connection)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(ids(&batches[0]), [1, 2]);
+        assert!(cursor.is_done());
+
+        connection.ping().await.unwrap();
+        assert_eq!(server.await.unwrap(), [0x0E]);
+    }
+
+    #[tokio::test]
+    async fn cursor_batches_should_end_after_an_error() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let open = ServerStatus::AUTOCOMMIT | ServerStatus::CURSOR_EXISTS;
+        tokio::spawn(async move {
+            answer_prepare(&mut server).await;
+            read_packet(&mut server).await;
+            respond(&mut server, &[vec![1], column("id"), eof(open)]).await;
+            read_packet(&mut server).await;
+            respond(&mut server, &[binary_row(1), eof(open)]).await;
+            read_packet(&mut server).await;
+            respond(
+                &mut server,
+                &[err(1243, "Unknown prepared statement handler")],
+            )
+            .await;
+            server
+        });
+
+        let statement = connection.prepare("SELECT id FROM t").await.unwrap();
+        let mut cursor = statement.cursor(&mut connection, &[], 1).await.unwrap();
+        let batches = cursor.batches(&mut connection).collect::<Vec<_>>().await;
+        assert_eq!(batches.len(), 2);
+        assert_eq!(ids(batches[0].as_ref().unwrap()), [1]);
+        assert!(matches!(
+            &batches[1],
+            Err(CommandError::Server(err)) if err.code == 1243
+        ));
+    }
+}
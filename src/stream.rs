@@ -1,21 +1,55 @@
 use std::{
-    io,
+    fmt, io,
     pin::Pin,
+    sync::Mutex,
     task::{Context, Poll},
 };
+#[cfg(unix)]
+use tokio::net::UnixStream;
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
-    net::{TcpStream, UnixStream},
+    net::TcpStream,
 };
 
-#[derive(Debug)]
+/// A byte stream the protocol can run over, like an SSH channel, a vsock or
+/// [`tokio::io::duplex`]
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
 pub enum Stream {
     Tcp(TcpStream),
     #[cfg(unix)]
     Unix(UnixStream),
+    /// A transport opened by the user. The mutex is never locked, it only makes the stream
+    /// `Sync` as it is always accessed through `&mut`.
+    Custom(Mutex<Box<dyn Transport>>),
+}
+
+impl Stream {
+    pub fn custom(transport: impl Transport + 'static) -> Self {
+        Stream::Custom(Mutex::new(Box::new(transport)))
+    }
+}
+
+/// The transport of a custom stream, which cannot be poisoned as it is never locked
+fn transport(transport: &mut Mutex<Box<dyn Transport>>) -> Pin<&mut dyn Transport> {
+    let transport = transport.get_mut().unwrap_or_else(|e| e.into_inner());
+    Pin::new(&mut **transport)
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stream::Tcp(stream) => f.debug_tuple("Tcp").field(stream).finish(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => f.debug_tuple("Unix").field(stream).finish(),
+            Stream::Custom(_) => f.write_str("Custom"),
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum StreamType {
     #[default]
     Tcp,
@@ -33,6 +67,7 @@ impl AsyncRead for Stream {
             Stream::Tcp(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
             #[cfg(unix)]
             Stream::Unix(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Custom(ref mut stream) => transport(stream).poll_read(cx, buf),
         }
     }
 }
@@ -47,6 +82,7 @@ impl AsyncWrite for Stream {
             Stream::Tcp(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
             #[cfg(unix)]
             Stream::Unix(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Custom(ref mut stream) => transport(stream).poll_write(cx, buf),
         }
     }
 
@@ -55,6 +91,7 @@ impl AsyncWrite for Stream {
             Stream::Tcp(ref mut stream) => Pin::new(stream).poll_flush(cx),
             #[cfg(unix)]
             Stream::Unix(ref mut stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Custom(ref mut stream) => transport(stream).poll_flush(cx),
         }
     }
 
@@ -63,6 +100,7 @@ impl AsyncWrite for Stream {
             Stream::Tcp(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
             #[cfg(unix)]
             Stream::Unix(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Custom(ref mut stream) => transport(stream).poll_shutdown(cx),
         }
     }
 }
@@ -0,0 +1,62 @@
+use std::{future::Future, time::Duration};
+
+use tokio::time::Instant;
+
+/// The timeouts applied to a connection. Nothing is bounded by default.
+///
+/// A timeout firing in the middle of a packet leaves the packet sequence in an unknown state,
+/// so the connection is marked as broken and refuses any further command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Bounds opening the connection, including the TLS upgrade and authentication
+    pub connect: Option<Duration>,
+    /// Bounds waiting for any single packet from the server
+    pub read: Option<Duration>,
+    /// Bounds writing and flushing any single packet
+    pub write: Option<Duration>,
+    /// Bounds a whole command, from sending it until its last response packet
+    pub statement: Option<Duration>,
+    /// Issues `KILL QUERY` on a side connection when a command times out, so the server stops
+    /// working on it
+    pub cancel_on_timeout: bool,
+}
+
+/// The error wrapped in an [`std::io::Error`] when using a connection that has been poisoned
+/// by a timeout or an I/O failure.
+#[derive(Debug, thiserror::Error)]
+#[error("the connection is broken by a previous failure and can no longer be used")]
+pub struct BrokenConnection;
+
+impl BrokenConnection {
+    pub(crate) fn io_error() -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::NotConnected, BrokenConnection)
+    }
+
+    pub(crate) fn is_io_error(err: &std::io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<BrokenConnection>())
+    }
+}
+
+/// The earliest of two optional deadlines
+pub(crate) fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Runs the future until the deadline, failing with [`std::io::ErrorKind::TimedOut`]
+pub(crate) async fn io_deadline<F, T>(
+    deadline: Option<Instant>,
+    future: F,
+) -> Result<T, std::io::Error>
+where
+    F: Future<Output = Result<T, std::io::Error>>,
+{
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future)
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))?,
+        None => future.await,
+    }
+}
@@ -0,0 +1,419 @@
+//! Transactions guarded by [`Transaction`], with nested transactions mapped to savepoints
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    connection::{CommandError, Connection},
+    protocol::ServerStatus,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransactionError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("the connection is already in a transaction that was not started with begin")]
+    AlreadyInTransaction,
+
+    #[error("the server did not report a started transaction")]
+    NotStarted,
+
+    #[error("the server did not report the transaction as read only")]
+    NotReadOnly,
+
+    #[error("the transaction was ended by the server, e.g. by an implicit commit or a deadlock")]
+    Ended,
+
+    #[error("nested transactions cannot set an isolation level or access mode")]
+    NestedOptions,
+}
+
+/// The isolation level of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Whether a transaction may write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// The characteristics of a top level transaction started with [`Connection::begin_with`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransactionOptions {
+    pub isolation_level: Option<IsolationLevel>,
+    pub access_mode: Option<AccessMode>,
+    pub consistent_snapshot: bool,
+}
+
+impl TransactionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn isolation_level(mut self, level: IsolationLevel) -> Self {
+        self.isolation_level = Some(level);
+        self
+    }
+
+    pub fn read_only(mut self) -> Self {
+        self.access_mode = Some(AccessMode::ReadOnly);
+        self
+    }
+
+    pub fn read_write(mut self) -> Self {
+        self.access_mode = Some(AccessMode::ReadWrite);
+        self
+    }
+
+    /// Starts a consistent read right away instead of at the first read
+    pub fn consistent_snapshot(mut self) -> Self {
+        self.consistent_snapshot = true;
+        self
+    }
+
+    /// The `SET TRANSACTION` statement applying the isolation level to the next transaction
+    fn isolation_statement(&self) -> Option<String> {
+        self.isolation_level
+            .map(|level| format!("SET TRANSACTION ISOLATION LEVEL {}", level))
+    }
+
+    fn start_statement(&self) -> String {
+        let mut characteristics = Vec::new();
+        if self.consistent_snapshot {
+            characteristics.push("WITH CONSISTENT SNAPSHOT");
+        }
+        match self.access_mode {
+            Some(AccessMode::ReadOnly) => characteristics.push("READ ONLY"),
+            Some(AccessMode::ReadWrite) => characteristics.push("READ WRITE"),
+            None => {}
+        }
+
+        if characteristics.is_empty() {
+            "START TRANSACTION".to_owned()
+        } else {
+            format!("START TRANSACTION {}", characteristics.join(", "))
+        }
+    }
+}
+
+/// How deep the connection is in nested transactions, and which level a dropped guard left to
+/// be rolled back
+#[derive(Debug, Default)]
+pub(crate) struct TransactionState {
+    depth: usize,
+    pending_rollback: Option<usize>,
+}
+
+impl TransactionState {
+    /// Takes the level whose guard was dropped without commit, to roll back before the next
+    /// command
+    pub(crate) fn take_pending_rollback(&mut self) -> Option<usize> {
+        self.pending_rollback.take()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn finish(&mut self, level: usize) {
+        self.depth = level - 1;
+    }
+
+    fn abandon(&mut self, level: usize) {
+        self.finish(level);
+        self.pending_rollback = Some(self.pending_rollback.map_or(level, |l| l.min(level)));
+    }
+}
+
+/// The statement rolling back the given level, `ROLLBACK` for the top level transaction
+pub(crate) fn rollback_statement(level: usize) -> String {
+    if level <= 1 {
+        "ROLLBACK".to_owned()
+    } else {
+        format!("ROLLBACK TO SAVEPOINT {}", savepoint_name(level))
+    }
+}
+
+fn savepoint_name(level: usize) -> String {
+    format!("dibi_savepoint_{}", level - 1)
+}
+
+impl Connection {
+    /// Whether the server reports an open transaction
+    pub fn in_transaction(&self) -> bool {
+        self.status_flags().contains(ServerStatus::IN_TRANSACTION)
+    }
+
+    /// Starts a transaction, or a savepoint when called through an open [`Transaction`]
+    pub async fn begin(&mut self) -> Result<Transaction<'_>, TransactionError> {
+        self.begin_with(TransactionOptions::default()).await
+    }
+
+    /// Starts a transaction with the given characteristics. Nested transactions are savepoints
+    /// and only accept the default options. A read only transaction is rolled back again if
+    /// the server does not report it as such.
+    pub async fn begin_with(
+        &mut self,
+        options: TransactionOptions,
+    ) -> Result<Transaction<'_>, TransactionError> {
+        // A guard dropped earlier may still leave a transaction for the server to roll back
+        self.rollback_abandoned().await?;
+
+        let depth = self.transaction_mut().depth;
+        if depth > 0 {
+            if options != TransactionOptions::default() {
+                return Err(TransactionError::NestedOptions);
+            }
+            if !self.in_transaction() {
+                return Err(TransactionError::Ended);
+            }
+            let level = depth + 1;
+            self.query(&format!("SAVEPOINT {}", savepoint_name(level)))
+                .await?;
+            return Ok(Transaction::new(self, level));
+        }
+
+        if self.in_transaction() {
+            return Err(TransactionError::AlreadyInTransaction);
+        }
+        if let Some(statement) = options.isolation_statement() {
+            self.query(&statement).await?;
+        }
+        self.query(&options.start_statement()).await?;
+        if !self.in_transaction() {
+            return Err(TransactionError::NotStarted);
+        }
+        if options.access_mode == Some(AccessMode::ReadOnly)
+            && !self
+                .status_flags()
+                .contains(ServerStatus::IN_TRANS_READONLY)
+        {
+            self.query("ROLLBACK").await?;
+            return Err(TransactionError::NotReadOnly);
+        }
+        Ok(Transaction::new(self, 1))
+    }
+}
+
+/// An open transaction or savepoint. It derefs to the [`Connection`] to run statements in it.
+///
+/// Dropping the guard without [`commit`](Transaction::commit) or
+/// [`rollback`](Transaction::rollback) rolls it back before the next command on the connection.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    connection: &'a mut Connection,
+    level: usize,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+    fn new(connection: &'a mut Connection, level: usize) -> Self {
+        connection.transaction_mut().depth = level;
+        Self {
+            connection,
+            level,
+            finished: false,
+        }
+    }
+
+    /// Whether this guard is a savepoint inside another transaction
+    pub fn is_nested(&self) -> bool {
+        self.level > 1
+    }
+
+    /// Whether the server still reports the transaction as open
+    pub fn is_active(&self) -> bool {
+        self.connection.in_transaction()
+    }
+
+    /// Whether the server reports the transaction as read only
+    pub fn is_read_only(&self) -> bool {
+        self.connection
+            .status_flags()
+            .contains(ServerStatus::IN_TRANS_READONLY)
+    }
+
+    /// Commits the transaction, or releases the savepoint of a nested one
+    pub async fn commit(mut self) -> Result<(), TransactionError> {
+        let statement = if self.is_nested() {
+            format!("RELEASE SAVEPOINT {}", savepoint_name(self.level))
+        } else {
+            "COMMIT".to_owned()
+        };
+        self.finish(&statement).await
+    }
+
+    /// Rolls back the transaction, or to the savepoint of a nested one
+    pub async fn rollback(mut self) -> Result<(), TransactionError> {
+        let statement = rollback_statement(self.level);
+        self.finish(&statement).await
+    }
+
+    async fn finish(&mut self, statement: &str) -> Result<(), TransactionError> {
+        self.finished = true;
+        self.connection.transaction_mut().finish(self.level);
+        if !self.is_active() {
+            return Err(TransactionError::Ended);
+        }
+        self.connection.query(statement).await?;
+        Ok(())
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+    }
+}
+
+impl DerefMut for Transaction<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("Transaction dropped without commit, rolling back on next use");
+            self.connection.transaction_mut().abandon(self.level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{connect_in_memory, ok, read_packet, respond};
+
+    #[test]
+    fn options_should_build_statements() {
+        let options = TransactionOptions::new();
+        assert_eq!(options.isolation_statement(), None);
+        assert_eq!(options.start_statement(), "START TRANSACTION");
+
+        let options = TransactionOptions::new()
+            .isolation_level(IsolationLevel::Serializable)
+            .consistent_snapshot()
+            .read_only();
+        assert_eq!(
+            options.isolation_statement().as_deref(),
+            Some("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
+        );
+        assert_eq!(
+            options.start_statement(),
+            "START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY"
+        );
+    }
+
+    #[test]
+    fn dropped_guards_should_roll_back_the_outermost_level() {
+        let mut state = TransactionState {
+            depth: 3,
+            pending_rollback: None,
+        };
+        state.abandon(3);
+        assert_eq!(state.depth, 2);
+        state.abandon(2);
+        assert_eq!(state.depth, 1);
+        assert_eq!(state.take_pending_rollback(), Some(2));
+        assert_eq!(state.take_pending_rollback(), None);
+
+        assert_eq!(
+            rollback_statement(2),
+            "ROLLBACK TO SAVEPOINT dibi_savepoint_1"
+        );
+        assert_eq!(rollback_statement(1), "ROLLBACK");
+    }
+
+    #[tokio::test]
+    async fn transactions_should_follow_the_server_status() {
+        let (mut connection, mut server) = connect_in_memory().await;
+        let idle = ServerStatus::AUTOCOMMIT;
+        let open = idle | ServerStatus::IN_TRANSACTION;
+        let read_only = open | ServerStatus::IN_TRANS_READONLY;
+        let script = [
+            open, open, open, open, open, idle, read_only, idle, open, idle, idle, open, idle,
+        ];
+        let server = tokio::spawn(async move {
+            let mut commands = Vec::new();
+            for status in script {
+                let (_, command) = read_packet(&mut server).await;
+                commands.push(String::from_utf8_lossy(&command[1..]).into_owned());
+                respond(&mut server, &[ok(status)]).await;
+            }
+            commands
+        });
+
+        let mut transaction = connection.begin().await.unwrap();
+        assert!(transaction.is_active() && !transaction.is_read_only());
+        let savepoint = transaction.begin().await.unwrap();
+        assert!(savepoint.is_nested());
+        savepoint.commit().await.unwrap();
+        transaction.begin().await.unwrap().rollback().await.unwrap();
+        transaction.commit().await.unwrap();
+        assert!(!connection.in_transaction());
+
+        let options = TransactionOptions::new().read_only();
+        let transaction = connection.begin_with(options).await.unwrap();
+        assert!(transaction.is_read_only());
+        transaction.rollback().await.unwrap();
+
+        // The dropped guard is rolled back before the next command
+        drop(connection.begin().await.unwrap());
+        assert!(connection.in_transaction());
+        connection.ping().await.unwrap();
+
+        let err = connection.begin_with(options).await.unwrap_err();
+        assert!(matches!(err, TransactionError::NotReadOnly));
+
+        assert_eq!(
+            server.await.unwrap(),
+            [
+                "START TRANSACTION",
+                "SAVEPOINT dibi_savepoint_1",
+                "RELEASE SAVEPOINT dibi_savepoint_1",
+                "SAVEPOINT dibi_savepoint_1",
+                "ROLLBACK TO SAVEPOINT dibi_savepoint_1",
+                "COMMIT",
+                "START TRANSACTION READ ONLY",
+                "ROLLBACK",
+                "START TRANSACTION",
+                "ROLLBACK",
+                "",
+                "START TRANSACTION READ ONLY",
+                "ROLLBACK",
+            ]
+        );
+    }
+}
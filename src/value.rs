@@ -0,0 +1,158 @@
+use bytes::Bytes;
+
+/// A single column value of a row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bytes(Bytes),
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Self::Int(value) => Some(value),
+            Self::UInt(value) => i64::try_from(value).ok(),
+            Self::Bytes(_) => self.as_str()?.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::Int(value) => u64::try_from(value).ok(),
+            Self::UInt(value) => Some(value),
+            Self::Bytes(_) => self.as_str()?.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Float(value) => Some(value as f64),
+            Self::Double(value) => Some(value),
+            Self::Int(value) => Some(value as f64),
+            Self::UInt(value) => Some(value as f64),
+            Self::Bytes(_) => self.as_str()?.parse().ok(),
+            Self::Null => None,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($($ty:ty => $variant:ident as $as:ty),* $(,)?) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value as $as)
+                }
+            }
+        )*
+    };
+}
+
+impl_from! {
+    i8 => Int as i64,
+    i16 => Int as i64,
+    i32 => Int as i64,
+    i64 => Int as i64,
+    u8 => UInt as u64,
+    u16 => UInt as u64,
+    u32 => UInt as u64,
+    u64 => UInt as u64,
+    f32 => Float as f32,
+    f64 => Double as f64,
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Bytes(Bytes::copy_from_slice(value.as_bytes()))
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Bytes(value.into())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(Bytes::copy_from_slice(value))
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value.into())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+/// The first `fsp` digits of the microseconds after a dot, empty without fractional seconds
+fn format_fraction(micros: u32, fsp: u8) -> String {
+    if fsp == 0 {
+        return String::new();
+    }
+    let digits = format!("{:06}", micros);
+    format!(".{}", &digits[..(fsp as usize).min(6)])
+}
+
+/// Formats a `DATETIME` the way the text protocol sends it
+pub(crate) fn format_date_time(
+    (year, month, day): (u64, u64, u64),
+    (hour, minute, second): (u64, u64, u64),
+    micros: u32,
+    fsp: u8,
+) -> String {
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        format_fraction(micros, fsp)
+    )
+}
+
+/// Formats a `TIME` the way the text protocol sends it
+pub(crate) fn format_time(
+    negative: bool,
+    (hour, minute, second): (u64, u64, u64),
+    micros: u32,
+    fsp: u8,
+) -> String {
+    format!(
+        "{}{:02}:{:02}:{:02}{}",
+        if negative { "-" } else { "" },
+        hour,
+        minute,
+        second,
+        format_fraction(micros, fsp)
+    )
+}
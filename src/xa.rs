@@ -0,0 +1,261 @@
+//! [XA transactions](https://dev.mysql.com/doc/refman/8.0/en/xa.html) for coordinating a
+//! transaction branch across several servers
+
+use std::fmt;
+
+use crate::{
+    connection::{CommandError, Connection},
+    result::Row,
+};
+
+/// The longest global transaction id and branch qualifier a server accepts
+pub const MAX_XID_PART_LEN: usize = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum XidError {
+    #[error("the global transaction id is {0} bytes, at most {MAX_XID_PART_LEN} are allowed")]
+    GtridLength(usize),
+
+    #[error("the branch qualifier is {0} bytes, at most {MAX_XID_PART_LEN} are allowed")]
+    BqualLength(usize),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum XaError {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    #[error("XA RECOVER returned a malformed row")]
+    MalformedRecoverRow(#[source] Option<XidError>),
+}
+
+/// An XA transaction id, made of a global transaction id, a branch qualifier and a format id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Xid {
+    gtrid: Vec<u8>,
+    bqual: Vec<u8>,
+    format_id: i64,
+}
+
+impl Xid {
+    /// The format id used by servers when none is given
+    pub const DEFAULT_FORMAT_ID: i64 = 1;
+
+    /// Creates an id with an empty branch qualifier and the default format id
+    pub fn new(gtrid: impl Into<Vec<u8>>) -> Result<Self, XidError> {
+        Self::with_branch(gtrid, Vec::new(), Self::DEFAULT_FORMAT_ID)
+    }
+
+    pub fn with_branch(
+        gtrid: impl Into<Vec<u8>>,
+        bqual: impl Into<Vec<u8>>,
+        format_id: i64,
+    ) -> Result<Self, XidError> {
+        let gtrid = gtrid.into();
+        let bqual = bqual.into();
+        if gtrid.is_empty() || gtrid.len() > MAX_XID_PART_LEN {
+            return Err(XidError::GtridLength(gtrid.len()));
+        }
+        if bqual.len() > MAX_XID_PART_LEN {
+            return Err(XidError::BqualLength(bqual.len()));
+        }
+        Ok(Self {
+            gtrid,
+            bqual,
+            format_id,
+        })
+    }
+
+    pub fn gtrid(&self) -> &[u8] {
+        &self.gtrid
+    }
+
+    pub fn bqual(&self) -> &[u8] {
+        &self.bqual
+    }
+
+    pub fn format_id(&self) -> i64 {
+        self.format_id
+    }
+
+    /// Parses a row of `XA RECOVER`: `formatID`, `gtrid_length`, `bqual_length` and `data`
+    /// holding both parts back to back
+    fn from_recover_row(row: &Row) -> Result<Self, XaError> {
+        let malformed = || XaError::MalformedRecoverRow(None);
+        let format_id = row.get(0).and_then(|v| v.as_i64()).ok_or_else(malformed)?;
+        let len = |i| {
+            row.get(i)
+                .and_then(|v| v.as_u64())
+                .and_then(|len| usize::try_from(len).ok())
+                .ok_or_else(malformed)
+        };
+        let (gtrid_len, bqual_len) = (len(1)?, len(2)?);
+        let data = row
+            .get(3)
+            .and_then(|v| v.as_bytes())
+            .ok_or_else(malformed)?;
+        if gtrid_len.checked_add(bqual_len) != Some(data.len()) {
+            return Err(malformed());
+        }
+
+        let (gtrid, bqual) = data.split_at(gtrid_len);
+        Self::with_branch(gtrid, bqual, format_id)
+            .map_err(|err| XaError::MalformedRecoverRow(Some(err)))
+    }
+}
+
+/// Formats the id as the SQL literal `X'<gtrid>',X'<bqual>',<formatID>`
+impl fmt::Display for Xid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("X'")?;
+        for b in &self.gtrid {
+            write!(f, "{:02x}", b)?;
+        }
+        f.write_str("',X'")?;
+        for b in &self.bqual {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, "',{}", self.format_id)
+    }
+}
+
+impl Connection {
+    /// Starts an XA transaction branch with `XA START`
+    pub async fn xa_start(&mut self, xid: &Xid) -> Result<(), XaError> {
+        self.xa(&format!("XA START {}", xid)).await
+    }
+
+    /// Ends the active branch with `XA END`, moving it to the idle state
+    pub async fn xa_end(&mut self, xid: &Xid) -> Result<(), XaError> {
+        self.xa(&format!("XA END {}", xid)).await
+    }
+
+    /// Prepares an idle branch with `XA PREPARE` for the second phase of the commit
+    pub async fn xa_prepare(&mut self, xid: &Xid) -> Result<(), XaError> {
+        self.xa(&format!("XA PREPARE {}", xid)).await
+    }
+
+    /// Commits a prepared branch with `XA COMMIT`
+    pub async fn xa_commit(&mut self, xid: &Xid) -> Result<(), XaError> {
+        self.xa(&format!("XA COMMIT {}", xid)).await
+    }
+
+    /// Prepares and commits an idle branch at once with `XA COMMIT ... ONE PHASE`
+    pub async fn xa_commit_one_phase(&mut self, xid: &Xid) -> Result<(), XaError> {
+        self.xa(&format!("XA COMMIT {} ONE PHASE", xid)).await
+    }
+
+    /// Rolls back an idle or prepared branch with `XA ROLLBACK`
+    pub async fn xa_rollback(&mut self, xid: &Xid) -> Result<(), XaError> {
+        self.xa(&format!("XA ROLLBACK {}", xid)).await
+    }
+
+    /// Lists the prepared branches of the server with `XA RECOVER`, e.g. to resolve in-doubt
+    /// branches after a crash of the coordinator
+    pub async fn xa_recover(&mut self) -> Result<Vec<Xid>, XaError> {
+        let result = self.query("XA RECOVER").await?;
+        result.rows.iter().map(Xid::from_recover_row).collect()
+    }
+
+    async fn xa(&mut self, statement: &str) -> Result<(), XaError> {
+        self.query(statement).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::{
+        codec::PacketFrame,
+        protocol::{ColumnDefinition, ColumnFlags, ColumnType},
+        BufMutExt,
+    };
+
+    /// A text row of `XA RECOVER` with the given `formatID`, `gtrid_length`, `bqual_length`
+    /// and `data`
+    fn recover_row(format_id: &str, gtrid_len: &str, bqual_len: &str, data: &[u8]) -> Row {
+        let column = |name: &str| ColumnDefinition {
+            schema: String::new(),
+            table_alias: String::new(),
+            table: String::new(),
+            name: name.into(),
+            org_name: name.into(),
+            collation: 63,
+            length: 0,
+            column_type: ColumnType::VarString,
+            flags: ColumnFlags::empty(),
+            decimals: 0,
+        };
+        let columns: Arc<[ColumnDefinition]> = ["formatID", "gtrid_length", "bqual_length", "data"]
+            .map(column)
+            .into();
+        let mut bytes = BytesMut::new();
+        bytes.put_len_encoded_str(format_id);
+        bytes.put_len_encoded_str(gtrid_len);
+        bytes.put_len_encoded_str(bqual_len);
+        bytes.put_len_encoded_str(data);
+        Row::decode_text(PacketFrame::new(bytes.freeze()), columns).unwrap()
+    }
+
+    #[test]
+    fn recover_rows_should_split_the_data() {
+        let xid = Xid::from_recover_row(&recover_row("7", "8", "2", b"order-42b1")).unwrap();
+        assert_eq!(xid, Xid::with_branch("order-42", "b1", 7).unwrap());
+
+        // The parts are binary and may hold quotes, backslashes and NUL bytes
+        let data = [0x00, 0x27, 0x5C, 0xFF, 0x00];
+        let xid = Xid::from_recover_row(&recover_row("-1", "3", "2", &data)).unwrap();
+        assert_eq!(xid.gtrid(), [0x00, 0x27, 0x5C]);
+        assert_eq!(xid.bqual(), [0xFF, 0x00]);
+        assert_eq!(xid.format_id(), -1);
+
+        let xid = Xid::from_recover_row(&recover_row("1", "3", "0", b"abc")).unwrap();
+        assert!(xid.bqual().is_empty());
+    }
+
+    #[test]
+    fn recover_rows_with_bad_lengths_should_fail() {
+        let malformed = |row: Row| Xid::from_recover_row(&row).unwrap_err();
+        for row in [
+            recover_row("1", "3", "3", b"abcde"),
+            recover_row("1", "9", "0", b"abc"),
+            recover_row("1", &u64::MAX.to_string(), "1", b"abc"),
+            recover_row("1", "-1", "4", b"abc"),
+            recover_row("1", "x", "0", b"abc"),
+            recover_row("one", "3", "0", b"abc"),
+        ] {
+            assert!(matches!(malformed(row), XaError::MalformedRecoverRow(None)));
+        }
+
+        assert!(matches!(
+            malformed(recover_row("1", "0", "2", b"ab")),
+            XaError::MalformedRecoverRow(Some(XidError::GtridLength(0)))
+        ));
+        let data = vec![b'a'; 66];
+        assert!(matches!(
+            malformed(recover_row("1", "1", "65", &data)),
+            XaError::MalformedRecoverRow(Some(XidError::BqualLength(65)))
+        ));
+    }
+
+    #[test]
+    fn xid_should_format_as_sql_literal() {
+        let xid = Xid::with_branch("order-42", [0x00, 0xff], 7).unwrap();
+        assert_eq!(xid.to_string(), "X'6f726465722d3432',X'00ff',7");
+        assert_eq!(Xid::new("a").unwrap().to_string(), "X'61',X'',1");
+    }
+
+    #[test]
+    fn invalid_xid_should_fail() {
+        assert!(matches!(Xid::new(""), Err(XidError::GtridLength(0))));
+        assert!(matches!(
+            Xid::with_branch("a", vec![0; 65], 1),
+            Err(XidError::BqualLength(65))
+        ));
+    }
+}